@@ -1,89 +1,1491 @@
 pub mod error;
+pub mod handle;
 
-use crate::repository;
-use crate::repository::{in_memory::InMemoryRepository, ChallengeDTO, UserDTO};
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::sync::{mpsc, oneshot};
+
+use crate::clock::{Clock, SystemClock};
+use crate::rate_limit::{RateLimitPolicy, RateLimiter};
+use crate::repository::error::RepositoryError;
+use crate::repository::{
+    ChallengeDTO, ChallengeRepository, ClientMetadataDTO, CommitmentDTO, CredentialDTO,
+    KeyAgreementDTO, PendingChallengeDTO, PendingChallengeRepository, SessionDTO,
+    SessionRepository, UserDTO, UserRepository,
+};
+use crate::sealing::KeySealer;
+use crate::token::{TokenClaims, TokenSigner, VerificationKey};
 
 use error::AuthActorError;
+use handle::LimitRequest;
+
+use zkp::ake::{key_exchange_transcript, KeyExchange};
+use zkp::chaum_pedersen::{ChaumPedersenProtocol, Context, Proof};
+use zkp::okamoto::OkamotoProtocol;
+use zkp::rotation::{rotation_transcript, RotationProof, RotationProtocol};
+use zkp::server_auth::{challenge_transcript, ServerAuthProtocol, ServerKey};
+use zkp::transcript::Transcript;
+use zkp::ZKPError;
+
+const LOGIN_TRANSCRIPT_LABEL: &[u8] = b"zkp-auth/login";
 
-use zkp::chaum_pedersen::{ChaumPedersenProtocol, Context};
+pub const DEFAULT_CHALLENGE_TTL: Duration = Duration::from_secs(5 * 60);
+pub const DEFAULT_MAX_PENDING_CHALLENGES: usize = 5;
+pub const DEFAULT_SESSION_IDLE_TTL: Duration = Duration::from_secs(30 * 60);
+pub const DEFAULT_SESSION_LIFETIME: Duration = Duration::from_secs(24 * 60 * 60);
 
 pub struct AuthChallenge {
     pub auth_id: String,
     pub c: i64,
+    pub key_share: Option<i64>,
+    pub server_proof: Proof,
+}
+
+#[derive(Clone)]
+pub enum AuthAnswer {
+    ChaumPedersen { s: i64 },
+    Okamoto { s1: i64, s2: i64 },
+}
+
+/// Proof that a request within a session was made by the holder of the session key, see
+/// [`zkp::ake::SessionKey::request_tag`].
+pub struct SessionProof {
+    pub counter: u64,
+    pub tag: Vec<u8>,
 }
 
 pub struct SessionDetails {
     pub session_id: String,
+    pub username: String,
+    pub created_at: u64,
+    pub last_seen_at: u64,
+    pub expires_at: u64,
+    pub client: ClientMetadataDTO,
+    /// Signed token standing in for the stored session, see [`AuthActor::with_token_signer`].
+    pub token: Option<String>,
+}
+
+impl From<SessionDTO> for SessionDetails {
+    fn from(val: SessionDTO) -> Self {
+        SessionDetails {
+            session_id: val.session_id,
+            username: val.username,
+            created_at: val.created_at,
+            last_seen_at: val.last_seen_at,
+            expires_at: val.expires_at,
+            client: val.client,
+            token: None,
+        }
+    }
+}
+
+impl From<TokenClaims> for SessionDetails {
+    fn from(val: TokenClaims) -> Self {
+        SessionDetails {
+            session_id: val.sid,
+            username: val.sub,
+            created_at: val.iat,
+            last_seen_at: val.iat,
+            expires_at: val.exp,
+            client: ClientMetadataDTO::default(),
+            token: None,
+        }
+    }
 }
 
-pub struct AuthActor {
-    user_repository: InMemoryRepository<UserDTO>,
-    challenge_repository: InMemoryRepository<ChallengeDTO>,
-    zkp: ChaumPedersenProtocol,
+/// Where the rate limits of an [`AuthActor`] are kept.
+enum RateLimits {
+    Off,
+    Local(RateLimiter),
+    /// Moved into the task of an [`AuthHandle`](handle::AuthHandle), asked over a channel by the
+    /// concurrent handlers.
+    Actor(mpsc::UnboundedSender<LimitRequest>),
 }
 
-impl AuthActor {
-    pub fn new(ctx: Context) -> Self {
+pub struct AuthActor<
+    U: UserRepository,
+    C: ChallengeRepository,
+    P: PendingChallengeRepository,
+    S: SessionRepository,
+> {
+    ctx: Context,
+    user_repository: U,
+    challenge_repository: C,
+    pending_challenge_repository: P,
+    session_repository: S,
+    zkp: Arc<ChaumPedersenProtocol>,
+    okamoto: Arc<OkamotoProtocol>,
+    rotation: Arc<RotationProtocol>,
+    key_exchange: KeyExchange,
+    server_auth: ServerAuthProtocol,
+    server_key: ServerKey,
+    clock: Arc<dyn Clock>,
+    challenge_ttl: Duration,
+    max_pending_challenges: usize,
+    session_idle_ttl: Duration,
+    session_lifetime: Duration,
+    key_sealer: KeySealer,
+    token_signer: Option<TokenSigner>,
+    rate_limits: RateLimits,
+}
+
+/// Runs proof verification on the blocking thread pool, so it does not stall the runtime.
+async fn verify_blocking<F>(verify: F) -> Result<Result<(), ZKPError>, AuthActorError>
+where
+    F: FnOnce() -> Result<(), ZKPError> + Send + 'static,
+{
+    tokio::task::spawn_blocking(verify)
+        .await
+        .map_err(|_| AuthActorError::WorkerFailure)
+}
+
+impl<U, C, P, S> AuthActor<U, C, P, S>
+where
+    U: UserRepository,
+    C: ChallengeRepository,
+    P: PendingChallengeRepository,
+    S: SessionRepository,
+{
+    pub fn new(
+        ctx: Context,
+        server_key: ServerKey,
+        user_repository: U,
+        challenge_repository: C,
+        pending_challenge_repository: P,
+        session_repository: S,
+    ) -> Self {
         Self {
-            user_repository: InMemoryRepository::<UserDTO>::default(),
-            challenge_repository: InMemoryRepository::<ChallengeDTO>::default(),
-            zkp: ChaumPedersenProtocol::new(ctx),
+            user_repository,
+            challenge_repository,
+            pending_challenge_repository,
+            session_repository,
+            zkp: Arc::new(ChaumPedersenProtocol::new(ctx.clone())),
+            okamoto: Arc::new(OkamotoProtocol::new(ctx.clone())),
+            rotation: Arc::new(RotationProtocol::new(ctx.clone())),
+            key_exchange: KeyExchange::new(ctx.clone()),
+            server_auth: ServerAuthProtocol::new(ctx.clone()),
+            server_key,
+            clock: Arc::new(SystemClock),
+            challenge_ttl: DEFAULT_CHALLENGE_TTL,
+            max_pending_challenges: DEFAULT_MAX_PENDING_CHALLENGES,
+            session_idle_ttl: DEFAULT_SESSION_IDLE_TTL,
+            session_lifetime: DEFAULT_SESSION_LIFETIME,
+            key_sealer: KeySealer::random(),
+            token_signer: None,
+            rate_limits: RateLimits::Off,
+            ctx,
         }
     }
 
-    pub fn register(&mut self, username: String, y1: i64, y2: i64) -> Result<(), AuthActorError> {
-        self.user_repository
-            .insert(&(username.clone()), &UserDTO::new(username, y1, y2))
-            .map_err(|e| e.into())
+    pub fn with_clock(self, clock: Arc<dyn Clock>) -> Self {
+        Self { clock, ..self }
     }
 
-    pub fn create_authentication_challenge(
-        &mut self,
-        username: String,
-        r1: i64,
-        r2: i64,
-    ) -> Result<AuthChallenge, AuthActorError> {
-        if !self.user_repository.exists(&username)? {
-            return Err(AuthActorError::UserNotFound);
+    /// Sets how long a challenge can be answered after it was created.
+    pub fn with_challenge_ttl(self, challenge_ttl: Duration) -> Self {
+        Self {
+            challenge_ttl,
+            ..self
+        }
+    }
+
+    /// Sets how many challenges a user can have pending at the same time.
+    pub fn with_max_pending_challenges(self, max_pending_challenges: usize) -> Self {
+        Self {
+            max_pending_challenges,
+            ..self
+        }
+    }
+
+    /// Sets how long a session stays valid without being refreshed.
+    pub fn with_session_idle_ttl(self, session_idle_ttl: Duration) -> Self {
+        Self {
+            session_idle_ttl,
+            ..self
+        }
+    }
+
+    /// Sets how long a session stays valid after it was established, however often it is
+    /// refreshed.
+    pub fn with_session_lifetime(self, session_lifetime: Duration) -> Self {
+        Self {
+            session_lifetime,
+            ..self
         }
-        let auth_id = username.clone();
-        let c = self.zkp.create_auth_challenge();
+    }
+
+    /// Sets the key the session keys are encrypted with before they are stored.
+    pub fn with_key_sealer(self, key_sealer: KeySealer) -> Self {
+        Self { key_sealer, ..self }
+    }
 
-        self.challenge_repository
-            .put(&auth_id, &ChallengeDTO::new(username, r1, r2, c))
-            .map_err(<repository::error::RepositoryError as Into<AuthActorError>>::into)?;
+    /// Issues signed tokens instead of storing sessions, so they can be verified offline.
+    pub fn with_token_signer(self, token_signer: TokenSigner) -> Self {
+        Self {
+            token_signer: Some(token_signer),
+            ..self
+        }
+    }
 
-        Ok(AuthChallenge { auth_id, c })
+    /// Throttles challenges and answers by user and by client address, see [`RateLimiter`].
+    pub fn with_rate_limits(self, policy: RateLimitPolicy) -> Self {
+        Self {
+            rate_limits: RateLimits::Local(RateLimiter::new(policy)),
+            ..self
+        }
     }
-    pub fn verify_authentication(
+
+    pub async fn register(
         &self,
-        auth_id: String,
-        s: i64,
-    ) -> Result<SessionDetails, AuthActorError> {
-        let UserDTO {
-            username: _,
-            y1,
-            y2,
-        } = match self.user_repository.get(&auth_id)? {
+        username: String,
+        credential: CredentialDTO,
+    ) -> Result<(), AuthActorError> {
+        match credential {
+            CredentialDTO::ChaumPedersen { y1, y2 } => {
+                self.ctx.check_element(y1)?;
+                self.ctx.check_element(y2)?;
+            }
+            CredentialDTO::Okamoto { y } => self.ctx.check_element(y)?,
+        }
+
+        self.user_repository
+            .insert(&(username.clone()), &UserDTO::new(username, credential))
+            .await
+            .map_err(|e| match e {
+                RepositoryError::ValueAlreadyExists => AuthActorError::UserAlreadyRegistered,
+                e => AuthActorError::storage(e),
+            })
+    }
+
+    pub async fn create_authentication_challenge(
+        &self,
+        username: String,
+        commitment: CommitmentDTO,
+        client_share: Option<i64>,
+        client: ClientMetadataDTO,
+    ) -> Result<AuthChallenge, AuthActorError> {
+        self.throttle(&username, &client).await?;
+        let UserDTO { credential, .. } = match self
+            .user_repository
+            .get(&username)
+            .await
+            .map_err(AuthActorError::storage)?
+        {
             None => return Err(AuthActorError::UserNotFound),
             Some(user) => user,
         };
+        let mut transcript = Transcript::new(LOGIN_TRANSCRIPT_LABEL);
+        transcript.append_message(b"username", username.as_bytes());
+        let c = match (&credential, &commitment) {
+            (CredentialDTO::ChaumPedersen { y1, y2 }, CommitmentDTO::ChaumPedersen { r1, r2 }) => {
+                self.ctx.check_element(*r1)?;
+                self.ctx.check_element(*r2)?;
+                self.zkp
+                    .append_to_transcript(&mut transcript, &[*y1, *y2], &[*r1, *r2]);
+                self.zkp
+                    .create_auth_challenge(&mut rand::thread_rng(), &mut transcript)?
+            }
+            (CredentialDTO::Okamoto { y }, CommitmentDTO::Okamoto { t }) => {
+                self.ctx.check_element(*t)?;
+                self.okamoto.append_to_transcript(&mut transcript, *y, *t);
+                self.okamoto
+                    .create_auth_challenge(&mut rand::thread_rng(), &mut transcript)?
+            }
+            _ => return Err(AuthActorError::CredentialTypeMismatch),
+        };
+        let auth_id = hex::encode(rand::random::<[u8; 16]>());
+        let commitments = match commitment {
+            CommitmentDTO::ChaumPedersen { r1, r2 } => vec![r1, r2],
+            CommitmentDTO::Okamoto { t } => vec![t],
+        };
+
+        // With a key exchange the prover answers the challenge bound to both key shares.
+        let (bound_c, key_share, key_agreement) = match client_share {
+            None => (c, None, None),
+            Some(client_share) => {
+                let secret = self.key_exchange.generate(&mut rand::thread_rng());
+                let key_share = secret.share();
+                let agreement = self.key_exchange.server_agree(
+                    &mut key_exchange_transcript(&username),
+                    secret,
+                    client_share,
+                    c,
+                )?;
+                let session_id = hex::encode(agreement.session_id);
+                let key_agreement = KeyAgreementDTO {
+                    session_key: self.key_sealer.seal(&session_id, &agreement.session_key),
+                    session_id,
+                };
+                (agreement.c, Some(key_share), Some(key_agreement))
+            }
+        };
+
+        let server_proof = self.server_auth.prove(
+            &mut rand::thread_rng(),
+            &self.server_key,
+            &mut challenge_transcript(
+                &username,
+                &commitments,
+                client_share.zip(key_share),
+                &auth_id,
+                c,
+            ),
+        )?;
+
+        // The slot is reserved first, so concurrent logins cannot exceed the cap, and released
+        // again if the challenge cannot be stored.
+        let created_at = self.clock.now();
+        self.update_pending_challenges(&username, |challenges| {
+            if challenges.len() >= self.max_pending_challenges {
+                return Err(AuthActorError::TooManyPendingChallenges);
+            }
+            challenges.push(PendingChallengeDTO {
+                auth_id: auth_id.clone(),
+                created_at,
+            });
+            Ok(())
+        })
+        .await?;
+        let challenge = ChallengeDTO::new(
+            username.clone(),
+            commitment,
+            bound_c,
+            key_agreement,
+            created_at,
+        );
+        if let Err(e) = self.challenge_repository.insert(&auth_id, &challenge).await {
+            self.update_pending_challenges(&username, |challenges| {
+                challenges.retain(|challenge| challenge.auth_id != auth_id);
+                Ok(())
+            })
+            .await?;
+            return Err(AuthActorError::storage(e));
+        }
+
+        Ok(AuthChallenge {
+            auth_id,
+            c,
+            key_share,
+            server_proof,
+        })
+    }
 
+    pub async fn verify_authentication(
+        &self,
+        auth_id: String,
+        answer: AuthAnswer,
+        client: ClientMetadataDTO,
+    ) -> Result<SessionDetails, AuthActorError> {
         let ChallengeDTO {
-            username: _,
-            r1,
-            r2,
+            username,
+            commitment,
             c,
-        } = match self.challenge_repository.get(&auth_id)? {
+            key_agreement,
+            created_at,
+        } = match self
+            .challenge_repository
+            .get(&auth_id)
+            .await
+            .map_err(AuthActorError::storage)?
+        {
             None => return Err(AuthActorError::AuthChallengeNotFound),
             Some(challenge) => challenge,
         };
 
-        match self.zkp.verify_auth_challenge(y1, y2, r1, r2, c, s)? {
-            false => Err(AuthActorError::AuthChallengeFailed),
-            true => Ok(SessionDetails {
-                session_id: "test session".into(),
-            }),
+        // Every challenge gets a single answer, whatever its outcome, even a throttled one. Only
+        // one of the concurrent verifications of the same challenge deletes it.
+        match self.challenge_repository.delete(&auth_id).await {
+            Err(RepositoryError::ValueNotFound) => {
+                return Err(AuthActorError::AuthChallengeNotFound)
+            }
+            result => result.map_err(AuthActorError::storage)?,
+        }
+        self.update_pending_challenges(&username, |challenges| {
+            challenges.retain(|challenge| challenge.auth_id != auth_id);
+            Ok(())
+        })
+        .await?;
+        self.throttle(&username, &client).await?;
+        if self.is_expired(created_at) {
+            return Err(AuthActorError::AuthChallengeExpired);
+        }
+
+        let UserDTO { credential, .. } = match self
+            .user_repository
+            .get(&username)
+            .await
+            .map_err(AuthActorError::storage)?
+        {
+            None => return Err(AuthActorError::UserNotFound),
+            Some(user) => user,
+        };
+
+        let verified = match (credential, commitment, answer) {
+            (
+                CredentialDTO::ChaumPedersen { y1, y2 },
+                CommitmentDTO::ChaumPedersen { r1, r2 },
+                AuthAnswer::ChaumPedersen { s },
+            ) => {
+                let zkp = self.zkp.clone();
+                verify_blocking(move || zkp.verify_auth_challenge(y1, y2, r1, r2, c, s))
+                    .await?
+                    .map_err(AuthActorError::from)
+            }
+            (
+                CredentialDTO::Okamoto { y },
+                CommitmentDTO::Okamoto { t },
+                AuthAnswer::Okamoto { s1, s2 },
+            ) => {
+                let okamoto = self.okamoto.clone();
+                verify_blocking(move || okamoto.verify_auth_challenge(y, t, c, &[s1, s2]))
+                    .await?
+                    .map_err(AuthActorError::from)
+            }
+            // Answering in the wrong scheme is a failed guess like any other.
+            _ => Err(AuthActorError::CredentialTypeMismatch),
+        };
+        self.record_verification(&username, &client, verified.is_ok());
+        verified?;
+
+        // The id agreed on with the key exchange is as unpredictable as a random one and lets the
+        // client confirm that both sides derived the same key.
+        let (session_id, session_key) = match key_agreement {
+            None => (hex::encode(rand::random::<[u8; 32]>()), None),
+            Some(KeyAgreementDTO {
+                session_id,
+                session_key,
+            }) => (session_id, Some(session_key)),
+        };
+        let now = self.clock.now();
+        // Edge services verify tokens offline and cannot check tags made with a key they do not
+        // hold, so token sessions are bearer tokens and the session key is not kept.
+        if let Some(token_signer) = &self.token_signer {
+            let (token, claims) = token_signer.issue(&username, &session_id, now)?;
+            return Ok(SessionDetails {
+                client,
+                token: Some(token),
+                ..claims.into()
+            });
+        }
+
+        let session = SessionDTO {
+            session_id,
+            username,
+            session_key,
+            last_counter: 0,
+            created_at: now,
+            last_seen_at: now,
+            expires_at: self.session_expiry(now, now),
+            client,
+        };
+        self.session_repository
+            .insert(&session.session_id, &session)
+            .await
+            .map_err(AuthActorError::storage)?;
+
+        Ok(session.into())
+    }
+
+    /// Returns the session of a token issued by any of the published keys if it has not expired.
+    pub fn validate_token(&self, token: &str) -> Result<SessionDetails, AuthActorError> {
+        match &self.token_signer {
+            None => Err(AuthActorError::SessionsNotStateless),
+            Some(token_signer) => Ok(token_signer.verify(token, self.clock.now())?.into()),
+        }
+    }
+
+    /// Returns the id of the signing key and every key tokens can be verified with.
+    pub fn token_keys(&self) -> Result<(String, Vec<VerificationKey>), AuthActorError> {
+        match &self.token_signer {
+            None => Err(AuthActorError::SessionsNotStateless),
+            Some(token_signer) => Ok((
+                token_signer.signing_key_id().into(),
+                token_signer.verification_keys(),
+            )),
+        }
+    }
+
+    /// Returns the session if it has not expired, without extending it.
+    pub async fn validate_session(
+        &self,
+        session_id: String,
+        proof: Option<SessionProof>,
+    ) -> Result<SessionDetails, AuthActorError> {
+        self.update_session(&session_id, proof.as_ref(), |session| session)
+            .await
+            .map(SessionDetails::from)
+    }
+
+    /// Records activity within the session, which keeps it valid for another idle period.
+    pub async fn refresh_session(
+        &self,
+        session_id: String,
+        proof: Option<SessionProof>,
+    ) -> Result<SessionDetails, AuthActorError> {
+        let now = self.clock.now();
+        self.update_session(&session_id, proof.as_ref(), |session| SessionDTO {
+            last_seen_at: now,
+            expires_at: self.session_expiry(session.created_at, now),
+            ..session
+        })
+        .await
+        .map(SessionDetails::from)
+    }
+
+    pub async fn revoke_session(
+        &self,
+        session_id: String,
+        proof: Option<SessionProof>,
+    ) -> Result<(), AuthActorError> {
+        self.update_session(&session_id, proof.as_ref(), |session| session)
+            .await?;
+        match self.session_repository.delete(&session_id).await {
+            Err(RepositoryError::ValueNotFound) => Err(AuthActorError::SessionNotFound),
+            result => result.map_err(AuthActorError::storage),
+        }
+    }
+
+    /// Applies `update` to the session if it has not expired.
+    ///
+    /// Sessions established with a key exchange only accept requests tagged with their key and a
+    /// counter above the last accepted one, which is recorded with the update, so a tag cannot be
+    /// replayed. Sessions without a key are authorized by their id alone.
+    async fn update_session<F>(
+        &self,
+        session_id: &str,
+        proof: Option<&SessionProof>,
+        update: F,
+    ) -> Result<SessionDTO, AuthActorError>
+    where
+        F: Fn(SessionDTO) -> SessionDTO,
+    {
+        loop {
+            let session = self.live_session(session_id).await?;
+            let mut updated = update(session.clone());
+            if let Some(sealed_key) = &session.session_key {
+                let proof = proof.ok_or(AuthActorError::InvalidSessionProof)?;
+                let session_key = self
+                    .key_sealer
+                    .open(session_id, sealed_key)
+                    .map_err(|_| AuthActorError::InvalidSessionProof)?;
+                if proof.counter <= session.last_counter {
+                    return Err(AuthActorError::InvalidSessionProof);
+                }
+                session_key
+                    .verify_request_tag(session_id, proof.counter, &proof.tag)
+                    .map_err(|_| AuthActorError::InvalidSessionProof)?;
+                updated.last_counter = proof.counter;
+            }
+            if updated == session {
+                return Ok(session);
+            }
+
+            match self
+                .session_repository
+                .compare_and_swap(session_id, &session, &updated)
+                .await
+            {
+                Err(RepositoryError::ConcurrentModification) => continue,
+                Err(RepositoryError::ValueNotFound) => return Err(AuthActorError::SessionNotFound),
+                result => {
+                    result.map_err(AuthActorError::storage)?;
+                    return Ok(updated);
+                }
+            }
+        }
+    }
+
+    /// Loads the session, removing it once it has expired.
+    async fn live_session(&self, session_id: &str) -> Result<SessionDTO, AuthActorError> {
+        if self.token_signer.is_some() {
+            return Err(AuthActorError::SessionsNotStored);
+        }
+        let session = match self
+            .session_repository
+            .get(session_id)
+            .await
+            .map_err(AuthActorError::storage)?
+        {
+            None => return Err(AuthActorError::SessionNotFound),
+            Some(session) => session,
+        };
+        if self.clock.now() < session.expires_at {
+            return Ok(session);
+        }
+
+        match self.session_repository.delete(session_id).await {
+            Ok(()) | Err(RepositoryError::ValueNotFound) => Err(AuthActorError::SessionExpired),
+            Err(e) => Err(AuthActorError::storage(e)),
+        }
+    }
+
+    /// Slides the expiry one idle period past the last activity, within the session lifetime.
+    fn session_expiry(&self, created_at: u64, last_seen_at: u64) -> u64 {
+        (last_seen_at + self.session_idle_ttl.as_secs())
+            .min(created_at + self.session_lifetime.as_secs())
+    }
+
+    async fn throttle(
+        &self,
+        username: &str,
+        client: &ClientMetadataDTO,
+    ) -> Result<(), AuthActorError> {
+        let (peer, now) = (client.address.as_deref(), self.clock.now());
+        match &self.rate_limits {
+            RateLimits::Off => Ok(()),
+            RateLimits::Local(rate_limiter) => Ok(rate_limiter.check(username, peer, now)?),
+            RateLimits::Actor(requests) => {
+                let (reply, response) = oneshot::channel();
+                requests
+                    .send(LimitRequest::Check {
+                        username: username.into(),
+                        peer: peer.map(Into::into),
+                        now,
+                        reply,
+                    })
+                    .map_err(|_| AuthActorError::WorkerFailure)?;
+                Ok(response
+                    .await
+                    .map_err(|_| AuthActorError::WorkerFailure)??)
+            }
+        }
+    }
+
+    /// Counts a verification against the user and the client address.
+    fn record_verification(&self, username: &str, client: &ClientMetadataDTO, verified: bool) {
+        let (peer, now) = (client.address.as_deref(), self.clock.now());
+        match &self.rate_limits {
+            RateLimits::Off => {}
+            RateLimits::Local(rate_limiter) => match verified {
+                true => rate_limiter.record_success(username),
+                false => rate_limiter.record_failure(username, peer, now),
+            },
+            RateLimits::Actor(requests) => {
+                // Requests of one handler are handled in order, so its next check sees this one.
+                let _ = requests.send(LimitRequest::Record {
+                    username: username.into(),
+                    peer: peer.map(Into::into),
+                    now,
+                    verified,
+                });
+            }
+        }
+    }
+
+    fn is_expired(&self, created_at: u64) -> bool {
+        self.clock.now().saturating_sub(created_at) >= self.challenge_ttl.as_secs()
+    }
+
+    /// Applies `update` to the pending challenges of the user that have not expired yet.
+    ///
+    /// The update is retried until no concurrent login of the same user changed them meanwhile.
+    async fn update_pending_challenges<F>(
+        &self,
+        username: &str,
+        update: F,
+    ) -> Result<(), AuthActorError>
+    where
+        F: Fn(&mut Vec<PendingChallengeDTO>) -> Result<(), AuthActorError> + Send + Sync,
+    {
+        let repository = &self.pending_challenge_repository;
+        loop {
+            let current = repository
+                .get(username)
+                .await
+                .map_err(AuthActorError::storage)?;
+            let mut updated = current.clone().unwrap_or_default();
+            updated
+                .challenges
+                .retain(|challenge| !self.is_expired(challenge.created_at));
+            update(&mut updated.challenges)?;
+
+            let result = match &current {
+                None if updated.challenges.is_empty() => return Ok(()),
+                None => repository.insert(username, &updated).await,
+                Some(current) if *current == updated => return Ok(()),
+                Some(current) => {
+                    repository
+                        .compare_and_swap(username, current, &updated)
+                        .await
+                }
+            };
+            match result {
+                Err(
+                    RepositoryError::ValueAlreadyExists
+                    | RepositoryError::ConcurrentModification
+                    | RepositoryError::ValueNotFound,
+                ) => continue,
+                result => return result.map_err(AuthActorError::storage),
+            }
+        }
+    }
+
+    /// Replaces the credential of `username` if `key_version` is still its current version.
+    pub async fn rotate_credentials(
+        &self,
+        username: String,
+        key_version: u64,
+        new_y1: i64,
+        new_y2: i64,
+        proof: RotationProof,
+    ) -> Result<u64, AuthActorError> {
+        let user = match self
+            .user_repository
+            .get(&username)
+            .await
+            .map_err(AuthActorError::storage)?
+        {
+            None => return Err(AuthActorError::UserNotFound),
+            Some(user) => user,
+        };
+        let (y1, y2) = match user.credential {
+            CredentialDTO::ChaumPedersen { y1, y2 } => (y1, y2),
+            CredentialDTO::Okamoto { .. } => return Err(AuthActorError::CredentialTypeMismatch),
+        };
+        if key_version != user.key_version {
+            return Err(AuthActorError::CredentialsChanged);
+        }
+
+        let rotation = self.rotation.clone();
+        let mut transcript = rotation_transcript(&username, key_version);
+        verify_blocking(move || {
+            rotation.verify_proof(&mut transcript, &[y1, y2], &[new_y1, new_y2], &proof)
+        })
+        .await??;
+
+        let rotated = UserDTO {
+            credential: CredentialDTO::ChaumPedersen {
+                y1: new_y1,
+                y2: new_y2,
+            },
+            key_version: key_version + 1,
+            ..user.clone()
+        };
+        self.user_repository
+            .compare_and_swap(&username, &user, &rotated)
+            .await
+            .map_err(|e| match e {
+                RepositoryError::ConcurrentModification => AuthActorError::CredentialsChanged,
+                RepositoryError::ValueNotFound => AuthActorError::UserNotFound,
+                e => AuthActorError::storage(e),
+            })?;
+        Ok(rotated.key_version)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::ManualClock;
+    use crate::repository::{
+        in_memory::InMemoryRepository, DBResult, PendingChallengesDTO, Repository,
+    };
+    use async_trait::async_trait;
+    use zkp::ake::SessionKey;
+    use zkp::server_auth::ServerAuthProtocol;
+
+    const ZKP_G: i64 = 4;
+    const ZKP_H: i64 = 9;
+    const ZKP_P: i64 = 20123;
+    const ZKP_Q: i64 = 10061;
+    const USERNAME: &str = "alice";
+    const SECRET: i64 = 9;
+    const CHALLENGE_K: i64 = 27;
+
+    /// User repository whose users change between every read and write.
+    #[derive(Default)]
+    struct RacingUserRepository(InMemoryRepository<UserDTO>);
+
+    #[async_trait]
+    impl Repository<UserDTO> for RacingUserRepository {
+        async fn insert(&self, key: &str, value: &UserDTO) -> DBResult<()> {
+            self.0.insert(key, value).await
+        }
+
+        async fn put(&self, key: &str, value: &UserDTO) -> DBResult<()> {
+            self.0.put(key, value).await
+        }
+
+        async fn get(&self, key: &str) -> DBResult<Option<UserDTO>> {
+            self.0.get(key).await
+        }
+
+        async fn delete(&self, key: &str) -> DBResult<()> {
+            self.0.delete(key).await
+        }
+
+        async fn exists(&self, key: &str) -> DBResult<bool> {
+            self.0.exists(key).await
+        }
+
+        async fn compare_and_swap(
+            &self,
+            _key: &str,
+            _expected: &UserDTO,
+            _value: &UserDTO,
+        ) -> DBResult<()> {
+            Err(RepositoryError::ConcurrentModification)
+        }
+    }
+
+    /// Challenge repository whose storage is down.
+    struct UnavailableChallengeRepository;
+
+    #[async_trait]
+    impl Repository<ChallengeDTO> for UnavailableChallengeRepository {
+        async fn insert(&self, _key: &str, _value: &ChallengeDTO) -> DBResult<()> {
+            Err(RepositoryError::Storage("unavailable".into()))
+        }
+
+        async fn put(&self, _key: &str, _value: &ChallengeDTO) -> DBResult<()> {
+            Err(RepositoryError::Storage("unavailable".into()))
+        }
+
+        async fn get(&self, _key: &str) -> DBResult<Option<ChallengeDTO>> {
+            Err(RepositoryError::Storage("unavailable".into()))
+        }
+
+        async fn delete(&self, _key: &str) -> DBResult<()> {
+            Err(RepositoryError::Storage("unavailable".into()))
+        }
+
+        async fn exists(&self, _key: &str) -> DBResult<bool> {
+            Err(RepositoryError::Storage("unavailable".into()))
+        }
+
+        async fn compare_and_swap(
+            &self,
+            _key: &str,
+            _expected: &ChallengeDTO,
+            _value: &ChallengeDTO,
+        ) -> DBResult<()> {
+            Err(RepositoryError::Storage("unavailable".into()))
+        }
+    }
+
+    fn ctx() -> Context {
+        Context::new(ZKP_G, ZKP_H, ZKP_P, ZKP_Q).unwrap()
+    }
+
+    type TestActor<U> = AuthActor<
+        U,
+        InMemoryRepository<ChallengeDTO>,
+        InMemoryRepository<PendingChallengesDTO>,
+        InMemoryRepository<SessionDTO>,
+    >;
+
+    fn actor<U: UserRepository + Default>() -> TestActor<U> {
+        let server_key = ServerAuthProtocol::new(ctx()).key_from_secret(7).unwrap();
+        AuthActor::new(
+            ctx(),
+            server_key,
+            U::default(),
+            InMemoryRepository::default(),
+            InMemoryRepository::default(),
+            InMemoryRepository::default(),
+        )
+    }
+
+    async fn register<U: UserRepository>(actor: &TestActor<U>, zkp: &ChaumPedersenProtocol) {
+        let (y1, y2) = zkp.calculate_registration_data(SECRET).unwrap();
+        assert!(actor
+            .register(USERNAME.into(), CredentialDTO::ChaumPedersen { y1, y2 })
+            .await
+            .is_ok());
+    }
+
+    #[tokio::test]
+    async fn should_log_in_with_key_exchange() {
+        let actor = actor::<InMemoryRepository<UserDTO>>();
+        let zkp = ChaumPedersenProtocol::new(ctx());
+        let key_exchange = KeyExchange::new(ctx());
+        register(&actor, &zkp).await;
+
+        let (r1, r2) = zkp.calculate_login_challenge_data(CHALLENGE_K).unwrap();
+        let secret = key_exchange.generate(&mut rand::thread_rng());
+        let client_share = secret.share();
+        let challenge = actor
+            .create_authentication_challenge(
+                USERNAME.into(),
+                CommitmentDTO::ChaumPedersen { r1, r2 },
+                Some(client_share),
+                ClientMetadataDTO::default(),
+            )
+            .await
+            .ok()
+            .unwrap();
+        let agreement = key_exchange
+            .client_agree(
+                &mut key_exchange_transcript(USERNAME),
+                secret,
+                challenge.key_share.unwrap(),
+                challenge.c,
+            )
+            .unwrap();
+
+        let s = zkp
+            .calculate_challenge(CHALLENGE_K, agreement.c, SECRET)
+            .unwrap();
+        let session = actor
+            .verify_authentication(
+                challenge.auth_id,
+                AuthAnswer::ChaumPedersen { s },
+                ClientMetadataDTO::default(),
+            )
+            .await
+            .ok()
+            .unwrap();
+        assert_eq!(session.session_id, hex::encode(agreement.session_id));
+
+        // Requests within the session are tagged with the agreed key and a growing counter.
+        let session_id = session.session_id;
+        let proof = |counter| {
+            Some(SessionProof {
+                counter,
+                tag: agreement
+                    .session_key
+                    .request_tag(&session_id, counter)
+                    .to_vec(),
+            })
+        };
+        assert!(matches!(
+            actor.validate_session(session_id.clone(), None).await,
+            Err(AuthActorError::InvalidSessionProof)
+        ));
+        assert!(actor
+            .validate_session(session_id.clone(), proof(1))
+            .await
+            .is_ok());
+        assert!(matches!(
+            actor.refresh_session(session_id.clone(), proof(1)).await,
+            Err(AuthActorError::InvalidSessionProof)
+        ));
+        let forged = SessionProof {
+            counter: 3,
+            tag: SessionKey::from([0; 32])
+                .request_tag(&session_id, 3)
+                .to_vec(),
+        };
+        assert!(matches!(
+            actor
+                .refresh_session(session_id.clone(), Some(forged))
+                .await,
+            Err(AuthActorError::InvalidSessionProof)
+        ));
+        assert!(actor
+            .refresh_session(session_id.clone(), proof(2))
+            .await
+            .is_ok());
+        assert!(actor
+            .revoke_session(session_id.clone(), proof(3))
+            .await
+            .is_ok());
+    }
+
+    async fn challenge<U: UserRepository>(
+        actor: &TestActor<U>,
+        zkp: &ChaumPedersenProtocol,
+    ) -> (String, AuthAnswer) {
+        let (r1, r2) = zkp.calculate_login_challenge_data(CHALLENGE_K).unwrap();
+        let AuthChallenge { auth_id, c, .. } = actor
+            .create_authentication_challenge(
+                USERNAME.into(),
+                CommitmentDTO::ChaumPedersen { r1, r2 },
+                None,
+                ClientMetadataDTO::default(),
+            )
+            .await
+            .ok()
+            .unwrap();
+        let s = zkp.calculate_challenge(CHALLENGE_K, c, SECRET).unwrap();
+        (auth_id, AuthAnswer::ChaumPedersen { s })
+    }
+
+    #[tokio::test]
+    async fn should_consume_challenge_on_first_answer() {
+        let actor = actor::<InMemoryRepository<UserDTO>>();
+        let zkp = ChaumPedersenProtocol::new(ctx());
+        register(&actor, &zkp).await;
+
+        let (auth_id, answer) = challenge(&actor, &zkp).await;
+        assert!(actor
+            .verify_authentication(
+                auth_id.clone(),
+                answer.clone(),
+                ClientMetadataDTO::default()
+            )
+            .await
+            .is_ok());
+        assert!(matches!(
+            actor
+                .verify_authentication(auth_id, answer, ClientMetadataDTO::default())
+                .await,
+            Err(AuthActorError::AuthChallengeNotFound)
+        ));
+
+        // A wrong answer burns the challenge as well.
+        let (auth_id, answer) = challenge(&actor, &zkp).await;
+        assert!(matches!(
+            actor
+                .verify_authentication(
+                    auth_id.clone(),
+                    AuthAnswer::ChaumPedersen { s: 1 },
+                    ClientMetadataDTO::default()
+                )
+                .await,
+            Err(AuthActorError::AuthChallengeFailed)
+        ));
+        assert!(matches!(
+            actor
+                .verify_authentication(auth_id, answer, ClientMetadataDTO::default())
+                .await,
+            Err(AuthActorError::AuthChallengeNotFound)
+        ));
+    }
+
+    #[tokio::test]
+    async fn should_reject_expired_challenge() {
+        let clock = Arc::new(ManualClock::new(1_700_000_000));
+        let actor = actor::<InMemoryRepository<UserDTO>>()
+            .with_clock(clock.clone())
+            .with_challenge_ttl(Duration::from_secs(60));
+        let zkp = ChaumPedersenProtocol::new(ctx());
+        register(&actor, &zkp).await;
+
+        let (auth_id, answer) = challenge(&actor, &zkp).await;
+        clock.advance(Duration::from_secs(59));
+        assert!(actor
+            .verify_authentication(auth_id, answer, ClientMetadataDTO::default())
+            .await
+            .is_ok());
+
+        let (auth_id, answer) = challenge(&actor, &zkp).await;
+        clock.advance(Duration::from_secs(60));
+        assert!(matches!(
+            actor
+                .verify_authentication(auth_id, answer, ClientMetadataDTO::default())
+                .await,
+            Err(AuthActorError::AuthChallengeExpired)
+        ));
+    }
+
+    #[tokio::test]
+    async fn should_keep_concurrent_challenges_of_user() {
+        let actor = actor::<InMemoryRepository<UserDTO>>();
+        let zkp = ChaumPedersenProtocol::new(ctx());
+        register(&actor, &zkp).await;
+
+        let (first_id, first_answer) = challenge(&actor, &zkp).await;
+        let (second_id, second_answer) = challenge(&actor, &zkp).await;
+        assert_ne!(first_id, second_id);
+        assert_eq!(first_id.len(), 32);
+
+        assert!(actor
+            .verify_authentication(second_id, second_answer, ClientMetadataDTO::default())
+            .await
+            .is_ok());
+        assert!(actor
+            .verify_authentication(first_id, first_answer, ClientMetadataDTO::default())
+            .await
+            .is_ok());
+    }
+
+    #[tokio::test]
+    async fn should_release_pending_challenge_that_was_not_stored() {
+        let zkp = ChaumPedersenProtocol::new(ctx());
+        let server_key = ServerAuthProtocol::new(ctx()).key_from_secret(7).unwrap();
+        let actor = AuthActor::new(
+            ctx(),
+            server_key,
+            InMemoryRepository::<UserDTO>::default(),
+            UnavailableChallengeRepository,
+            InMemoryRepository::<PendingChallengesDTO>::default(),
+            InMemoryRepository::<SessionDTO>::default(),
+        );
+        let (y1, y2) = zkp.calculate_registration_data(SECRET).unwrap();
+        assert!(actor
+            .register(USERNAME.into(), CredentialDTO::ChaumPedersen { y1, y2 })
+            .await
+            .is_ok());
+
+        let (r1, r2) = zkp.calculate_login_challenge_data(CHALLENGE_K).unwrap();
+        let result = actor
+            .create_authentication_challenge(
+                USERNAME.into(),
+                CommitmentDTO::ChaumPedersen { r1, r2 },
+                None,
+                ClientMetadataDTO::default(),
+            )
+            .await;
+        assert!(matches!(result, Err(AuthActorError::StorageFailure(_))));
+        let pending = actor.pending_challenge_repository.get(USERNAME).await;
+        assert!(pending.unwrap().unwrap_or_default().challenges.is_empty());
+    }
+
+    #[tokio::test]
+    async fn should_cap_pending_challenges() {
+        let clock = Arc::new(ManualClock::new(1_700_000_000));
+        let actor = actor::<InMemoryRepository<UserDTO>>()
+            .with_clock(clock.clone())
+            .with_challenge_ttl(Duration::from_secs(60))
+            .with_max_pending_challenges(2);
+        let zkp = ChaumPedersenProtocol::new(ctx());
+        register(&actor, &zkp).await;
+        let (r1, r2) = zkp.calculate_login_challenge_data(CHALLENGE_K).unwrap();
+        let create = || {
+            actor.create_authentication_challenge(
+                USERNAME.into(),
+                CommitmentDTO::ChaumPedersen { r1, r2 },
+                None,
+                ClientMetadataDTO::default(),
+            )
+        };
+
+        let (auth_id, answer) = challenge(&actor, &zkp).await;
+        assert!(create().await.is_ok());
+        assert!(matches!(
+            create().await,
+            Err(AuthActorError::TooManyPendingChallenges)
+        ));
+
+        // Answered and expired challenges free their slots.
+        assert!(actor
+            .verify_authentication(auth_id, answer, ClientMetadataDTO::default())
+            .await
+            .is_ok());
+        assert!(create().await.is_ok());
+        clock.advance(Duration::from_secs(60));
+        assert!(create().await.is_ok());
+        assert!(create().await.is_ok());
+        assert!(matches!(
+            create().await,
+            Err(AuthActorError::TooManyPendingChallenges)
+        ));
+    }
+
+    #[tokio::test]
+    async fn should_back_off_and_lock_out_after_failed_answers() {
+        let clock = Arc::new(ManualClock::new(1_700_000_000));
+        let actor = actor::<InMemoryRepository<UserDTO>>()
+            .with_clock(clock.clone())
+            .with_rate_limits(RateLimitPolicy {
+                free_failures: 1,
+                backoff: Duration::from_secs(5),
+                lockout_failures: 3,
+                lockout: Duration::from_secs(600),
+                ..Default::default()
+            });
+        let zkp = ChaumPedersenProtocol::new(ctx());
+        register(&actor, &zkp).await;
+        let (r1, r2) = zkp.calculate_login_challenge_data(CHALLENGE_K).unwrap();
+        let create = || {
+            actor.create_authentication_challenge(
+                USERNAME.into(),
+                CommitmentDTO::ChaumPedersen { r1, r2 },
+                None,
+                ClientMetadataDTO::default(),
+            )
+        };
+        let fail = || async {
+            let (auth_id, _) = challenge(&actor, &zkp).await;
+            actor
+                .verify_authentication(
+                    auth_id,
+                    AuthAnswer::ChaumPedersen { s: 1 },
+                    ClientMetadataDTO::default(),
+                )
+                .await
+        };
+
+        assert!(matches!(
+            fail().await,
+            Err(AuthActorError::AuthChallengeFailed)
+        ));
+        let (auth_id, answer) = challenge(&actor, &zkp).await;
+        assert!(matches!(
+            fail().await,
+            Err(AuthActorError::AuthChallengeFailed)
+        ));
+        assert!(matches!(
+            actor
+                .verify_authentication(auth_id.clone(), answer.clone(), ClientMetadataDTO::default())
+                .await,
+            Err(AuthActorError::RateLimited(retry_after)) if retry_after.as_secs() == 5
+        ));
+
+        // The throttled answer consumed the challenge, a new one can be answered after the
+        // back-off.
+        clock.advance(Duration::from_secs(5));
+        assert!(matches!(
+            actor
+                .verify_authentication(auth_id, answer, ClientMetadataDTO::default())
+                .await,
+            Err(AuthActorError::AuthChallengeNotFound)
+        ));
+        let (auth_id, answer) = challenge(&actor, &zkp).await;
+        assert!(actor
+            .verify_authentication(auth_id, answer, ClientMetadataDTO::default())
+            .await
+            .is_ok());
+
+        for _ in 0..3 {
+            assert!(fail().await.is_err());
+            clock.advance(Duration::from_secs(5));
         }
+        assert!(matches!(
+            create().await,
+            Err(AuthActorError::AccountLocked(retry_after)) if retry_after.as_secs() == 595
+        ));
+        clock.advance(Duration::from_secs(595));
+        assert!(create().await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn should_count_answers_in_other_scheme_as_failures() {
+        let actor = actor::<InMemoryRepository<UserDTO>>().with_rate_limits(RateLimitPolicy {
+            free_failures: 0,
+            backoff: Duration::from_secs(5),
+            ..Default::default()
+        });
+        let zkp = ChaumPedersenProtocol::new(ctx());
+        register(&actor, &zkp).await;
+
+        let (auth_id, _) = challenge(&actor, &zkp).await;
+        assert!(matches!(
+            actor
+                .verify_authentication(
+                    auth_id,
+                    AuthAnswer::Okamoto { s1: 1, s2: 1 },
+                    ClientMetadataDTO::default(),
+                )
+                .await,
+            Err(AuthActorError::CredentialTypeMismatch)
+        ));
+        let (r1, r2) = zkp.calculate_login_challenge_data(CHALLENGE_K).unwrap();
+        assert!(matches!(
+            actor
+                .create_authentication_challenge(
+                    USERNAME.into(),
+                    CommitmentDTO::ChaumPedersen { r1, r2 },
+                    None,
+                    ClientMetadataDTO::default(),
+                )
+                .await,
+            Err(AuthActorError::RateLimited(retry_after)) if retry_after.as_secs() == 5
+        ));
+    }
+
+    async fn log_in<U: UserRepository>(
+        actor: &TestActor<U>,
+        zkp: &ChaumPedersenProtocol,
+    ) -> SessionDetails {
+        let (auth_id, answer) = challenge(actor, zkp).await;
+        let client = ClientMetadataDTO {
+            address: Some("192.0.2.1".into()),
+            user_agent: Some("curl/8.0".into()),
+        };
+        actor
+            .verify_authentication(auth_id, answer, client)
+            .await
+            .ok()
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn should_establish_session_without_key_exchange() {
+        let actor = actor::<InMemoryRepository<UserDTO>>();
+        let zkp = ChaumPedersenProtocol::new(ctx());
+        register(&actor, &zkp).await;
+
+        let first = log_in(&actor, &zkp).await;
+        let second = log_in(&actor, &zkp).await;
+        assert_ne!(first.session_id, second.session_id);
+        assert_eq!(first.session_id.len(), 64);
+
+        let session = actor
+            .validate_session(first.session_id.clone(), None)
+            .await
+            .ok()
+            .unwrap();
+        assert_eq!(session.username, USERNAME);
+        assert_eq!(session.client.user_agent.as_deref(), Some("curl/8.0"));
+        assert!(matches!(
+            actor.validate_session("unknown".into(), None).await,
+            Err(AuthActorError::SessionNotFound)
+        ));
+    }
+
+    #[tokio::test]
+    async fn should_slide_session_expiry() {
+        let clock = Arc::new(ManualClock::new(1_700_000_000));
+        let actor = actor::<InMemoryRepository<UserDTO>>()
+            .with_clock(clock.clone())
+            .with_session_idle_ttl(Duration::from_secs(60))
+            .with_session_lifetime(Duration::from_secs(150));
+        let zkp = ChaumPedersenProtocol::new(ctx());
+        register(&actor, &zkp).await;
+
+        let session = log_in(&actor, &zkp).await;
+        assert_eq!(session.expires_at, 1_700_000_060);
+
+        // Validating does not extend the session, refreshing does.
+        clock.advance(Duration::from_secs(50));
+        let validated = actor
+            .validate_session(session.session_id.clone(), None)
+            .await;
+        assert_eq!(validated.ok().unwrap().expires_at, 1_700_000_060);
+        let refreshed = actor
+            .refresh_session(session.session_id.clone(), None)
+            .await;
+        let refreshed = refreshed.ok().unwrap();
+        assert_eq!(refreshed.last_seen_at, 1_700_000_050);
+        assert_eq!(refreshed.expires_at, 1_700_000_110);
+
+        // The lifetime caps the expiry.
+        clock.advance(Duration::from_secs(50));
+        let refreshed = actor
+            .refresh_session(session.session_id.clone(), None)
+            .await;
+        assert_eq!(refreshed.ok().unwrap().expires_at, 1_700_000_150);
+
+        clock.advance(Duration::from_secs(50));
+        assert!(matches!(
+            actor
+                .refresh_session(session.session_id.clone(), None)
+                .await,
+            Err(AuthActorError::SessionExpired)
+        ));
+        assert!(matches!(
+            actor.validate_session(session.session_id, None).await,
+            Err(AuthActorError::SessionNotFound)
+        ));
+    }
+
+    #[tokio::test]
+    async fn should_revoke_session() {
+        let actor = actor::<InMemoryRepository<UserDTO>>();
+        let zkp = ChaumPedersenProtocol::new(ctx());
+        register(&actor, &zkp).await;
+
+        let session = log_in(&actor, &zkp).await;
+        assert!(actor
+            .revoke_session(session.session_id.clone(), None)
+            .await
+            .is_ok());
+        assert!(matches!(
+            actor
+                .validate_session(session.session_id.clone(), None)
+                .await,
+            Err(AuthActorError::SessionNotFound)
+        ));
+        assert!(matches!(
+            actor.revoke_session(session.session_id, None).await,
+            Err(AuthActorError::SessionNotFound)
+        ));
+    }
+
+    #[tokio::test]
+    async fn should_issue_token_instead_of_session() {
+        let clock = Arc::new(ManualClock::new(1_700_000_000));
+        let token_signer = TokenSigner::new(&[[1; 32]], Duration::from_secs(60), &ctx()).unwrap();
+        let actor = actor::<InMemoryRepository<UserDTO>>()
+            .with_clock(clock.clone())
+            .with_token_signer(token_signer);
+        let zkp = ChaumPedersenProtocol::new(ctx());
+        register(&actor, &zkp).await;
+
+        let session = log_in(&actor, &zkp).await;
+        let token = session.token.unwrap();
+        assert!(!actor
+            .session_repository
+            .exists(&session.session_id)
+            .await
+            .unwrap());
+        assert!(matches!(
+            actor
+                .validate_session(session.session_id.clone(), None)
+                .await,
+            Err(AuthActorError::SessionsNotStored)
+        ));
+
+        let (signing_key_id, keys) = actor.token_keys().ok().unwrap();
+        let claims = crate::token::verify(&token, &keys, clock.now()).unwrap();
+        assert_eq!(claims.sub, USERNAME);
+        assert_eq!(claims.sid, session.session_id);
+        assert_eq!(keys[0].key_id, signing_key_id);
+
+        let validated = actor.validate_token(&token).ok().unwrap();
+        assert_eq!(validated.expires_at, 1_700_000_060);
+        clock.advance(Duration::from_secs(60));
+        assert!(matches!(
+            actor.validate_token(&token),
+            Err(AuthActorError::SessionExpired)
+        ));
+    }
+
+    #[tokio::test]
+    async fn should_report_concurrent_rotation() {
+        let actor = actor::<RacingUserRepository>();
+        let rotation = RotationProtocol::new(ctx());
+        register(&actor, &ChaumPedersenProtocol::new(ctx())).await;
+
+        let new_ys = rotation.calculate_new_registration_data(SECRET + 1);
+        let proof = rotation.create_proof(
+            &mut rotation_transcript(USERNAME, 0),
+            (SECRET, CHALLENGE_K),
+            (SECRET + 1, CHALLENGE_K + 1),
+        );
+        assert!(matches!(
+            actor
+                .rotate_credentials(USERNAME.into(), 0, new_ys[0], new_ys[1], proof)
+                .await,
+            Err(AuthActorError::CredentialsChanged)
+        ));
+    }
+
+    #[tokio::test]
+    async fn should_reject_replayed_rotation() {
+        let actor = actor::<InMemoryRepository<UserDTO>>();
+        let rotation = RotationProtocol::new(ctx());
+        register(&actor, &ChaumPedersenProtocol::new(ctx())).await;
+        let rotate = |key_version, secret, new_secret| {
+            let new_ys = rotation.calculate_new_registration_data(new_secret);
+            let proof = rotation.create_proof(
+                &mut rotation_transcript(USERNAME, key_version),
+                (secret, CHALLENGE_K),
+                (new_secret, CHALLENGE_K + 1),
+            );
+            (new_ys, proof)
+        };
+
+        let (new_ys, first) = rotate(0, SECRET, SECRET + 1);
+        assert!(matches!(
+            actor
+                .rotate_credentials(USERNAME.into(), 0, new_ys[0], new_ys[1], first.clone())
+                .await,
+            Ok(1)
+        ));
+        let (ys, back) = rotate(1, SECRET + 1, SECRET);
+        assert!(matches!(
+            actor
+                .rotate_credentials(USERNAME.into(), 1, ys[0], ys[1], back)
+                .await,
+            Ok(2)
+        ));
+
+        // The key is back to the one the first proof rotated away from.
+        assert!(matches!(
+            actor
+                .rotate_credentials(USERNAME.into(), 0, new_ys[0], new_ys[1], first.clone())
+                .await,
+            Err(AuthActorError::CredentialsChanged)
+        ));
+        assert!(matches!(
+            actor
+                .rotate_credentials(USERNAME.into(), 2, new_ys[0], new_ys[1], first)
+                .await,
+            Err(AuthActorError::AuthChallengeFailed)
+        ));
     }
 }
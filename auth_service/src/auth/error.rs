@@ -1,24 +1,76 @@
+use std::time::Duration;
+
+use crate::rate_limit::Throttled;
 use crate::repository::error::RepositoryError;
+use crate::token::TokenError;
 use zkp::ZKPError;
 
 pub enum AuthActorError {
     AuthChallengeNotFound,
+    AuthChallengeExpired,
+    TooManyPendingChallenges,
+    RateLimited(Duration),
+    AccountLocked(Duration),
     AuthChallengeFailed,
+    CredentialTypeMismatch,
+    CredentialsChanged,
     UserAlreadyRegistered,
     UserNotFound,
-    ZKPMathError,
+    SessionNotFound,
+    SessionExpired,
+    /// The request was not tagged with the key of the session, or its counter was used before.
+    InvalidSessionProof,
+    SessionsNotStored,
+    SessionsNotStateless,
+    InvalidToken,
+    TokenSigningFailure,
+    InvalidProofData(ZKPError),
+    StorageFailure(String),
+    /// The actor queue is full, see [`super::handle::AuthHandle`].
+    Overloaded,
+    /// The task handling the request panicked or was cancelled.
+    WorkerFailure,
 }
 
-impl From<RepositoryError> for AuthActorError {
-    fn from(val: RepositoryError) -> Self {
+impl AuthActorError {
+    /// Reports a repository error the operation did not expect as a storage failure, so it is
+    /// never mistaken for the outcome of the request.
+    pub fn storage(val: RepositoryError) -> Self {
         match val {
-            RepositoryError::ValueAlreadyExists => AuthActorError::UserAlreadyRegistered,
+            RepositoryError::Storage(reason) => AuthActorError::StorageFailure(reason),
+            unexpected => AuthActorError::StorageFailure(format!("Unexpected {:?}", unexpected)),
         }
     }
 }
 
 impl From<ZKPError> for AuthActorError {
-    fn from(_val: ZKPError) -> Self {
-        AuthActorError::ZKPMathError
+    fn from(val: ZKPError) -> Self {
+        match val {
+            ZKPError::VerificationFailed => AuthActorError::AuthChallengeFailed,
+            _ => AuthActorError::InvalidProofData(val),
+        }
+    }
+}
+
+impl From<TokenError> for AuthActorError {
+    fn from(val: TokenError) -> Self {
+        match val {
+            TokenError::Expired => AuthActorError::SessionExpired,
+            TokenError::UnknownKey | TokenError::ParamsMismatch | TokenError::InvalidToken(_) => {
+                AuthActorError::InvalidToken
+            }
+            TokenError::InvalidSigningKey | TokenError::MissingSigningKey => {
+                AuthActorError::TokenSigningFailure
+            }
+        }
+    }
+}
+
+impl From<Throttled> for AuthActorError {
+    fn from(val: Throttled) -> Self {
+        match val {
+            Throttled::RateLimited { retry_after } => AuthActorError::RateLimited(retry_after),
+            Throttled::Locked { retry_after } => AuthActorError::AccountLocked(retry_after),
+        }
     }
 }
@@ -0,0 +1,778 @@
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use tokio::sync::{mpsc, oneshot, OwnedSemaphorePermit, Semaphore};
+use zkp::rotation::RotationProof;
+
+use crate::auth::{
+    error::AuthActorError, AuthActor, AuthAnswer, AuthChallenge, RateLimits, SessionDetails,
+    SessionProof,
+};
+use crate::rate_limit::{RateLimiter, Throttled};
+use crate::repository::{
+    ChallengeRepository, ClientMetadataDTO, CommitmentDTO, CredentialDTO,
+    PendingChallengeRepository, SessionRepository, UserRepository,
+};
+use crate::token::VerificationKey;
+
+pub const DEFAULT_QUEUE_CAPACITY: usize = 1024;
+pub const DEFAULT_MAX_IN_FLIGHT: usize = 64;
+
+type Reply<T> = oneshot::Sender<Result<T, AuthActorError>>;
+
+enum Command {
+    Register {
+        username: String,
+        credential: CredentialDTO,
+        reply: Reply<()>,
+    },
+    CreateAuthenticationChallenge {
+        username: String,
+        commitment: CommitmentDTO,
+        client_share: Option<i64>,
+        client: ClientMetadataDTO,
+        reply: Reply<AuthChallenge>,
+    },
+    VerifyAuthentication {
+        auth_id: String,
+        answer: AuthAnswer,
+        client: ClientMetadataDTO,
+        reply: Reply<SessionDetails>,
+    },
+    RotateCredentials {
+        username: String,
+        key_version: u64,
+        new_y1: i64,
+        new_y2: i64,
+        proof: RotationProof,
+        reply: Reply<u64>,
+    },
+    ValidateSession {
+        session_id: String,
+        proof: Option<SessionProof>,
+        reply: Reply<SessionDetails>,
+    },
+    ValidateToken {
+        token: String,
+        reply: Reply<SessionDetails>,
+    },
+    RefreshSession {
+        session_id: String,
+        proof: Option<SessionProof>,
+        reply: Reply<SessionDetails>,
+    },
+    RevokeSession {
+        session_id: String,
+        proof: Option<SessionProof>,
+        reply: Reply<()>,
+    },
+    TokenKeys {
+        reply: Reply<(String, Vec<VerificationKey>)>,
+    },
+}
+
+/// Call of a request handler to the rate limiter kept by the actor task.
+pub(super) enum LimitRequest {
+    Check {
+        username: String,
+        peer: Option<String>,
+        now: u64,
+        reply: oneshot::Sender<Result<(), Throttled>>,
+    },
+    Record {
+        username: String,
+        peer: Option<String>,
+        now: u64,
+        verified: bool,
+    },
+}
+
+impl LimitRequest {
+    fn handle(self, rate_limiter: &RateLimiter) {
+        match self {
+            LimitRequest::Check {
+                username,
+                peer,
+                now,
+                reply,
+            } => {
+                let _ = reply.send(rate_limiter.check(&username, peer.as_deref(), now));
+            }
+            LimitRequest::Record {
+                username,
+                peer,
+                now,
+                verified,
+            } => match verified {
+                true => rate_limiter.record_success(&username),
+                false => rate_limiter.record_failure(&username, peer.as_deref(), now),
+            },
+        }
+    }
+}
+
+impl Command {
+    /// Answers the command. A caller that went away in the meantime drops the reply.
+    async fn handle<U, C, P, S>(self, actor: &AuthActor<U, C, P, S>)
+    where
+        U: UserRepository,
+        C: ChallengeRepository,
+        P: PendingChallengeRepository,
+        S: SessionRepository,
+    {
+        match self {
+            Command::Register {
+                username,
+                credential,
+                reply,
+            } => {
+                let _ = reply.send(actor.register(username, credential).await);
+            }
+            Command::CreateAuthenticationChallenge {
+                username,
+                commitment,
+                client_share,
+                client,
+                reply,
+            } => {
+                let _ = reply.send(
+                    actor
+                        .create_authentication_challenge(username, commitment, client_share, client)
+                        .await,
+                );
+            }
+            Command::VerifyAuthentication {
+                auth_id,
+                answer,
+                client,
+                reply,
+            } => {
+                let _ = reply.send(actor.verify_authentication(auth_id, answer, client).await);
+            }
+            Command::RotateCredentials {
+                username,
+                key_version,
+                new_y1,
+                new_y2,
+                proof,
+                reply,
+            } => {
+                let _ = reply.send(
+                    actor
+                        .rotate_credentials(username, key_version, new_y1, new_y2, proof)
+                        .await,
+                );
+            }
+            Command::ValidateSession {
+                session_id,
+                proof,
+                reply,
+            } => {
+                let _ = reply.send(actor.validate_session(session_id, proof).await);
+            }
+            Command::ValidateToken { token, reply } => {
+                let _ = reply.send(actor.validate_token(&token));
+            }
+            Command::RefreshSession {
+                session_id,
+                proof,
+                reply,
+            } => {
+                let _ = reply.send(actor.refresh_session(session_id, proof).await);
+            }
+            Command::RevokeSession {
+                session_id,
+                proof,
+                reply,
+            } => {
+                let _ = reply.send(actor.revoke_session(session_id, proof).await);
+            }
+            Command::TokenKeys { reply } => {
+                let _ = reply.send(actor.token_keys());
+            }
+        }
+    }
+}
+
+#[derive(Default)]
+struct QueueMetrics {
+    queued: AtomicUsize,
+    peak_queued: AtomicUsize,
+    in_flight: AtomicUsize,
+    handled: AtomicU64,
+    rejected: AtomicU64,
+}
+
+/// Snapshot of the actor queue, see [`AuthHandle::queue_stats`].
+#[derive(Debug, PartialEq)]
+pub struct QueueStats {
+    pub capacity: usize,
+    pub queued: usize,
+    /// Most requests queued at once since the previous snapshot.
+    pub peak_queued: usize,
+    /// Most requests handled at once.
+    pub max_in_flight: usize,
+    pub in_flight: usize,
+    pub handled: u64,
+    /// Requests turned away because the queue was full.
+    pub rejected: u64,
+}
+
+/// Sends requests to an [`AuthActor`] running behind a task of its own.
+///
+/// The task takes the requests off a bounded queue and keeps the rate limiter and the queue
+/// metrics. Up to `max_in_flight` requests are handled at once, each in a task that calls the
+/// repositories, verifies proofs on the blocking thread pool, asks the actor task to check and
+/// count the rate limits and replies to the caller. Requests beyond the queue capacity fail right
+/// away with [`AuthActorError::Overloaded`].
+#[derive(Clone)]
+pub struct AuthHandle {
+    commands: mpsc::Sender<Command>,
+    capacity: usize,
+    max_in_flight: usize,
+    metrics: Arc<QueueMetrics>,
+}
+
+impl AuthHandle {
+    /// Panics if `capacity` or `max_in_flight` is 0.
+    pub fn spawn<U, C, P, S>(
+        mut actor: AuthActor<U, C, P, S>,
+        capacity: usize,
+        max_in_flight: usize,
+    ) -> Self
+    where
+        U: UserRepository + 'static,
+        C: ChallengeRepository + 'static,
+        P: PendingChallengeRepository + 'static,
+        S: SessionRepository + 'static,
+    {
+        assert!(max_in_flight > 0, "max_in_flight must be at least 1");
+        let (commands, receiver) = mpsc::channel(capacity);
+        let (limits, limit_requests) = mpsc::unbounded_channel();
+        let rate_limiter = match std::mem::replace(&mut actor.rate_limits, RateLimits::Off) {
+            RateLimits::Local(rate_limiter) => {
+                actor.rate_limits = RateLimits::Actor(limits);
+                Some(rate_limiter)
+            }
+            rate_limits => {
+                actor.rate_limits = rate_limits;
+                None
+            }
+        };
+        let metrics = Arc::new(QueueMetrics::default());
+        tokio::spawn(run(
+            Arc::new(actor),
+            receiver,
+            rate_limiter,
+            limit_requests,
+            Arc::new(Semaphore::new(max_in_flight)),
+            metrics.clone(),
+        ));
+
+        Self {
+            commands,
+            capacity,
+            max_in_flight,
+            metrics,
+        }
+    }
+
+    pub fn queue_stats(&self) -> QueueStats {
+        let queued = self.metrics.queued.load(Ordering::Relaxed);
+        QueueStats {
+            capacity: self.capacity,
+            queued,
+            peak_queued: self.metrics.peak_queued.swap(queued, Ordering::Relaxed),
+            max_in_flight: self.max_in_flight,
+            in_flight: self.metrics.in_flight.load(Ordering::Relaxed),
+            handled: self.metrics.handled.load(Ordering::Relaxed),
+            rejected: self.metrics.rejected.load(Ordering::Relaxed),
+        }
+    }
+
+    async fn call<T>(
+        &self,
+        command: impl FnOnce(Reply<T>) -> Command,
+    ) -> Result<T, AuthActorError> {
+        let (reply, response) = oneshot::channel();
+        let queued = self.metrics.queued.fetch_add(1, Ordering::Relaxed) + 1;
+        if let Err(e) = self.commands.try_send(command(reply)) {
+            self.metrics.queued.fetch_sub(1, Ordering::Relaxed);
+            return Err(match e {
+                mpsc::error::TrySendError::Full(_) => {
+                    self.metrics.rejected.fetch_add(1, Ordering::Relaxed);
+                    AuthActorError::Overloaded
+                }
+                mpsc::error::TrySendError::Closed(_) => AuthActorError::WorkerFailure,
+            });
+        }
+        self.metrics
+            .peak_queued
+            .fetch_max(queued, Ordering::Relaxed);
+
+        response.await.unwrap_or(Err(AuthActorError::WorkerFailure))
+    }
+
+    pub async fn register(
+        &self,
+        username: String,
+        credential: CredentialDTO,
+    ) -> Result<(), AuthActorError> {
+        self.call(|reply| Command::Register {
+            username,
+            credential,
+            reply,
+        })
+        .await
+    }
+
+    pub async fn create_authentication_challenge(
+        &self,
+        username: String,
+        commitment: CommitmentDTO,
+        client_share: Option<i64>,
+        client: ClientMetadataDTO,
+    ) -> Result<AuthChallenge, AuthActorError> {
+        self.call(|reply| Command::CreateAuthenticationChallenge {
+            username,
+            commitment,
+            client_share,
+            client,
+            reply,
+        })
+        .await
+    }
+
+    pub async fn verify_authentication(
+        &self,
+        auth_id: String,
+        answer: AuthAnswer,
+        client: ClientMetadataDTO,
+    ) -> Result<SessionDetails, AuthActorError> {
+        self.call(|reply| Command::VerifyAuthentication {
+            auth_id,
+            answer,
+            client,
+            reply,
+        })
+        .await
+    }
+
+    pub async fn rotate_credentials(
+        &self,
+        username: String,
+        key_version: u64,
+        new_y1: i64,
+        new_y2: i64,
+        proof: RotationProof,
+    ) -> Result<u64, AuthActorError> {
+        self.call(|reply| Command::RotateCredentials {
+            username,
+            key_version,
+            new_y1,
+            new_y2,
+            proof,
+            reply,
+        })
+        .await
+    }
+
+    pub async fn validate_session(
+        &self,
+        session_id: String,
+        proof: Option<SessionProof>,
+    ) -> Result<SessionDetails, AuthActorError> {
+        self.call(|reply| Command::ValidateSession {
+            session_id,
+            proof,
+            reply,
+        })
+        .await
+    }
+
+    pub async fn validate_token(&self, token: String) -> Result<SessionDetails, AuthActorError> {
+        self.call(|reply| Command::ValidateToken { token, reply })
+            .await
+    }
+
+    pub async fn refresh_session(
+        &self,
+        session_id: String,
+        proof: Option<SessionProof>,
+    ) -> Result<SessionDetails, AuthActorError> {
+        self.call(|reply| Command::RefreshSession {
+            session_id,
+            proof,
+            reply,
+        })
+        .await
+    }
+
+    pub async fn revoke_session(
+        &self,
+        session_id: String,
+        proof: Option<SessionProof>,
+    ) -> Result<(), AuthActorError> {
+        self.call(|reply| Command::RevokeSession {
+            session_id,
+            proof,
+            reply,
+        })
+        .await
+    }
+
+    pub async fn token_keys(&self) -> Result<(String, Vec<VerificationKey>), AuthActorError> {
+        self.call(|reply| Command::TokenKeys { reply }).await
+    }
+}
+
+/// Counts a request as in flight until it is handled, or its handler panics.
+struct InFlight(Arc<QueueMetrics>);
+
+impl InFlight {
+    fn start(metrics: Arc<QueueMetrics>) -> Self {
+        metrics.queued.fetch_sub(1, Ordering::Relaxed);
+        metrics.in_flight.fetch_add(1, Ordering::Relaxed);
+        Self(metrics)
+    }
+}
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        self.0.in_flight.fetch_sub(1, Ordering::Relaxed);
+        self.0.handled.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// Hands the queued requests to handlers while one of the `permits` is free and answers their
+/// rate limit calls, until every [`AuthHandle`] is dropped.
+async fn run<U, C, P, S>(
+    actor: Arc<AuthActor<U, C, P, S>>,
+    mut commands: mpsc::Receiver<Command>,
+    rate_limiter: Option<RateLimiter>,
+    mut limit_requests: mpsc::UnboundedReceiver<LimitRequest>,
+    permits: Arc<Semaphore>,
+    metrics: Arc<QueueMetrics>,
+) where
+    U: UserRepository + 'static,
+    C: ChallengeRepository + 'static,
+    P: PendingChallengeRepository + 'static,
+    S: SessionRepository + 'static,
+{
+    loop {
+        tokio::select! {
+            Some(request) = limit_requests.recv() => {
+                if let Some(rate_limiter) = &rate_limiter {
+                    request.handle(rate_limiter);
+                }
+            }
+            next = next_command(&permits, &mut commands) => {
+                let Some((permit, command)) = next else {
+                    break;
+                };
+                let (actor, in_flight) = (actor.clone(), InFlight::start(metrics.clone()));
+                tokio::spawn(async move {
+                    command.handle(&actor).await;
+                    drop((in_flight, permit));
+                });
+            }
+        }
+    }
+}
+
+/// Waits for a free handler before taking the next request, so the ones beyond `max_in_flight`
+/// stay in the queue.
+async fn next_command(
+    permits: &Arc<Semaphore>,
+    commands: &mut mpsc::Receiver<Command>,
+) -> Option<(OwnedSemaphorePermit, Command)> {
+    let permit = permits
+        .clone()
+        .acquire_owned()
+        .await
+        .expect("the permits are never closed");
+    Some((permit, commands.recv().await?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rate_limit::RateLimitPolicy;
+    use crate::repository::{
+        in_memory::InMemoryRepository, ChallengeDTO, DBResult, PendingChallengesDTO, Repository,
+        SessionDTO, UserDTO,
+    };
+    use async_trait::async_trait;
+    use std::sync::atomic::AtomicBool;
+    use std::time::Duration;
+    use tokio::sync::Barrier;
+    use zkp::chaum_pedersen::{ChaumPedersenProtocol, Context};
+    use zkp::server_auth::ServerAuthProtocol;
+
+    type TestActor<U> = AuthActor<
+        U,
+        InMemoryRepository<ChallengeDTO>,
+        InMemoryRepository<PendingChallengesDTO>,
+        InMemoryRepository<SessionDTO>,
+    >;
+
+    const USERNAME: &str = "alice";
+    const SECRET: i64 = 9;
+    const CHALLENGE_K: i64 = 27;
+
+    /// User repository that never answers a read, keeping the request in flight.
+    #[derive(Default)]
+    struct StalledUserRepository(InMemoryRepository<UserDTO>);
+
+    #[async_trait]
+    impl Repository<UserDTO> for StalledUserRepository {
+        async fn insert(&self, key: &str, value: &UserDTO) -> DBResult<()> {
+            self.0.insert(key, value).await
+        }
+
+        async fn put(&self, key: &str, value: &UserDTO) -> DBResult<()> {
+            self.0.put(key, value).await
+        }
+
+        async fn get(&self, _key: &str) -> DBResult<Option<UserDTO>> {
+            std::future::pending().await
+        }
+
+        async fn delete(&self, key: &str) -> DBResult<()> {
+            self.0.delete(key).await
+        }
+
+        async fn exists(&self, key: &str) -> DBResult<bool> {
+            self.0.exists(key).await
+        }
+
+        async fn compare_and_swap(
+            &self,
+            key: &str,
+            expected: &UserDTO,
+            value: &UserDTO,
+        ) -> DBResult<()> {
+            self.0.compare_and_swap(key, expected, value).await
+        }
+    }
+
+    /// User repository whose reads, once armed, wait until as many reads are waiting as the
+    /// barrier holds.
+    struct RendezvousUserRepository {
+        users: InMemoryRepository<UserDTO>,
+        armed: Arc<AtomicBool>,
+        barrier: Barrier,
+    }
+
+    #[async_trait]
+    impl Repository<UserDTO> for RendezvousUserRepository {
+        async fn insert(&self, key: &str, value: &UserDTO) -> DBResult<()> {
+            self.users.insert(key, value).await
+        }
+
+        async fn put(&self, key: &str, value: &UserDTO) -> DBResult<()> {
+            self.users.put(key, value).await
+        }
+
+        async fn get(&self, key: &str) -> DBResult<Option<UserDTO>> {
+            if self.armed.load(Ordering::Relaxed) {
+                self.barrier.wait().await;
+            }
+            self.users.get(key).await
+        }
+
+        async fn delete(&self, key: &str) -> DBResult<()> {
+            self.users.delete(key).await
+        }
+
+        async fn exists(&self, key: &str) -> DBResult<bool> {
+            self.users.exists(key).await
+        }
+
+        async fn compare_and_swap(
+            &self,
+            key: &str,
+            expected: &UserDTO,
+            value: &UserDTO,
+        ) -> DBResult<()> {
+            self.users.compare_and_swap(key, expected, value).await
+        }
+    }
+
+    fn ctx() -> Context {
+        Context::new(4, 9, 20123, 10061).unwrap()
+    }
+
+    fn actor<U: UserRepository>(users: U) -> TestActor<U> {
+        let server_key = ServerAuthProtocol::new(ctx()).key_from_secret(7).unwrap();
+        AuthActor::new(
+            ctx(),
+            server_key,
+            users,
+            InMemoryRepository::default(),
+            InMemoryRepository::default(),
+            InMemoryRepository::default(),
+        )
+    }
+
+    fn spawn<U: UserRepository + Default + 'static>(
+        capacity: usize,
+        max_in_flight: usize,
+    ) -> AuthHandle {
+        AuthHandle::spawn(actor(U::default()), capacity, max_in_flight)
+    }
+
+    async fn register(handle: &AuthHandle, zkp: &ChaumPedersenProtocol) {
+        let (y1, y2) = zkp.calculate_registration_data(SECRET).unwrap();
+        assert!(handle
+            .register(USERNAME.into(), CredentialDTO::ChaumPedersen { y1, y2 })
+            .await
+            .is_ok());
+    }
+
+    fn answer(
+        handle: &AuthHandle,
+        challenge: AuthChallenge,
+        s: i64,
+    ) -> impl std::future::Future<Output = Result<SessionDetails, AuthActorError>> {
+        let handle = handle.clone();
+        async move {
+            handle
+                .verify_authentication(
+                    challenge.auth_id,
+                    AuthAnswer::ChaumPedersen { s },
+                    ClientMetadataDTO::default(),
+                )
+                .await
+        }
+    }
+
+    fn create_challenge(
+        handle: &AuthHandle,
+        zkp: &ChaumPedersenProtocol,
+    ) -> impl std::future::Future<Output = Result<AuthChallenge, AuthActorError>> {
+        let (r1, r2) = zkp.calculate_login_challenge_data(CHALLENGE_K).unwrap();
+        let handle = handle.clone();
+        async move {
+            handle
+                .create_authentication_challenge(
+                    USERNAME.into(),
+                    CommitmentDTO::ChaumPedersen { r1, r2 },
+                    None,
+                    ClientMetadataDTO::default(),
+                )
+                .await
+        }
+    }
+
+    #[tokio::test]
+    async fn should_log_in_through_handle() {
+        let handle = spawn::<InMemoryRepository<UserDTO>>(DEFAULT_QUEUE_CAPACITY, 1);
+        let zkp = ChaumPedersenProtocol::new(ctx());
+        register(&handle, &zkp).await;
+
+        let challenge = create_challenge(&handle, &zkp).await.ok().unwrap();
+        let s = zkp
+            .calculate_challenge(CHALLENGE_K, challenge.c, SECRET)
+            .unwrap();
+        let session = answer(&handle, challenge, s).await.ok().unwrap();
+        assert!(handle
+            .validate_session(session.session_id, None)
+            .await
+            .is_ok());
+
+        let stats = handle.queue_stats();
+        assert_eq!((stats.queued, stats.in_flight), (0, 0));
+        assert_eq!((stats.handled, stats.rejected), (4, 0));
+        assert_eq!(stats.peak_queued, 1);
+    }
+
+    #[tokio::test]
+    async fn should_verify_answers_concurrently() {
+        let armed = Arc::new(AtomicBool::new(false));
+        let users = RendezvousUserRepository {
+            users: InMemoryRepository::default(),
+            armed: armed.clone(),
+            barrier: Barrier::new(2),
+        };
+        let handle = AuthHandle::spawn(actor(users), DEFAULT_QUEUE_CAPACITY, 2);
+        let zkp = ChaumPedersenProtocol::new(ctx());
+        register(&handle, &zkp).await;
+        let first = create_challenge(&handle, &zkp).await.ok().unwrap();
+        let second = create_challenge(&handle, &zkp).await.ok().unwrap();
+
+        // Each verification reads the user only once the other one reads it too, so they only
+        // finish if they overlap.
+        armed.store(true, Ordering::Relaxed);
+        let (first_s, second_s) = (
+            zkp.calculate_challenge(CHALLENGE_K, first.c, SECRET)
+                .unwrap(),
+            zkp.calculate_challenge(CHALLENGE_K, second.c, SECRET)
+                .unwrap(),
+        );
+        let (first, second) = tokio::time::timeout(Duration::from_secs(5), async {
+            tokio::join!(
+                answer(&handle, first, first_s),
+                answer(&handle, second, second_s)
+            )
+        })
+        .await
+        .expect("verifications did not overlap");
+        assert!(first.is_ok() && second.is_ok());
+        assert_eq!(handle.queue_stats().in_flight, 0);
+    }
+
+    #[tokio::test]
+    async fn should_rate_limit_through_handle() {
+        let actor = actor(InMemoryRepository::default()).with_rate_limits(RateLimitPolicy {
+            free_failures: 0,
+            backoff: Duration::from_secs(5),
+            ..Default::default()
+        });
+        let handle = AuthHandle::spawn(actor, DEFAULT_QUEUE_CAPACITY, DEFAULT_MAX_IN_FLIGHT);
+        let zkp = ChaumPedersenProtocol::new(ctx());
+        register(&handle, &zkp).await;
+
+        let challenge = create_challenge(&handle, &zkp).await.ok().unwrap();
+        assert!(matches!(
+            answer(&handle, challenge, 1).await,
+            Err(AuthActorError::AuthChallengeFailed)
+        ));
+        assert!(matches!(
+            create_challenge(&handle, &zkp).await,
+            Err(AuthActorError::RateLimited(retry_after)) if retry_after.as_secs() <= 5
+        ));
+    }
+
+    #[tokio::test]
+    async fn should_reject_requests_beyond_queue_capacity() {
+        let handle = spawn::<StalledUserRepository>(1, 1);
+        let zkp = ChaumPedersenProtocol::new(ctx());
+
+        tokio::spawn(create_challenge(&handle, &zkp));
+        while handle.queue_stats().in_flight == 0 {
+            tokio::task::yield_now().await;
+        }
+        tokio::spawn(create_challenge(&handle, &zkp));
+        while handle.queue_stats().queued == 0 {
+            tokio::task::yield_now().await;
+        }
+
+        assert!(matches!(
+            create_challenge(&handle, &zkp).await,
+            Err(AuthActorError::Overloaded)
+        ));
+        assert_eq!(
+            handle.queue_stats(),
+            QueueStats {
+                capacity: 1,
+                queued: 1,
+                peak_queued: 1,
+                max_in_flight: 1,
+                in_flight: 1,
+                handled: 0,
+                rejected: 1,
+            }
+        );
+    }
+}
@@ -0,0 +1,48 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Source of the current time, so tests can control expiry.
+pub trait Clock: Send + Sync {
+    /// Seconds since the Unix epoch.
+    fn now(&self) -> u64;
+}
+
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("System time must be after the Unix epoch")
+            .as_secs()
+    }
+}
+
+#[cfg(test)]
+pub use manual::ManualClock;
+
+#[cfg(test)]
+mod manual {
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::time::Duration;
+
+    use super::Clock;
+
+    /// Clock that only moves when told to.
+    pub struct ManualClock(AtomicU64);
+
+    impl ManualClock {
+        pub fn new(now: u64) -> Self {
+            Self(AtomicU64::new(now))
+        }
+
+        pub fn advance(&self, duration: Duration) {
+            self.0.fetch_add(duration.as_secs(), Ordering::Relaxed);
+        }
+    }
+
+    impl Clock for ManualClock {
+        fn now(&self) -> u64 {
+            self.0.load(Ordering::Relaxed)
+        }
+    }
+}
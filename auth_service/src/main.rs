@@ -1,16 +1,45 @@
 use std::env;
-use std::sync::{Arc, Mutex};
+use std::fs;
+use std::net::{IpAddr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
 use tonic::{transport::Server, Request, Response, Status};
-use zkp::chaum_pedersen;
+use zkp::{chaum_pedersen, rotation::RotationProof, server_auth::ServerAuthProtocol, ZKPError};
 use zkp_auth::{
     auth_server::{Auth, AuthServer},
     AuthenticationAnswerRequest, AuthenticationAnswerResponse, AuthenticationChallengeRequest,
-    AuthenticationChallengeResponse, RegisterRequest, RegisterResponse,
+    AuthenticationChallengeResponse, ClientMetadata, GetTokenKeysRequest, GetTokenKeysResponse,
+    KeyShare, OkamotoAnswer, OkamotoCommitment, OkamotoCredential, RefreshSessionRequest,
+    RefreshSessionResponse, RegisterRequest, RegisterResponse, RevokeSessionRequest,
+    RevokeSessionResponse, RotateCredentialsRequest, RotateCredentialsResponse, ServerProof,
+    Session, TokenKey, ValidateSessionRequest, ValidateSessionResponse,
 };
 
-use auth::{error::AuthActorError, AuthActor};
+use auth::{
+    error::AuthActorError,
+    handle::{AuthHandle, DEFAULT_MAX_IN_FLIGHT, DEFAULT_QUEUE_CAPACITY},
+    AuthActor, AuthAnswer, SessionDetails, SessionProof, DEFAULT_CHALLENGE_TTL,
+    DEFAULT_MAX_PENDING_CHALLENGES, DEFAULT_SESSION_IDLE_TTL, DEFAULT_SESSION_LIFETIME,
+};
+use repository::{
+    in_memory::InMemoryRepository,
+    log_store::{CompactionPolicy, LogRepository},
+    redis_store::RedisDatabase,
+    sqlite::SqliteDatabase,
+    ChallengeDTO, ClientMetadataDTO, CommitmentDTO, CredentialDTO, PendingChallengesDTO,
+    Repository, SessionDTO, UserDTO,
+};
 mod auth;
+mod clock;
+mod rate_limit;
 mod repository;
+mod sealing;
+mod token;
+
+use rate_limit::RateLimitPolicy;
+use sealing::KeySealer;
+use token::{TokenSigner, DEFAULT_TOKEN_TTL, TOKEN_ALGORITHM};
 
 pub mod zkp_auth {
     tonic::include_proto!("zkp_auth");
@@ -19,19 +48,57 @@ pub mod zkp_auth {
 const SERVER_LISTENING_ADDR_ENV: &str = "SERVER_LISTENING_ADDR";
 const ZKP_G_ENV: &str = "ZKP_G";
 const ZKP_H_ENV: &str = "ZKP_H";
+const ZKP_P_ENV: &str = "ZKP_P";
 const ZKP_Q_ENV: &str = "ZKP_Q";
+const SERVER_SECRET_KEY_ENV: &str = "SERVER_SECRET_KEY";
+const STORAGE_BACKEND_ENV: &str = "STORAGE_BACKEND";
+const DATABASE_PATH_ENV: &str = "DATABASE_PATH";
+const REDIS_URL_ENV: &str = "REDIS_URL";
+const CHALLENGE_TTL_SECS_ENV: &str = "CHALLENGE_TTL_SECS";
+const MAX_PENDING_CHALLENGES_ENV: &str = "MAX_PENDING_CHALLENGES";
+const SESSION_IDLE_TTL_SECS_ENV: &str = "SESSION_IDLE_TTL_SECS";
+const SESSION_LIFETIME_SECS_ENV: &str = "SESSION_LIFETIME_SECS";
+const SESSION_MODE_ENV: &str = "SESSION_MODE";
+const RATE_LIMIT_WINDOW_SECS_ENV: &str = "RATE_LIMIT_WINDOW_SECS";
+const RATE_LIMIT_PER_USER_ENV: &str = "RATE_LIMIT_PER_USER";
+const RATE_LIMIT_PER_PEER_ENV: &str = "RATE_LIMIT_PER_PEER";
+const FREE_LOGIN_FAILURES_ENV: &str = "FREE_LOGIN_FAILURES";
+const LOGIN_BACKOFF_SECS_ENV: &str = "LOGIN_BACKOFF_SECS";
+const MAX_LOGIN_BACKOFF_SECS_ENV: &str = "MAX_LOGIN_BACKOFF_SECS";
+const LOCKOUT_FAILURES_ENV: &str = "LOCKOUT_FAILURES";
+const LOCKOUT_SECS_ENV: &str = "LOCKOUT_SECS";
+const SESSION_KEY_SEALING_KEY_ENV: &str = "SESSION_KEY_SEALING_KEY";
+const TOKEN_SIGNING_KEYS_ENV: &str = "TOKEN_SIGNING_KEYS";
+const TOKEN_TTL_SECS_ENV: &str = "TOKEN_TTL_SECS";
+const TRUSTED_PROXIES_ENV: &str = "TRUSTED_PROXIES";
+const ACTOR_QUEUE_CAPACITY_ENV: &str = "ACTOR_QUEUE_CAPACITY";
+const ACTOR_MAX_IN_FLIGHT_ENV: &str = "ACTOR_MAX_IN_FLIGHT";
+const QUEUE_METRICS_INTERVAL_SECS_ENV: &str = "QUEUE_METRICS_INTERVAL_SECS";
 
-const LOG_TARGET: &str = "auth_service";
+const STORAGE_BACKEND_MEMORY: &str = "memory";
+const STORAGE_BACKEND_SQLITE: &str = "sqlite";
+const STORAGE_BACKEND_LOG: &str = "log";
+
+const SESSION_MODE_STORED: &str = "stored";
+const SESSION_MODE_TOKEN: &str = "token";
 
-type Actor = Arc<Mutex<AuthActor>>;
+const REDIS_KEY_PREFIX: &str = "zkp_auth";
+const RETRY_AFTER_METADATA: &str = "retry-after";
+
+const LOG_TARGET: &str = "auth_service";
 
 pub struct AuthService {
-    auth_actor: Actor,
+    auth_actor: AuthHandle,
+    /// Peers trusted to report the address of the client they forward, e.g. the router.
+    trusted_proxies: Vec<IpAddr>,
 }
 
 impl AuthService {
-    pub fn new(auth_actor: Actor) -> Self {
-        Self { auth_actor }
+    pub fn new(auth_actor: AuthHandle, trusted_proxies: Vec<IpAddr>) -> Self {
+        Self {
+            auth_actor,
+            trusted_proxies,
+        }
     }
 }
 
@@ -44,17 +111,26 @@ impl Auth for AuthService {
         let request = request.into_inner();
         log::info!("Handling register request: {:?}", request);
 
-        let actor = &mut self
-            .auth_actor
-            .lock()
-            .expect("Auth Actor must be available");
-        match actor.register(request.user, request.y1, request.y2) {
+        let actor = &self.auth_actor;
+        let credential = match request.okamoto {
+            Some(OkamotoCredential { y }) => CredentialDTO::Okamoto { y },
+            None => CredentialDTO::ChaumPedersen {
+                y1: request.y1,
+                y2: request.y2,
+            },
+        };
+        match actor.register(request.user, credential).await {
             Ok(_) => Ok(Response::new(RegisterResponse {})),
             Err(e) => {
                 let (code, msg) = match e {
                     AuthActorError::UserAlreadyRegistered => {
                         (tonic::Code::AlreadyExists, "UserAlreadyRegistered")
                     }
+                    AuthActorError::InvalidProofData(e) => return Err(zkp_error_status(e)),
+                    AuthActorError::StorageFailure(reason) => {
+                        return Err(storage_failure_status(reason))
+                    }
+                    AuthActorError::Overloaded => return Err(overloaded_status()),
                     _ => (tonic::Code::Internal, "Unexpected server error"),
                 };
 
@@ -67,26 +143,62 @@ impl Auth for AuthService {
         &self,
         request: Request<AuthenticationChallengeRequest>,
     ) -> Result<Response<AuthenticationChallengeResponse>, Status> {
+        let peer_address = request.remote_addr();
         let request = request.into_inner();
         log::info!(
             "Handling create authentication challenge request: {:?}",
             request
         );
 
-        let actor = &mut self
-            .auth_actor
-            .lock()
-            .expect("Auth Actor must be available");
-        match actor.create_authentication_challenge(request.user, request.r1, request.r2) {
-            Ok(auth::AuthChallenge { auth_id, c }) => {
-                Ok(Response::new(AuthenticationChallengeResponse {
-                    auth_id,
-                    c,
-                }))
-            }
+        let actor = &self.auth_actor;
+        let commitment = match request.okamoto {
+            Some(OkamotoCommitment { t }) => CommitmentDTO::Okamoto { t },
+            None => CommitmentDTO::ChaumPedersen {
+                r1: request.r1,
+                r2: request.r2,
+            },
+        };
+        let client_share = request.key_share.map(|KeyShare { share }| share);
+        let client = client_metadata(request.client, peer_address, &self.trusted_proxies);
+        match actor
+            .create_authentication_challenge(request.user, commitment, client_share, client)
+            .await
+        {
+            Ok(auth::AuthChallenge {
+                auth_id,
+                c,
+                key_share,
+                server_proof,
+            }) => Ok(Response::new(AuthenticationChallengeResponse {
+                auth_id,
+                c,
+                key_share: key_share.map(|share| KeyShare { share }),
+                server_proof: Some(ServerProof {
+                    c: server_proof.c,
+                    s: server_proof.s,
+                }),
+            })),
             Err(e) => {
                 let (code, msg) = match e {
                     AuthActorError::UserNotFound => (tonic::Code::NotFound, "User not found"),
+                    AuthActorError::TooManyPendingChallenges => (
+                        tonic::Code::ResourceExhausted,
+                        "Too many pending challenges",
+                    ),
+                    AuthActorError::RateLimited(retry_after) => {
+                        return Err(rate_limited_status(retry_after))
+                    }
+                    AuthActorError::AccountLocked(retry_after) => {
+                        return Err(account_locked_status(retry_after))
+                    }
+                    AuthActorError::CredentialTypeMismatch => {
+                        (tonic::Code::InvalidArgument, "Credential type mismatch")
+                    }
+                    AuthActorError::InvalidProofData(e) => return Err(zkp_error_status(e)),
+                    AuthActorError::StorageFailure(reason) => {
+                        return Err(storage_failure_status(reason))
+                    }
+                    AuthActorError::Overloaded => return Err(overloaded_status()),
                     _ => (tonic::Code::Internal, "Unexpected server error"),
                 };
 
@@ -99,31 +211,107 @@ impl Auth for AuthService {
         &self,
         request: Request<AuthenticationAnswerRequest>,
     ) -> Result<Response<AuthenticationAnswerResponse>, Status> {
+        let peer_address = request.remote_addr();
         let request = request.into_inner();
         log::info!("Handling verify authentication request: {:?}", request);
 
-        let actor = &mut self
-            .auth_actor
-            .lock()
-            .expect("Auth Actor must be available");
-        match actor.verify_authentication(request.auth_id, request.s) {
-            Ok(auth::SessionDetails { session_id }) => {
-                Ok(Response::new(AuthenticationAnswerResponse { session_id }))
-            }
+        let actor = &self.auth_actor;
+        let answer = match request.okamoto {
+            Some(OkamotoAnswer { s1, s2 }) => AuthAnswer::Okamoto { s1, s2 },
+            None => AuthAnswer::ChaumPedersen { s: request.s },
+        };
+        let client = client_metadata(request.client, peer_address, &self.trusted_proxies);
+        match actor
+            .verify_authentication(request.auth_id, answer, client)
+            .await
+        {
+            Ok(SessionDetails {
+                session_id,
+                expires_at,
+                token,
+                ..
+            }) => Ok(Response::new(AuthenticationAnswerResponse {
+                session_id,
+                expires_at,
+                token: token.unwrap_or_default(),
+            })),
             Err(e) => {
                 let (code, msg) = match e {
                     AuthActorError::UserNotFound => (tonic::Code::NotFound, "User not found"),
                     AuthActorError::AuthChallengeNotFound => {
                         (tonic::Code::NotFound, "Challenge not found")
                     }
+                    AuthActorError::AuthChallengeExpired => {
+                        (tonic::Code::DeadlineExceeded, "Challenge expired")
+                    }
+                    AuthActorError::RateLimited(retry_after) => {
+                        return Err(rate_limited_status(retry_after))
+                    }
+                    AuthActorError::AccountLocked(retry_after) => {
+                        return Err(account_locked_status(retry_after))
+                    }
+                    AuthActorError::CredentialTypeMismatch => {
+                        (tonic::Code::InvalidArgument, "Credential type mismatch")
+                    }
                     AuthActorError::AuthChallengeFailed => (
                         tonic::Code::Unauthenticated,
                         "Negative challenge verification",
                     ),
-                    AuthActorError::ZKPMathError => (
-                        tonic::Code::ResourceExhausted,
-                        "Arithmetic overflow during challenge verification",
+                    AuthActorError::InvalidProofData(e) => return Err(zkp_error_status(e)),
+                    AuthActorError::StorageFailure(reason) => {
+                        return Err(storage_failure_status(reason))
+                    }
+                    AuthActorError::Overloaded => return Err(overloaded_status()),
+                    _ => (tonic::Code::Internal, "Unexpected server error"),
+                };
+
+                Err(Status::new(code, msg))
+            }
+        }
+    }
+
+    async fn rotate_credentials(
+        &self,
+        request: Request<RotateCredentialsRequest>,
+    ) -> Result<Response<RotateCredentialsResponse>, Status> {
+        let request = request.into_inner();
+        log::info!("Handling rotate credentials request: {:?}", request);
+
+        let actor = &self.auth_actor;
+        let proof = RotationProof {
+            c: request.c,
+            s: request.s,
+            new_s: request.new_s,
+        };
+        match actor
+            .rotate_credentials(
+                request.user,
+                request.key_version,
+                request.new_y1,
+                request.new_y2,
+                proof,
+            )
+            .await
+        {
+            Ok(key_version) => Ok(Response::new(RotateCredentialsResponse { key_version })),
+            Err(e) => {
+                let (code, msg) = match e {
+                    AuthActorError::UserNotFound => (tonic::Code::NotFound, "User not found"),
+                    AuthActorError::CredentialTypeMismatch => {
+                        (tonic::Code::InvalidArgument, "Credential type mismatch")
+                    }
+                    AuthActorError::AuthChallengeFailed => (
+                        tonic::Code::Unauthenticated,
+                        "Negative rotation proof verification",
                     ),
+                    AuthActorError::CredentialsChanged => {
+                        (tonic::Code::Aborted, "Credentials changed during rotation")
+                    }
+                    AuthActorError::InvalidProofData(e) => return Err(zkp_error_status(e)),
+                    AuthActorError::StorageFailure(reason) => {
+                        return Err(storage_failure_status(reason))
+                    }
+                    AuthActorError::Overloaded => return Err(overloaded_status()),
                     _ => (tonic::Code::Internal, "Unexpected server error"),
                 };
 
@@ -131,29 +319,248 @@ impl Auth for AuthService {
             }
         }
     }
+
+    async fn validate_session(
+        &self,
+        request: Request<ValidateSessionRequest>,
+    ) -> Result<Response<ValidateSessionResponse>, Status> {
+        log::info!("Handling validate session request");
+
+        let request = request.into_inner();
+        let actor = &self.auth_actor;
+        let proof = session_proof(request.counter, request.tag);
+        let session = match request.token.is_empty() {
+            true => actor.validate_session(request.session_id, proof).await,
+            false => actor.validate_token(request.token).await,
+        };
+        match session {
+            Ok(session) => Ok(Response::new(ValidateSessionResponse {
+                session: Some(session_message(session)),
+            })),
+            Err(e) => Err(session_error_status(e)),
+        }
+    }
+
+    async fn refresh_session(
+        &self,
+        request: Request<RefreshSessionRequest>,
+    ) -> Result<Response<RefreshSessionResponse>, Status> {
+        log::info!("Handling refresh session request");
+
+        let request = request.into_inner();
+        match self
+            .auth_actor
+            .refresh_session(
+                request.session_id,
+                session_proof(request.counter, request.tag),
+            )
+            .await
+        {
+            Ok(session) => Ok(Response::new(RefreshSessionResponse {
+                session: Some(session_message(session)),
+            })),
+            Err(e) => Err(session_error_status(e)),
+        }
+    }
+
+    async fn revoke_session(
+        &self,
+        request: Request<RevokeSessionRequest>,
+    ) -> Result<Response<RevokeSessionResponse>, Status> {
+        log::info!("Handling revoke session request");
+
+        let request = request.into_inner();
+        match self
+            .auth_actor
+            .revoke_session(
+                request.session_id,
+                session_proof(request.counter, request.tag),
+            )
+            .await
+        {
+            Ok(_) => Ok(Response::new(RevokeSessionResponse {})),
+            Err(e) => Err(session_error_status(e)),
+        }
+    }
+
+    async fn get_token_keys(
+        &self,
+        _request: Request<GetTokenKeysRequest>,
+    ) -> Result<Response<GetTokenKeysResponse>, Status> {
+        log::info!("Handling get token keys request");
+
+        match self.auth_actor.token_keys().await {
+            Ok((signing_key_id, keys)) => Ok(Response::new(GetTokenKeysResponse {
+                signing_key_id,
+                keys: keys
+                    .into_iter()
+                    .map(|key| TokenKey {
+                        key_id: key.key_id,
+                        algorithm: TOKEN_ALGORITHM.into(),
+                        public_key: key.public_key,
+                    })
+                    .collect(),
+            })),
+            Err(e) => Err(session_error_status(e)),
+        }
+    }
+}
+
+/// Identifies the client by the IP address of the peer, or by the address a trusted proxy
+/// reported for it. Logins are rate limited by this address, so any other caller cannot pick it.
+fn client_metadata(
+    client: Option<ClientMetadata>,
+    peer_address: Option<SocketAddr>,
+    trusted_proxies: &[IpAddr],
+) -> ClientMetadataDTO {
+    let ClientMetadata {
+        address,
+        user_agent,
+    } = client.unwrap_or_default();
+    let peer_ip = peer_address.map(|address| address.ip());
+    let address = match peer_ip {
+        Some(ip) if trusted_proxies.contains(&ip) => Some(address.parse().unwrap_or(ip)),
+        peer_ip => peer_ip,
+    };
+    ClientMetadataDTO {
+        address: address.map(|address: IpAddr| address.to_string()),
+        user_agent: Some(user_agent).filter(|user_agent| !user_agent.is_empty()),
+    }
+}
+
+/// Requests without a tag carry no proof, which only sessions without a key accept.
+fn session_proof(counter: u64, tag: Vec<u8>) -> Option<SessionProof> {
+    Some(SessionProof { counter, tag }).filter(|proof| !proof.tag.is_empty())
+}
+
+fn session_message(session: SessionDetails) -> Session {
+    Session {
+        session_id: session.session_id,
+        user: session.username,
+        created_at: session.created_at,
+        last_seen_at: session.last_seen_at,
+        expires_at: session.expires_at,
+        client: Some(ClientMetadata {
+            address: session.client.address.unwrap_or_default(),
+            user_agent: session.client.user_agent.unwrap_or_default(),
+        }),
+    }
+}
+
+fn session_error_status(error: AuthActorError) -> Status {
+    let (code, msg) = match error {
+        AuthActorError::SessionNotFound => (tonic::Code::Unauthenticated, "Session not found"),
+        AuthActorError::SessionExpired => (tonic::Code::Unauthenticated, "Session expired"),
+        AuthActorError::InvalidSessionProof => {
+            (tonic::Code::Unauthenticated, "Invalid session proof")
+        }
+        AuthActorError::InvalidToken => (tonic::Code::Unauthenticated, "Invalid token"),
+        AuthActorError::SessionsNotStored => {
+            (tonic::Code::Unimplemented, "Sessions are issued as tokens")
+        }
+        AuthActorError::SessionsNotStateless => {
+            (tonic::Code::Unimplemented, "Tokens are not issued")
+        }
+        AuthActorError::StorageFailure(reason) => return storage_failure_status(reason),
+        AuthActorError::Overloaded => return overloaded_status(),
+        _ => (tonic::Code::Internal, "Unexpected server error"),
+    };
+
+    Status::new(code, msg)
+}
+
+/// Maps proof errors onto gRPC codes, malformed client input is an invalid argument.
+fn zkp_error_status(error: ZKPError) -> Status {
+    let code = match error {
+        ZKPError::LengthMismatch { .. }
+        | ZKPError::ElementNotInSubgroup { .. }
+        | ZKPError::ScalarOutOfRange { .. }
+        | ZKPError::MalformedEncoding { .. }
+        | ZKPError::InvalidKeyShare => tonic::Code::InvalidArgument,
+        ZKPError::VerificationFailed => tonic::Code::Unauthenticated,
+        ZKPError::RngFailure => tonic::Code::Unavailable,
+        ZKPError::InvalidParameters { .. }
+        | ZKPError::EmptyBases
+        | ZKPError::InvalidShares { .. }
+        | ZKPError::InvalidBackupShare { .. }
+        | ZKPError::InvalidMnemonic { .. }
+        | ZKPError::InvalidKeystore { .. }
+        | ZKPError::WrongPassword => tonic::Code::Internal,
+    };
+
+    Status::new(code, error.to_string())
+}
+
+/// Tells the client in the `retry-after` metadata how many seconds to wait.
+fn throttled_status(code: tonic::Code, msg: &str, retry_after: Duration) -> Status {
+    let mut status = Status::new(code, msg);
+    status
+        .metadata_mut()
+        .insert(RETRY_AFTER_METADATA, retry_after.as_secs().into());
+    status
+}
+
+fn rate_limited_status(retry_after: Duration) -> Status {
+    throttled_status(
+        tonic::Code::ResourceExhausted,
+        "Too many requests",
+        retry_after,
+    )
+}
+
+fn account_locked_status(retry_after: Duration) -> Status {
+    throttled_status(
+        tonic::Code::FailedPrecondition,
+        "Account temporarily locked",
+        retry_after,
+    )
+}
+
+/// The actor queue is full, the client can retry on another replica or later.
+fn overloaded_status() -> Status {
+    Status::new(tonic::Code::Unavailable, "Service overloaded")
+}
+
+fn storage_failure_status(reason: String) -> Status {
+    log::error!(target: LOG_TARGET, "Storage failure: {}", reason);
+    Status::new(tonic::Code::Unavailable, "Storage unavailable")
 }
 
 fn read_env_var(name: &str) -> String {
     env::var(name).unwrap_or_else(|_| panic!("Missing env variable: {:?}", name))
 }
 
+fn read_optional_env_var<T: FromStr>(name: &str) -> Option<T> {
+    env::var(name).ok().map(|value| {
+        value
+            .parse()
+            .unwrap_or_else(|_| panic!("Invalid value set for {:?}", name))
+    })
+}
+
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     env_logger::init_from_env(env_logger::Env::new().default_filter_or("info"));
 
     let server_address = read_env_var(SERVER_LISTENING_ADDR_ENV)
         .parse()
-        .expect(format!("Invalid value set for {:?}", SERVER_LISTENING_ADDR_ENV).as_str());
+        .unwrap_or_else(|_| panic!("Invalid value set for {:?}", SERVER_LISTENING_ADDR_ENV));
 
     let zkp_g = read_env_var(ZKP_G_ENV)
         .parse()
-        .expect(format!("Invalid value set for {:?}", ZKP_G_ENV).as_str());
+        .unwrap_or_else(|_| panic!("Invalid value set for {:?}", ZKP_G_ENV));
     let zkp_h = read_env_var(ZKP_H_ENV)
         .parse()
-        .expect(format!("Invalid value set for {:?}", ZKP_H_ENV).as_str());
+        .unwrap_or_else(|_| panic!("Invalid value set for {:?}", ZKP_H_ENV));
+    let zkp_p = read_env_var(ZKP_P_ENV)
+        .parse()
+        .unwrap_or_else(|_| panic!("Invalid value set for {:?}", ZKP_P_ENV));
     let zkp_q = read_env_var(ZKP_Q_ENV)
         .parse()
-        .expect(format!("Invalid value set for {:?}", ZKP_Q_ENV).as_str());
+        .unwrap_or_else(|_| panic!("Invalid value set for {:?}", ZKP_Q_ENV));
+    let server_secret_key = read_env_var(SERVER_SECRET_KEY_ENV)
+        .parse()
+        .unwrap_or_else(|_| panic!("Invalid value set for {:?}", SERVER_SECRET_KEY_ENV));
 
     log::info!(
         target: LOG_TARGET,
@@ -161,10 +568,86 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         server_address
     );
 
-    let zkp_ctx = chaum_pedersen::Context::new(zkp_g, zkp_h, zkp_q);
-    let auth_actor = Arc::new(Mutex::new(AuthActor::new(zkp_ctx)));
+    let zkp_ctx =
+        chaum_pedersen::Context::new(zkp_g, zkp_h, zkp_p, zkp_q).expect("Invalid ZKP parameters");
+    let server_key = ServerAuthProtocol::new(zkp_ctx.clone())
+        .key_from_secret(server_secret_key)
+        .unwrap_or_else(|_| panic!("Invalid value set for {:?}", SERVER_SECRET_KEY_ENV));
+    log::info!(
+        target: LOG_TARGET,
+        "Server public key: {:?}",
+        server_key.public_key()
+    );
+    let challenge_ttl = read_optional_env_var(CHALLENGE_TTL_SECS_ENV)
+        .map(Duration::from_secs)
+        .unwrap_or(DEFAULT_CHALLENGE_TTL);
+    let max_pending_challenges =
+        read_optional_env_var(MAX_PENDING_CHALLENGES_ENV).unwrap_or(DEFAULT_MAX_PENDING_CHALLENGES);
+    let session_idle_ttl = read_optional_env_var(SESSION_IDLE_TTL_SECS_ENV)
+        .map(Duration::from_secs)
+        .unwrap_or(DEFAULT_SESSION_IDLE_TTL);
+    let session_lifetime = read_optional_env_var(SESSION_LIFETIME_SECS_ENV)
+        .map(Duration::from_secs)
+        .unwrap_or(DEFAULT_SESSION_LIFETIME);
 
-    let auth_service = AuthService::new(auth_actor);
+    let mut storage = open_storage();
+    if let Ok(url) = env::var(REDIS_URL_ENV) {
+        log::info!(
+            target: LOG_TARGET,
+            "Keeping challenges and sessions in Redis: {:?}",
+            url
+        );
+        let redis = RedisDatabase::connect(&url, REDIS_KEY_PREFIX)
+            .await
+            .unwrap_or_else(|e| panic!("Cannot connect to Redis {:?}: {:?}", url, e));
+        storage.challenges = Box::new(redis.challenges(challenge_ttl));
+        storage.pending_challenges = Box::new(redis.pending_challenges(challenge_ttl));
+        storage.sessions = Box::new(redis.sessions(session_lifetime));
+    }
+
+    let token_signer = match env::var(SESSION_MODE_ENV)
+        .unwrap_or_else(|_| SESSION_MODE_STORED.into())
+        .as_str()
+    {
+        SESSION_MODE_STORED => None,
+        SESSION_MODE_TOKEN => Some(open_token_signer(&zkp_ctx)),
+        _ => panic!("Invalid value set for {:?}", SESSION_MODE_ENV),
+    };
+
+    let auth_actor = AuthActor::new(
+        zkp_ctx,
+        server_key,
+        storage.users,
+        storage.challenges,
+        storage.pending_challenges,
+        storage.sessions,
+    )
+    .with_challenge_ttl(challenge_ttl)
+    .with_max_pending_challenges(max_pending_challenges)
+    .with_session_idle_ttl(session_idle_ttl)
+    .with_session_lifetime(session_lifetime)
+    .with_rate_limits(rate_limit_policy())
+    .with_key_sealer(open_key_sealer());
+    let auth_actor = match token_signer {
+        None => auth_actor,
+        Some(token_signer) => auth_actor.with_token_signer(token_signer),
+    };
+    let queue_capacity =
+        read_optional_env_var(ACTOR_QUEUE_CAPACITY_ENV).unwrap_or(DEFAULT_QUEUE_CAPACITY);
+    if queue_capacity == 0 {
+        panic!("Invalid value set for {:?}", ACTOR_QUEUE_CAPACITY_ENV);
+    }
+    let max_in_flight =
+        read_optional_env_var(ACTOR_MAX_IN_FLIGHT_ENV).unwrap_or(DEFAULT_MAX_IN_FLIGHT);
+    if max_in_flight == 0 {
+        panic!("Invalid value set for {:?}", ACTOR_MAX_IN_FLIGHT_ENV);
+    }
+    let auth_actor = AuthHandle::spawn(auth_actor, queue_capacity, max_in_flight);
+    match read_optional_env_var(QUEUE_METRICS_INTERVAL_SECS_ENV).unwrap_or(60) {
+        0 => {}
+        secs => log_queue_stats(auth_actor.clone(), Duration::from_secs(secs)),
+    }
+    let auth_service = AuthService::new(auth_actor, trusted_proxies());
 
     Server::builder()
         .add_service(AuthServer::new(auth_service))
@@ -172,3 +655,156 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         .await?;
     Ok(())
 }
+
+struct Storage {
+    users: Box<dyn Repository<UserDTO>>,
+    challenges: Box<dyn Repository<ChallengeDTO>>,
+    pending_challenges: Box<dyn Repository<PendingChallengesDTO>>,
+    sessions: Box<dyn Repository<SessionDTO>>,
+}
+
+fn open_storage() -> Storage {
+    let storage_backend =
+        env::var(STORAGE_BACKEND_ENV).unwrap_or_else(|_| STORAGE_BACKEND_MEMORY.into());
+    match storage_backend.as_str() {
+        STORAGE_BACKEND_SQLITE => {
+            let path = read_env_var(DATABASE_PATH_ENV);
+            log::info!(target: LOG_TARGET, "Using SQLite database: {:?}", path);
+            let db = SqliteDatabase::open(&path)
+                .unwrap_or_else(|e| panic!("Cannot open database {:?}: {:?}", path, e));
+            Storage {
+                users: Box::new(db.users()),
+                challenges: Box::new(db.challenges()),
+                pending_challenges: Box::new(db.pending_challenges()),
+                sessions: Box::new(db.sessions()),
+            }
+        }
+        STORAGE_BACKEND_LOG => {
+            let dir = PathBuf::from(read_env_var(DATABASE_PATH_ENV));
+            log::info!(target: LOG_TARGET, "Using key-value logs in: {:?}", dir);
+            fs::create_dir_all(&dir)
+                .unwrap_or_else(|e| panic!("Cannot create directory {:?}: {:?}", dir, e));
+            Storage {
+                users: Box::new(open_log::<UserDTO>(&dir, "users.log")),
+                challenges: Box::new(open_log::<ChallengeDTO>(&dir, "challenges.log")),
+                pending_challenges: Box::new(open_log::<PendingChallengesDTO>(
+                    &dir,
+                    "pending_challenges.log",
+                )),
+                sessions: Box::new(open_log::<SessionDTO>(&dir, "sessions.log")),
+            }
+        }
+        STORAGE_BACKEND_MEMORY => {
+            log::warn!(target: LOG_TARGET, "Data is kept in memory only");
+            Storage {
+                users: Box::<InMemoryRepository<UserDTO>>::default(),
+                challenges: Box::<InMemoryRepository<ChallengeDTO>>::default(),
+                pending_challenges: Box::<InMemoryRepository<PendingChallengesDTO>>::default(),
+                sessions: Box::<InMemoryRepository<SessionDTO>>::default(),
+            }
+        }
+        _ => panic!("Invalid value set for {:?}", STORAGE_BACKEND_ENV),
+    }
+}
+
+/// Comma separated IP addresses of the proxies whose reported client address is trusted.
+fn trusted_proxies() -> Vec<IpAddr> {
+    match env::var(TRUSTED_PROXIES_ENV) {
+        Err(_) => Vec::new(),
+        Ok(proxies) => proxies
+            .split(',')
+            .map(|proxy| proxy.trim().parse())
+            .collect::<Result<_, _>>()
+            .unwrap_or_else(|_| panic!("Invalid value set for {:?}", TRUSTED_PROXIES_ENV)),
+    }
+}
+
+fn rate_limit_policy() -> RateLimitPolicy {
+    let default = RateLimitPolicy::default();
+    let read_secs = |name, default| {
+        read_optional_env_var(name)
+            .map(Duration::from_secs)
+            .unwrap_or(default)
+    };
+
+    RateLimitPolicy {
+        window: read_secs(RATE_LIMIT_WINDOW_SECS_ENV, default.window),
+        max_requests_per_user: read_optional_env_var(RATE_LIMIT_PER_USER_ENV)
+            .unwrap_or(default.max_requests_per_user),
+        max_requests_per_peer: read_optional_env_var(RATE_LIMIT_PER_PEER_ENV)
+            .unwrap_or(default.max_requests_per_peer),
+        free_failures: read_optional_env_var(FREE_LOGIN_FAILURES_ENV)
+            .unwrap_or(default.free_failures),
+        backoff: read_secs(LOGIN_BACKOFF_SECS_ENV, default.backoff),
+        max_backoff: read_secs(MAX_LOGIN_BACKOFF_SECS_ENV, default.max_backoff),
+        lockout_failures: read_optional_env_var(LOCKOUT_FAILURES_ENV)
+            .unwrap_or(default.lockout_failures),
+        lockout: read_secs(LOCKOUT_SECS_ENV, default.lockout),
+    }
+}
+
+/// Without a configured sealing key, session keys stored before a restart cannot be opened.
+fn open_key_sealer() -> KeySealer {
+    match env::var(SESSION_KEY_SEALING_KEY_ENV) {
+        Ok(key) => {
+            KeySealer::new(&sealing::parse_sealing_key(&key).unwrap_or_else(|_| {
+                panic!("Invalid value set for {:?}", SESSION_KEY_SEALING_KEY_ENV)
+            }))
+        }
+        Err(_) => {
+            log::warn!(
+                target: LOG_TARGET,
+                "Sealing session keys with a random key, sessions do not survive a restart"
+            );
+            KeySealer::random()
+        }
+    }
+}
+
+/// Signs with the first of the comma separated hex seeds, the rest are kept for verification.
+fn open_token_signer(zkp_ctx: &chaum_pedersen::Context) -> TokenSigner {
+    let seeds = read_env_var(TOKEN_SIGNING_KEYS_ENV)
+        .split(',')
+        .map(token::parse_seed)
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap_or_else(|_| panic!("Invalid value set for {:?}", TOKEN_SIGNING_KEYS_ENV));
+    let token_ttl = read_optional_env_var(TOKEN_TTL_SECS_ENV)
+        .map(Duration::from_secs)
+        .unwrap_or(DEFAULT_TOKEN_TTL);
+
+    let token_signer = TokenSigner::new(&seeds, token_ttl, zkp_ctx)
+        .unwrap_or_else(|_| panic!("Invalid value set for {:?}", TOKEN_SIGNING_KEYS_ENV));
+    log::info!(
+        target: LOG_TARGET,
+        "Issuing session tokens signed with key: {:?}",
+        token_signer.signing_key_id()
+    );
+    token_signer
+}
+
+fn log_queue_stats(auth_actor: AuthHandle, interval: Duration) {
+    tokio::spawn(async move {
+        let mut ticks = tokio::time::interval(interval);
+        loop {
+            ticks.tick().await;
+            let stats = auth_actor.queue_stats();
+            log::info!(
+                target: LOG_TARGET,
+                "Actor queue: {}/{} queued (peak {}), {}/{} in flight, {} handled, {} rejected",
+                stats.queued,
+                stats.capacity,
+                stats.peak_queued,
+                stats.in_flight,
+                stats.max_in_flight,
+                stats.handled,
+                stats.rejected
+            );
+        }
+    });
+}
+
+fn open_log<T>(dir: &Path, name: &str) -> LogRepository<T> {
+    let path = dir.join(name);
+    LogRepository::open(&path, CompactionPolicy::default())
+        .unwrap_or_else(|e| panic!("Cannot open log {:?}: {:?}", path, e))
+}
@@ -0,0 +1,358 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Limits of [`RateLimiter`]. A limit of `0` requests disables it.
+#[derive(Clone)]
+pub struct RateLimitPolicy {
+    pub window: Duration,
+    /// Requests a user can make within one window, from any peer.
+    pub max_requests_per_user: u32,
+    /// Requests a peer can make within one window, for any user.
+    pub max_requests_per_peer: u32,
+    /// Failed verifications of a user, or from a peer, that are not throttled, e.g. typos.
+    pub free_failures: u32,
+    /// The wait after the first throttled failure, doubled after every further one.
+    pub backoff: Duration,
+    pub max_backoff: Duration,
+    /// Failed verifications in a row after which the user, or the peer, is locked out, `0` never
+    /// locks out.
+    pub lockout_failures: u32,
+    /// How long a lockout lasts, and how long failures are remembered after the last one.
+    pub lockout: Duration,
+}
+
+impl Default for RateLimitPolicy {
+    fn default() -> Self {
+        Self {
+            window: Duration::from_secs(60),
+            max_requests_per_user: 20,
+            max_requests_per_peer: 100,
+            free_failures: 3,
+            backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(60),
+            lockout_failures: 10,
+            lockout: Duration::from_secs(15 * 60),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Throttled {
+    /// Too many requests, or backing off after failed verifications.
+    RateLimited { retry_after: Duration },
+    /// Too many failed verifications in a row.
+    Locked { retry_after: Duration },
+}
+
+struct Window {
+    started_at: u64,
+    requests: u32,
+}
+
+#[derive(Default)]
+struct Failures {
+    count: u32,
+    last_failed_at: u64,
+    blocked_until: u64,
+    locked: bool,
+}
+
+#[derive(Default)]
+struct State {
+    windows: HashMap<String, Window>,
+    failures: HashMap<String, Failures>,
+    swept_at: u64,
+}
+
+/// Fixed-window request limits by user and by peer, and back-off after failed verifications of a
+/// user or from a peer, so a peer guessing the secrets of many users is throttled as well.
+///
+/// The counters live in the memory of one replica, so every replica enforces the limits on its
+/// own. Expired counters are swept at most once per window. Times are Unix seconds.
+pub struct RateLimiter {
+    policy: RateLimitPolicy,
+    state: Mutex<State>,
+}
+
+impl RateLimiter {
+    pub fn new(policy: RateLimitPolicy) -> Self {
+        Self {
+            policy,
+            state: Mutex::default(),
+        }
+    }
+
+    /// Counts a request of `username` from `peer`, unless one of them is throttled.
+    pub fn check(&self, username: &str, peer: Option<&str>, now: u64) -> Result<(), Throttled> {
+        let mut state = self
+            .state
+            .lock()
+            .expect("RateLimiter lock must not be poisoned");
+
+        let window = self.policy.window.as_secs();
+        if now >= state.swept_at + window {
+            self.sweep(&mut state, now);
+        }
+
+        let mut keys = vec![(
+            format!("user:{username}"),
+            self.policy.max_requests_per_user,
+        )];
+        if let Some(peer) = peer {
+            keys.push((format!("peer:{peer}"), self.policy.max_requests_per_peer));
+        }
+
+        for (key, _) in &keys {
+            if let Some(failures) = state.failures.get(key) {
+                if now < failures.blocked_until {
+                    let retry_after = Duration::from_secs(failures.blocked_until - now);
+                    return Err(match failures.locked {
+                        true => Throttled::Locked { retry_after },
+                        false => Throttled::RateLimited { retry_after },
+                    });
+                }
+            }
+        }
+
+        for (key, limit) in keys.iter().filter(|(_, limit)| *limit > 0) {
+            if let Some(counter) = state.windows.get(key) {
+                if now < counter.started_at + window && counter.requests >= *limit {
+                    return Err(Throttled::RateLimited {
+                        retry_after: Duration::from_secs(counter.started_at + window - now),
+                    });
+                }
+            }
+        }
+
+        for (key, _) in keys {
+            let counter = state.windows.entry(key).or_insert(Window {
+                started_at: now,
+                requests: 0,
+            });
+            if now >= counter.started_at + window {
+                *counter = Window {
+                    started_at: now,
+                    requests: 0,
+                };
+            }
+            counter.requests += 1;
+        }
+        Ok(())
+    }
+
+    /// Drops the windows that have ended and the failures that are no longer remembered.
+    fn sweep(&self, state: &mut State, now: u64) {
+        let window = self.policy.window.as_secs();
+        state
+            .windows
+            .retain(|_, counter| now < counter.started_at + window);
+        state
+            .failures
+            .retain(|_, failures| !self.is_forgotten(failures, now));
+        state.swept_at = now;
+    }
+
+    fn is_forgotten(&self, failures: &Failures, now: u64) -> bool {
+        now >= failures.blocked_until
+            && now >= failures.last_failed_at + self.policy.lockout.as_secs()
+    }
+
+    /// Backs the user and the peer off exponentially after the free failures and locks them out
+    /// after `lockout_failures` in a row.
+    pub fn record_failure(&self, username: &str, peer: Option<&str>, now: u64) {
+        let mut state = self
+            .state
+            .lock()
+            .expect("RateLimiter lock must not be poisoned");
+        let mut keys = vec![format!("user:{username}")];
+        keys.extend(peer.map(|peer| format!("peer:{peer}")));
+        for key in keys {
+            self.count_failure(state.failures.entry(key).or_default(), now);
+        }
+    }
+
+    fn count_failure(&self, failures: &mut Failures, now: u64) {
+        if self.is_forgotten(failures, now) {
+            *failures = Failures::default();
+        }
+
+        failures.count += 1;
+        failures.last_failed_at = now;
+        failures.locked = false;
+        if self.policy.lockout_failures > 0 && failures.count >= self.policy.lockout_failures {
+            failures.count = 0;
+            failures.locked = true;
+            failures.blocked_until = now + self.policy.lockout.as_secs();
+        } else if failures.count > self.policy.free_failures {
+            let doublings = failures.count - self.policy.free_failures - 1;
+            let backoff = self
+                .policy
+                .backoff
+                .saturating_mul(2u32.saturating_pow(doublings))
+                .min(self.policy.max_backoff);
+            failures.blocked_until = now + backoff.as_secs();
+        }
+    }
+
+    /// Resets the failures of the user. Those of the peer stay, so logging in to an own account
+    /// does not reset the back-off earned guessing at others.
+    pub fn record_success(&self, username: &str) {
+        self.state
+            .lock()
+            .expect("RateLimiter lock must not be poisoned")
+            .failures
+            .remove(&format!("user:{username}"));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOW: u64 = 1_700_000_000;
+    const PEER: Option<&str> = Some("192.0.2.1");
+
+    fn rate_limited(secs: u64) -> Result<(), Throttled> {
+        Err(Throttled::RateLimited {
+            retry_after: Duration::from_secs(secs),
+        })
+    }
+
+    #[test]
+    fn should_limit_requests_by_user_and_peer() {
+        let limiter = RateLimiter::new(RateLimitPolicy {
+            max_requests_per_user: 2,
+            max_requests_per_peer: 3,
+            ..Default::default()
+        });
+
+        assert_eq!(limiter.check("alice", PEER, NOW), Ok(()));
+        assert_eq!(limiter.check("alice", PEER, NOW), Ok(()));
+        let now = NOW + 20;
+        assert_eq!(
+            limiter.check("alice", Some("192.0.2.2"), now),
+            rate_limited(40)
+        );
+
+        assert_eq!(limiter.check("bob", PEER, now), Ok(()));
+        assert_eq!(limiter.check("carol", PEER, now), rate_limited(40));
+        assert_eq!(limiter.check("carol", Some("192.0.2.2"), now), Ok(()));
+
+        assert_eq!(limiter.check("alice", PEER, NOW + 60), Ok(()));
+    }
+
+    #[test]
+    fn should_back_off_and_lock_out_after_failures() {
+        let limiter = RateLimiter::new(RateLimitPolicy {
+            free_failures: 1,
+            backoff: Duration::from_secs(2),
+            max_backoff: Duration::from_secs(5),
+            lockout_failures: 5,
+            lockout: Duration::from_secs(600),
+            ..Default::default()
+        });
+        let mut now = NOW;
+
+        limiter.record_failure("alice", None, now);
+        assert_eq!(limiter.check("alice", PEER, now), Ok(()));
+        for backoff in [2, 4, 5] {
+            limiter.record_failure("alice", None, now);
+            assert_eq!(limiter.check("alice", PEER, now), rate_limited(backoff));
+            now += backoff;
+        }
+        assert_eq!(limiter.check("bob", PEER, now), Ok(()));
+
+        limiter.record_failure("alice", None, now);
+        assert_eq!(
+            limiter.check("alice", PEER, now),
+            Err(Throttled::Locked {
+                retry_after: Duration::from_secs(600)
+            })
+        );
+        assert_eq!(limiter.check("alice", PEER, now + 600), Ok(()));
+    }
+
+    #[test]
+    fn should_forget_failures_after_lockout_period() {
+        let limiter = RateLimiter::new(RateLimitPolicy {
+            free_failures: 1,
+            backoff: Duration::from_secs(2),
+            lockout: Duration::from_secs(600),
+            ..Default::default()
+        });
+
+        limiter.record_failure("alice", None, NOW);
+        limiter.record_failure("alice", None, NOW + 600);
+        assert_eq!(limiter.check("alice", PEER, NOW + 600), Ok(()));
+        limiter.record_failure("alice", None, NOW + 601);
+        assert_eq!(limiter.check("alice", PEER, NOW + 601), rate_limited(2));
+    }
+
+    #[test]
+    fn should_sweep_expired_counters_once_per_window() {
+        let limiter = RateLimiter::new(RateLimitPolicy {
+            lockout: Duration::from_secs(90),
+            ..Default::default()
+        });
+        let tracked = || {
+            let state = limiter.state.lock().unwrap();
+            (state.windows.len(), state.failures.len())
+        };
+
+        assert_eq!(limiter.check("alice", PEER, NOW), Ok(()));
+        limiter.record_failure("alice", None, NOW);
+        assert_eq!(limiter.check("bob", PEER, NOW + 30), Ok(()));
+        assert_eq!(tracked(), (3, 1));
+
+        assert_eq!(limiter.check("carol", None, NOW + 60), Ok(()));
+        assert_eq!(tracked(), (2, 1));
+
+        // The window of bob and the failures of alice ended, but the last sweep was less than a
+        // window ago.
+        assert_eq!(limiter.check("carol", None, NOW + 100), Ok(()));
+        assert_eq!(tracked(), (2, 1));
+        assert_eq!(limiter.check("carol", None, NOW + 120), Ok(()));
+        assert_eq!(tracked(), (1, 0));
+    }
+
+    #[test]
+    fn should_back_off_peer_after_failures_for_any_user() {
+        let limiter = RateLimiter::new(RateLimitPolicy {
+            free_failures: 1,
+            backoff: Duration::from_secs(2),
+            lockout_failures: 3,
+            lockout: Duration::from_secs(600),
+            ..Default::default()
+        });
+
+        limiter.record_failure("alice", PEER, NOW);
+        limiter.record_failure("bob", PEER, NOW);
+        assert_eq!(limiter.check("carol", PEER, NOW), rate_limited(2));
+        assert_eq!(limiter.check("carol", Some("192.0.2.2"), NOW), Ok(()));
+        assert_eq!(limiter.check("alice", None, NOW), Ok(()));
+
+        // Logging in to an own account does not reset the failures of the peer.
+        limiter.record_success("carol");
+        limiter.record_failure("dave", PEER, NOW + 2);
+        assert_eq!(
+            limiter.check("carol", PEER, NOW + 2),
+            Err(Throttled::Locked {
+                retry_after: Duration::from_secs(600)
+            })
+        );
+    }
+
+    #[test]
+    fn should_reset_failures_on_success() {
+        let limiter = RateLimiter::new(RateLimitPolicy {
+            free_failures: 1,
+            ..Default::default()
+        });
+
+        limiter.record_failure("alice", None, NOW);
+        limiter.record_success("alice");
+        limiter.record_failure("alice", None, NOW);
+        assert_eq!(limiter.check("alice", PEER, NOW), Ok(()));
+    }
+}
@@ -1,36 +1,200 @@
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+
+#[cfg(test)]
+mod conformance;
 pub mod error;
 pub mod in_memory;
+pub mod log_store;
+pub mod redis_store;
+pub mod sqlite;
+
+pub type DBResult<T> = Result<T, error::RepositoryError>;
+
+/// Key-value storage of one kind of DTO.
+///
+/// Implementations are shared between concurrent requests, so every operation takes `&self` and
+/// updates that must not lose concurrent writes go through [`Repository::compare_and_swap`].
+#[async_trait]
+pub trait Repository<T>: Send + Sync {
+    /// Stores the value, failing with `ValueAlreadyExists` if the key is taken.
+    async fn insert(&self, key: &str, value: &T) -> DBResult<()>;
+
+    /// Stores the value, replacing any previous one.
+    async fn put(&self, key: &str, value: &T) -> DBResult<()>;
+
+    async fn get(&self, key: &str) -> DBResult<Option<T>>;
+
+    /// Removes the value, failing with `ValueNotFound` if there is none.
+    async fn delete(&self, key: &str) -> DBResult<()>;
+
+    async fn exists(&self, key: &str) -> DBResult<bool>;
+
+    /// Replaces the value only if it is still equal to `expected`.
+    async fn compare_and_swap(&self, key: &str, expected: &T, value: &T) -> DBResult<()>;
+}
+
+#[async_trait]
+impl<T: Send + Sync, R: Repository<T> + ?Sized> Repository<T> for Box<R> {
+    async fn insert(&self, key: &str, value: &T) -> DBResult<()> {
+        (**self).insert(key, value).await
+    }
+
+    async fn put(&self, key: &str, value: &T) -> DBResult<()> {
+        (**self).put(key, value).await
+    }
+
+    async fn get(&self, key: &str) -> DBResult<Option<T>> {
+        (**self).get(key).await
+    }
+
+    async fn delete(&self, key: &str) -> DBResult<()> {
+        (**self).delete(key).await
+    }
+
+    async fn exists(&self, key: &str) -> DBResult<bool> {
+        (**self).exists(key).await
+    }
+
+    async fn compare_and_swap(&self, key: &str, expected: &T, value: &T) -> DBResult<()> {
+        (**self).compare_and_swap(key, expected, value).await
+    }
+}
+
+/// Registered users by username.
+pub trait UserRepository: Repository<UserDTO> {}
+
+impl<R: Repository<UserDTO>> UserRepository for R {}
+
+/// Pending authentication challenges by `auth_id`.
+pub trait ChallengeRepository: Repository<ChallengeDTO> {}
 
-type DBResult<T> = Result<T, error::RepositoryError>;
+impl<R: Repository<ChallengeDTO>> ChallengeRepository for R {}
 
-#[derive(Clone, Default)]
+/// Pending challenges of every user by username.
+pub trait PendingChallengeRepository: Repository<PendingChallengesDTO> {}
+
+impl<R: Repository<PendingChallengesDTO>> PendingChallengeRepository for R {}
+
+/// Established sessions by session id.
+pub trait SessionRepository: Repository<SessionDTO> {}
+
+impl<R: Repository<SessionDTO>> SessionRepository for R {}
+
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub enum CredentialDTO {
+    ChaumPedersen { y1: i64, y2: i64 },
+    Okamoto { y: i64 },
+}
+
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
 pub struct UserDTO {
+    #[allow(dead_code)]
     pub username: String,
-    pub y1: i64,
-    pub y2: i64,
+    pub credential: CredentialDTO,
+    /// Number of rotations of the credential, bound into every rotation proof.
+    #[serde(default)]
+    pub key_version: u64,
 }
 
 impl UserDTO {
-    pub fn new(username: String, y1: i64, y2: i64) -> Self {
-        Self { username, y1, y2 }
+    pub fn new(username: String, credential: CredentialDTO) -> Self {
+        Self {
+            username,
+            credential,
+            key_version: 0,
+        }
     }
 }
 
-#[derive(Clone, Default)]
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub enum CommitmentDTO {
+    ChaumPedersen { r1: i64, r2: i64 },
+    Okamoto { t: i64 },
+}
+
+/// Client a session was established for, as reported by the service in front of `auth_service`.
+#[derive(Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ClientMetadataDTO {
+    pub address: Option<String>,
+    pub user_agent: Option<String>,
+}
+
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub struct SessionDTO {
+    pub session_id: String,
+    pub username: String,
+    /// Authenticates the requests made within the session, if it was established with a key
+    /// exchange.
+    pub session_key: Option<SealedKeyDTO>,
+    /// Highest request counter accepted so far, every request has to present a higher one.
+    #[serde(default)]
+    pub last_counter: u64,
+    /// Unix times in seconds. Sessions stored before they were recorded count as expired.
+    #[serde(default)]
+    pub created_at: u64,
+    #[serde(default)]
+    pub last_seen_at: u64,
+    #[serde(default)]
+    pub expires_at: u64,
+    #[serde(default)]
+    pub client: ClientMetadataDTO,
+}
+
+/// Session key encrypted with the sealing key of the service, see [`crate::sealing::KeySealer`].
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub struct SealedKeyDTO {
+    pub nonce: [u8; 12],
+    /// Encrypted key followed by the authentication tag.
+    pub ciphertext: Vec<u8>,
+}
+
+/// Session id and key agreed on with the key exchange of a challenge.
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub struct KeyAgreementDTO {
+    pub session_id: String,
+    pub session_key: SealedKeyDTO,
+}
+
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
 pub struct ChallengeDTO {
     pub username: String,
-    pub r1: i64,
-    pub r2: i64,
+    pub commitment: CommitmentDTO,
     pub c: i64,
+    /// Session agreed on with the key exchange, established once the answer is verified.
+    pub key_agreement: Option<KeyAgreementDTO>,
+    /// Unix time in seconds. Challenges stored before it was recorded count as expired.
+    #[serde(default)]
+    pub created_at: u64,
 }
 
 impl ChallengeDTO {
-    pub fn new(username: String, r1: i64, r2: i64, c: i64) -> Self {
+    pub fn new(
+        username: String,
+        commitment: CommitmentDTO,
+        c: i64,
+        key_agreement: Option<KeyAgreementDTO>,
+        created_at: u64,
+    ) -> Self {
         Self {
             username,
-            r1,
-            r2,
+            commitment,
             c,
+            key_agreement,
+            created_at,
         }
     }
 }
+
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub struct PendingChallengeDTO {
+    pub auth_id: String,
+    /// Unix time in seconds, the same as the `created_at` of the challenge.
+    pub created_at: u64,
+}
+
+/// Challenges of one user that were neither answered nor expired when it was last updated.
+#[derive(Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PendingChallengesDTO {
+    pub challenges: Vec<PendingChallengeDTO>,
+}
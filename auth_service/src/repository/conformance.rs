@@ -0,0 +1,127 @@
+//! Behaviour every [`Repository`](super::Repository) implementation must share.
+
+/// Expands to the repository test suite run against the repository returned by `$init`.
+///
+/// The attributes following it, e.g. `#[ignore]`, are put on every test.
+macro_rules! repository_tests {
+    ($init:expr $(, #[$attr:meta])*) => {
+        use $crate::repository::error::RepositoryError;
+
+        const DB_KEY: &str = "test_key";
+
+        #[tokio::test]
+        $(#[$attr])*
+        async fn should_not_exist() {
+            let db = $init;
+            assert!(!db.exists(DB_KEY).await.unwrap());
+            assert_eq!(db.get(DB_KEY).await.unwrap(), None);
+        }
+
+        #[tokio::test]
+        $(#[$attr])*
+        async fn should_insert() {
+            let db = $init;
+            let value: String = String::from("test value");
+
+            assert!(!db.exists(DB_KEY).await.unwrap());
+            assert_eq!(db.insert(DB_KEY, &value).await, Ok(()));
+            assert!(db.exists(DB_KEY).await.unwrap());
+            assert_eq!(db.get(DB_KEY).await.unwrap(), Some(value));
+        }
+
+        #[tokio::test]
+        $(#[$attr])*
+        async fn should_not_insert_twice() {
+            let db = $init;
+            let value: String = String::from("test value");
+
+            assert!(!db.exists(DB_KEY).await.unwrap());
+            assert_eq!(db.insert(DB_KEY, &value).await, Ok(()));
+            assert_eq!(
+                db.insert(DB_KEY, &value).await,
+                Err(RepositoryError::ValueAlreadyExists)
+            );
+        }
+
+        #[tokio::test]
+        $(#[$attr])*
+        async fn should_put_nonexistent_value() {
+            let db = $init;
+            let value: String = String::from("test value");
+
+            assert!(!db.exists(DB_KEY).await.unwrap());
+            assert_eq!(db.put(DB_KEY, &value).await, Ok(()));
+            assert!(db.exists(DB_KEY).await.unwrap());
+            assert_eq!(db.get(DB_KEY).await.unwrap(), Some(value));
+        }
+
+        #[tokio::test]
+        $(#[$attr])*
+        async fn should_update_existent_value_with_put() {
+            let db = $init;
+            let value: String = String::from("test value");
+            let updated_value: String = String::from("updated value");
+
+            assert!(!db.exists(DB_KEY).await.unwrap());
+            assert_eq!(db.insert(DB_KEY, &value).await, Ok(()));
+            assert_eq!(db.put(DB_KEY, &updated_value).await, Ok(()));
+            assert_eq!(db.get(DB_KEY).await.unwrap(), Some(updated_value));
+        }
+
+        #[tokio::test]
+        $(#[$attr])*
+        async fn should_compare_and_swap_unchanged_value() {
+            let db = $init;
+            let value: String = String::from("test value");
+            let updated_value: String = String::from("updated value");
+
+            assert_eq!(db.insert(DB_KEY, &value).await, Ok(()));
+            assert_eq!(
+                db.compare_and_swap(DB_KEY, &value, &updated_value).await,
+                Ok(())
+            );
+            assert_eq!(db.get(DB_KEY).await.unwrap(), Some(updated_value));
+        }
+
+        #[tokio::test]
+        $(#[$attr])*
+        async fn should_not_compare_and_swap_changed_value() {
+            let db = $init;
+            let value: String = String::from("test value");
+            let updated_value: String = String::from("updated value");
+
+            assert_eq!(db.insert(DB_KEY, &updated_value).await, Ok(()));
+            assert_eq!(
+                db.compare_and_swap(DB_KEY, &value, &updated_value).await,
+                Err(RepositoryError::ConcurrentModification)
+            );
+        }
+
+        #[tokio::test]
+        $(#[$attr])*
+        async fn should_not_compare_and_swap_nonexistent_value() {
+            let db = $init;
+            let value: String = String::from("test value");
+
+            assert_eq!(
+                db.compare_and_swap(DB_KEY, &value, &value).await,
+                Err(RepositoryError::ValueNotFound)
+            );
+            assert!(!db.exists(DB_KEY).await.unwrap());
+        }
+
+        #[tokio::test]
+        $(#[$attr])*
+        async fn should_delete_existent_value() {
+            let db = $init;
+            let value: String = String::from("test value");
+
+            assert_eq!(db.insert(DB_KEY, &value).await, Ok(()));
+            assert_eq!(db.delete(DB_KEY).await, Ok(()));
+            assert!(!db.exists(DB_KEY).await.unwrap());
+            assert_eq!(db.delete(DB_KEY).await, Err(RepositoryError::ValueNotFound));
+        }
+    };
+}
+
+pub(crate) use repository_tests;
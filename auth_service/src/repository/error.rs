@@ -1,4 +1,31 @@
 #[derive(Debug, PartialEq)]
 pub enum RepositoryError {
     ValueAlreadyExists,
+    ConcurrentModification,
+    ValueNotFound,
+    Storage(String),
+}
+
+impl From<std::io::Error> for RepositoryError {
+    fn from(val: std::io::Error) -> Self {
+        RepositoryError::Storage(val.to_string())
+    }
+}
+
+impl From<rusqlite::Error> for RepositoryError {
+    fn from(val: rusqlite::Error) -> Self {
+        RepositoryError::Storage(val.to_string())
+    }
+}
+
+impl From<redis::RedisError> for RepositoryError {
+    fn from(val: redis::RedisError) -> Self {
+        RepositoryError::Storage(val.to_string())
+    }
+}
+
+impl From<serde_json::Error> for RepositoryError {
+    fn from(val: serde_json::Error) -> Self {
+        RepositoryError::Storage(val.to_string())
+    }
 }
@@ -1,114 +1,82 @@
 use std::collections::hash_map::HashMap;
+use std::sync::{Mutex, MutexGuard};
 
-use crate::repository::{error::RepositoryError, DBResult};
+use async_trait::async_trait;
+
+use crate::repository::{error::RepositoryError, DBResult, Repository};
 
-#[derive(Default)]
 pub struct InMemoryRepository<T: Clone> {
-    items: HashMap<String, T>,
+    items: Mutex<HashMap<String, T>>,
+}
+
+impl<T: Clone> Default for InMemoryRepository<T> {
+    fn default() -> Self {
+        Self {
+            items: Mutex::new(HashMap::new()),
+        }
+    }
 }
 
 impl<T: Clone> InMemoryRepository<T> {
-    pub fn insert(&mut self, key: &str, value: &T) -> DBResult<()> {
-        self.do_insert(key, value)
+    fn items(&self) -> MutexGuard<'_, HashMap<String, T>> {
+        self.items
+            .lock()
+            .expect("InMemoryRepository lock must not be poisoned")
     }
+}
 
-    pub fn put(&mut self, key: &str, value: &T) -> DBResult<()> {
-        self.do_put(key, value);
+#[async_trait]
+impl<T: Clone + PartialEq + Send + Sync> Repository<T> for InMemoryRepository<T> {
+    async fn insert(&self, key: &str, value: &T) -> DBResult<()> {
+        let mut items = self.items();
+        if items.contains_key(key) {
+            return Err(RepositoryError::ValueAlreadyExists);
+        }
+        items.insert(key.into(), value.clone());
         Ok(())
     }
 
-    pub fn get(&self, key: &str) -> DBResult<Option<T>> {
-        Ok(self.do_get(key))
+    async fn put(&self, key: &str, value: &T) -> DBResult<()> {
+        self.items().insert(key.into(), value.clone());
+        Ok(())
     }
 
-    pub fn exists(&self, key: &str) -> DBResult<bool> {
-        Ok(self.do_exists(key))
+    async fn get(&self, key: &str) -> DBResult<Option<T>> {
+        Ok(self.items().get(key).cloned())
     }
 
-    fn do_insert(&mut self, key: &str, value: &T) -> DBResult<()> {
-        match self.do_exists(key) {
-            false => match self.do_put(key, value) {
-                None => Ok(()),
-                Some(_) => panic!("User for key {:?} should not exist", key),
-            },
-            true => Err(RepositoryError::ValueAlreadyExists),
+    async fn delete(&self, key: &str) -> DBResult<()> {
+        match self.items().remove(key) {
+            None => Err(RepositoryError::ValueNotFound),
+            Some(_) => Ok(()),
         }
     }
 
-    fn do_put(&mut self, key: &str, value: &T) -> Option<T> {
-        self.items.insert(key.into(), (*value).clone())
-    }
-
-    fn do_get(&self, key: &str) -> Option<T> {
-        self.items.get(key).map(|dto| (*dto).clone())
+    async fn exists(&self, key: &str) -> DBResult<bool> {
+        Ok(self.items().contains_key(key))
     }
 
-    fn do_exists(&self, key: &str) -> bool {
-        self.items.contains_key(key)
+    async fn compare_and_swap(&self, key: &str, expected: &T, value: &T) -> DBResult<()> {
+        let mut items = self.items();
+        match items.get(key) {
+            None => Err(RepositoryError::ValueNotFound),
+            Some(current) if current != expected => Err(RepositoryError::ConcurrentModification),
+            Some(_) => {
+                items.insert(key.into(), value.clone());
+                Ok(())
+            }
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-
-    const DB_KEY: &str = "test_key";
+    use crate::repository::conformance::repository_tests;
 
     fn init() -> InMemoryRepository<String> {
         InMemoryRepository::<String>::default()
     }
 
-    #[test]
-    fn should_not_exist() {
-        let db = init();
-        assert!(!db.exists(DB_KEY).unwrap());
-        assert_eq!(db.get(DB_KEY).unwrap(), None);
-    }
-
-    #[test]
-    fn should_insert() {
-        let mut db = init();
-        let value: String = String::from("test value");
-
-        assert!(!db.exists(DB_KEY).unwrap());
-        assert_eq!(db.insert(DB_KEY, &value), Ok(()));
-        assert!(db.exists(DB_KEY).unwrap());
-        assert_eq!(db.get(DB_KEY).unwrap(), Some(value));
-    }
-
-    #[test]
-    fn should_not_insert_twice() {
-        let mut db = init();
-        let value: String = String::from("test value");
-
-        assert!(!db.exists(DB_KEY).unwrap());
-        assert_eq!(db.insert(DB_KEY, &value), Ok(()));
-        assert_eq!(
-            db.insert(DB_KEY, &value),
-            Err(RepositoryError::ValueAlreadyExists)
-        );
-    }
-
-    #[test]
-    fn should_put_nonexistent_value() {
-        let mut db = init();
-        let value: String = String::from("test value");
-
-        assert!(!db.exists(DB_KEY).unwrap());
-        assert_eq!(db.put(DB_KEY, &value), Ok(()));
-        assert!(db.exists(DB_KEY).unwrap());
-        assert_eq!(db.get(DB_KEY).unwrap(), Some(value));
-    }
-
-    #[test]
-    fn should_update_existent_value_with_put() {
-        let mut db = init();
-        let value: String = String::from("test value");
-        let updated_value: String = String::from("updated value");
-
-        assert!(!db.exists(DB_KEY).unwrap());
-        assert_eq!(db.insert(DB_KEY, &value), Ok(()));
-        assert_eq!(db.put(DB_KEY, &updated_value), Ok(()));
-        assert_eq!(db.get(DB_KEY).unwrap(), Some(updated_value));
-    }
+    repository_tests!(init());
 }
@@ -0,0 +1,595 @@
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::marker::PhantomData;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use async_trait::async_trait;
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::repository::{
+    error::RepositoryError, ChallengeDTO, DBResult, PendingChallengesDTO, Repository, SessionDTO,
+    UserDTO,
+};
+
+const LOG_TARGET: &str = "auth_service::log_store";
+
+const HEADER_LEN: usize = 8;
+const OP_PUT: u8 = 1;
+const OP_DELETE: u8 = 2;
+
+/// Layout of the values kept in a [`LogStore`].
+///
+/// Every encoded value starts with its layout version, so values written by an older release can
+/// still be decoded after the DTO changes.
+pub trait VersionedValue: Sized {
+    const VERSION: u8;
+
+    fn encode_body(&self) -> DBResult<Vec<u8>>;
+
+    fn decode_body(version: u8, body: &[u8]) -> DBResult<Self>;
+
+    fn encode(&self) -> DBResult<Vec<u8>> {
+        let mut bytes = vec![Self::VERSION];
+        bytes.extend(self.encode_body()?);
+        Ok(bytes)
+    }
+
+    fn decode(bytes: &[u8]) -> DBResult<Self> {
+        match bytes.split_first() {
+            Some((&version, body)) => Self::decode_body(version, body),
+            None => Err(RepositoryError::Storage("empty value".into())),
+        }
+    }
+}
+
+/// When to rewrite the log without the values that were overwritten or deleted.
+#[derive(Clone, Copy, Debug)]
+pub struct CompactionPolicy {
+    /// Logs smaller than this are never compacted.
+    pub min_log_bytes: u64,
+    /// Compact once this share of the log is garbage.
+    pub max_garbage_ratio: f64,
+}
+
+impl Default for CompactionPolicy {
+    fn default() -> Self {
+        Self {
+            min_log_bytes: 1 << 20,
+            max_garbage_ratio: 0.5,
+        }
+    }
+}
+
+/// Append-only log of put and delete records with an in-memory index of the live values.
+///
+/// A record is `crc32 | length | op | key length | key | value`. Writes are synced before they
+/// are acknowledged, and a torn record left at the end of the log by a crash is truncated on
+/// open. Compaction writes the live values to a new file and renames it over the log.
+pub struct LogStore {
+    path: PathBuf,
+    file: File,
+    index: HashMap<String, Vec<u8>>,
+    log_bytes: u64,
+    live_bytes: u64,
+    policy: CompactionPolicy,
+}
+
+/// Repository keeping [`VersionedValue`]s in a [`LogStore`].
+pub struct LogRepository<T> {
+    store: Arc<Mutex<LogStore>>,
+    value: PhantomData<fn() -> T>,
+}
+
+fn record(op: u8, key: &str, value: &[u8]) -> Vec<u8> {
+    let mut body = vec![op];
+    body.extend((key.len() as u32).to_le_bytes());
+    body.extend(key.as_bytes());
+    body.extend(value);
+
+    let mut record = crc32fast::hash(&body).to_le_bytes().to_vec();
+    record.extend((body.len() as u32).to_le_bytes());
+    record.extend(body);
+    record
+}
+
+/// Parses the record at the start of `bytes`, returning `None` for a torn or corrupted one.
+fn parse_record(bytes: &[u8]) -> Option<(usize, u8, String, &[u8])> {
+    let header = bytes.get(..HEADER_LEN)?;
+    let crc = u32::from_le_bytes(header[..4].try_into().ok()?);
+    let len = u32::from_le_bytes(header[4..].try_into().ok()?) as usize;
+    let body = bytes.get(HEADER_LEN..HEADER_LEN.checked_add(len)?)?;
+    if crc32fast::hash(body) != crc {
+        return None;
+    }
+
+    let (&op, rest) = body.split_first()?;
+    let key_len = u32::from_le_bytes(rest.get(..4)?.try_into().ok()?) as usize;
+    let key = rest.get(4..4usize.checked_add(key_len)?)?;
+    let key = String::from_utf8(key.to_vec()).ok()?;
+    Some((HEADER_LEN + len, op, key, &rest[4 + key_len..]))
+}
+
+fn record_len(key: &str, value: &[u8]) -> u64 {
+    (HEADER_LEN + 1 + 4 + key.len() + value.len()) as u64
+}
+
+impl LogStore {
+    /// Opens or creates the log at `path` and replays it into the index.
+    pub fn open(path: impl AsRef<Path>, policy: CompactionPolicy) -> io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let mut file = OpenOptions::new()
+            .read(true)
+            .append(true)
+            .create(true)
+            .open(&path)?;
+        let mut bytes = Vec::new();
+        file.read_to_end(&mut bytes)?;
+
+        let mut index = HashMap::new();
+        let mut offset = 0;
+        while offset < bytes.len() {
+            match parse_record(&bytes[offset..]) {
+                Some((len, OP_PUT, key, value)) => {
+                    index.insert(key, value.to_vec());
+                    offset += len;
+                }
+                Some((len, OP_DELETE, key, _)) => {
+                    index.remove(&key);
+                    offset += len;
+                }
+                _ => break,
+            }
+        }
+        if offset < bytes.len() {
+            log::warn!(
+                target: LOG_TARGET,
+                "Truncating {} bytes of torn records from {:?}",
+                bytes.len() - offset,
+                path
+            );
+            file.set_len(offset as u64)?;
+            file.sync_all()?;
+        }
+
+        let live_bytes = index
+            .iter()
+            .map(|(key, value)| record_len(key, value))
+            .sum();
+        Ok(Self {
+            path,
+            file,
+            index,
+            log_bytes: offset as u64,
+            live_bytes,
+            policy,
+        })
+    }
+
+    pub fn get(&self, key: &str) -> Option<&[u8]> {
+        self.index.get(key).map(Vec::as_slice)
+    }
+
+    pub fn put(&mut self, key: &str, value: Vec<u8>) -> io::Result<()> {
+        self.append(&record(OP_PUT, key, &value))?;
+        self.live_bytes += record_len(key, &value);
+        if let Some(previous) = self.index.insert(key.into(), value) {
+            self.live_bytes -= record_len(key, &previous);
+        }
+        self.compact_if_needed()
+    }
+
+    pub fn delete(&mut self, key: &str) -> io::Result<bool> {
+        if !self.index.contains_key(key) {
+            return Ok(false);
+        }
+
+        self.append(&record(OP_DELETE, key, &[]))?;
+        if let Some(previous) = self.index.remove(key) {
+            self.live_bytes -= record_len(key, &previous);
+        }
+        self.compact_if_needed()?;
+        Ok(true)
+    }
+
+    fn append(&mut self, record: &[u8]) -> io::Result<()> {
+        self.file.write_all(record)?;
+        self.file.sync_data()?;
+        self.log_bytes += record.len() as u64;
+        Ok(())
+    }
+
+    fn compact_if_needed(&mut self) -> io::Result<()> {
+        let garbage = self.log_bytes - self.live_bytes;
+        if self.log_bytes >= self.policy.min_log_bytes
+            && garbage as f64 > self.log_bytes as f64 * self.policy.max_garbage_ratio
+        {
+            self.compact()?;
+        }
+        Ok(())
+    }
+
+    /// Rewrites the log with the live values only.
+    pub fn compact(&mut self) -> io::Result<()> {
+        let compacted_path = self.path.with_extension("compact");
+        let mut compacted = File::create(&compacted_path)?;
+        for (key, value) in &self.index {
+            compacted.write_all(&record(OP_PUT, key, value))?;
+        }
+        compacted.sync_all()?;
+        fs::rename(&compacted_path, &self.path)?;
+        if let Some(dir) = self.path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            File::open(dir)?.sync_all()?;
+        }
+
+        self.file = OpenOptions::new().append(true).open(&self.path)?;
+        log::info!(
+            target: LOG_TARGET,
+            "Compacted {:?} from {} to {} bytes",
+            self.path,
+            self.log_bytes,
+            self.live_bytes
+        );
+        self.log_bytes = self.live_bytes;
+        Ok(())
+    }
+}
+
+impl<T> LogRepository<T> {
+    pub fn open(path: impl AsRef<Path>, policy: CompactionPolicy) -> DBResult<Self> {
+        Ok(Self {
+            store: Arc::new(Mutex::new(LogStore::open(path, policy)?)),
+            value: PhantomData,
+        })
+    }
+
+    /// Runs the operation on the blocking thread pool, so syncing the log does not stall the
+    /// runtime.
+    async fn run<V, F>(&self, operation: F) -> DBResult<V>
+    where
+        V: Send + 'static,
+        F: FnOnce(&mut LogStore) -> DBResult<V> + Send + 'static,
+    {
+        let store = self.store.clone();
+        tokio::task::spawn_blocking(move || {
+            let mut store = store
+                .lock()
+                .expect("LogRepository lock must not be poisoned");
+            operation(&mut store)
+        })
+        .await
+        .map_err(|e| RepositoryError::Storage(e.to_string()))?
+    }
+}
+
+#[async_trait]
+impl<T: VersionedValue + Send + Sync> Repository<T> for LogRepository<T> {
+    async fn insert(&self, key: &str, value: &T) -> DBResult<()> {
+        let (key, value) = (key.to_owned(), value.encode()?);
+        self.run(move |store| {
+            if store.get(&key).is_some() {
+                return Err(RepositoryError::ValueAlreadyExists);
+            }
+            Ok(store.put(&key, value)?)
+        })
+        .await
+    }
+
+    async fn put(&self, key: &str, value: &T) -> DBResult<()> {
+        let (key, value) = (key.to_owned(), value.encode()?);
+        self.run(move |store| Ok(store.put(&key, value)?)).await
+    }
+
+    async fn get(&self, key: &str) -> DBResult<Option<T>> {
+        let key = key.to_owned();
+        let value = self
+            .run(move |store| Ok(store.get(&key).map(<[u8]>::to_vec)))
+            .await?;
+        value.as_deref().map(T::decode).transpose()
+    }
+
+    async fn delete(&self, key: &str) -> DBResult<()> {
+        let key = key.to_owned();
+        self.run(move |store| match store.delete(&key)? {
+            true => Ok(()),
+            false => Err(RepositoryError::ValueNotFound),
+        })
+        .await
+    }
+
+    async fn exists(&self, key: &str) -> DBResult<bool> {
+        let key = key.to_owned();
+        self.run(move |store| Ok(store.get(&key).is_some())).await
+    }
+
+    /// Compares the stored value re-encoded at the current version, so values written by an
+    /// older release still match.
+    async fn compare_and_swap(&self, key: &str, expected: &T, value: &T) -> DBResult<()> {
+        let (key, expected, value) = (key.to_owned(), expected.encode()?, value.encode()?);
+        let reencode: fn(&[u8]) -> DBResult<Vec<u8>> = |stored| T::decode(stored)?.encode();
+        self.run(move |store| match store.get(&key) {
+            None => Err(RepositoryError::ValueNotFound),
+            Some(current) if reencode(current)? != expected => {
+                Err(RepositoryError::ConcurrentModification)
+            }
+            Some(_) => Ok(store.put(&key, value)?),
+        })
+        .await
+    }
+}
+
+fn encode_json<T: Serialize>(value: &T) -> DBResult<Vec<u8>> {
+    Ok(serde_json::to_vec(value)?)
+}
+
+/// Decodes any version up to `T::VERSION`, the fields added since default when missing.
+fn decode_json<T: VersionedValue + DeserializeOwned>(
+    type_name: &str,
+    version: u8,
+    body: &[u8],
+) -> DBResult<T> {
+    match version {
+        version if (1..=T::VERSION).contains(&version) => Ok(serde_json::from_slice(body)?),
+        _ => Err(RepositoryError::Storage(format!(
+            "unsupported {} version {}",
+            type_name, version
+        ))),
+    }
+}
+
+impl VersionedValue for UserDTO {
+    /// Version 2 added `key_version`, which is 0 for version 1 users.
+    const VERSION: u8 = 2;
+
+    fn encode_body(&self) -> DBResult<Vec<u8>> {
+        encode_json(self)
+    }
+
+    fn decode_body(version: u8, body: &[u8]) -> DBResult<Self> {
+        decode_json("UserDTO", version, body)
+    }
+}
+
+impl VersionedValue for ChallengeDTO {
+    /// Version 2 added `created_at`, which is the epoch for version 1 challenges.
+    const VERSION: u8 = 2;
+
+    fn encode_body(&self) -> DBResult<Vec<u8>> {
+        encode_json(self)
+    }
+
+    fn decode_body(version: u8, body: &[u8]) -> DBResult<Self> {
+        decode_json("ChallengeDTO", version, body)
+    }
+}
+
+impl VersionedValue for PendingChallengesDTO {
+    const VERSION: u8 = 1;
+
+    fn encode_body(&self) -> DBResult<Vec<u8>> {
+        encode_json(self)
+    }
+
+    fn decode_body(version: u8, body: &[u8]) -> DBResult<Self> {
+        decode_json("PendingChallengesDTO", version, body)
+    }
+}
+
+impl VersionedValue for SessionDTO {
+    /// Version 2 added the timestamps, which are the epoch for version 1 sessions, and the
+    /// client metadata.
+    const VERSION: u8 = 2;
+
+    fn encode_body(&self) -> DBResult<Vec<u8>> {
+        encode_json(self)
+    }
+
+    fn decode_body(version: u8, body: &[u8]) -> DBResult<Self> {
+        decode_json("SessionDTO", version, body)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repository::{conformance::repository_tests, CommitmentDTO, CredentialDTO};
+
+    impl VersionedValue for String {
+        const VERSION: u8 = 1;
+
+        fn encode_body(&self) -> DBResult<Vec<u8>> {
+            Ok(self.as_bytes().to_vec())
+        }
+
+        fn decode_body(_version: u8, body: &[u8]) -> DBResult<Self> {
+            String::from_utf8(body.to_vec()).map_err(|e| RepositoryError::Storage(e.to_string()))
+        }
+    }
+
+    /// Log file removed when the test ends.
+    struct TempLog(PathBuf);
+
+    impl TempLog {
+        fn new(name: &str) -> Self {
+            let path = std::env::temp_dir().join(format!(
+                "auth_service-{}-{}.log",
+                std::process::id(),
+                name
+            ));
+            let _ = fs::remove_file(&path);
+            Self(path)
+        }
+    }
+
+    impl Drop for TempLog {
+        fn drop(&mut self) {
+            let _ = fs::remove_file(&self.0);
+        }
+    }
+
+    fn init(log: &TempLog) -> LogRepository<String> {
+        LogRepository::open(&log.0, CompactionPolicy::default()).unwrap()
+    }
+
+    mod conformance {
+        use super::*;
+
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        static LOGS: AtomicUsize = AtomicUsize::new(0);
+
+        fn init() -> LogRepository<String> {
+            // The log is unlinked right away, the open file keeps working until it is dropped.
+            let log = TempLog::new(&LOGS.fetch_add(1, Ordering::Relaxed).to_string());
+            super::init(&log)
+        }
+
+        repository_tests!(init());
+    }
+
+    fn user(y1: i64) -> UserDTO {
+        UserDTO::new("alice".into(), CredentialDTO::ChaumPedersen { y1, y2: 81 })
+    }
+
+    #[tokio::test]
+    async fn should_replay_log_after_reopening() {
+        let log = TempLog::new("replay");
+        let db = init(&log);
+        assert_eq!(db.insert("alice", &"first".into()).await, Ok(()));
+        assert_eq!(db.put("alice", &"second".into()).await, Ok(()));
+        assert_eq!(db.insert("bob", &"bob".into()).await, Ok(()));
+        assert_eq!(db.delete("bob").await, Ok(()));
+        drop(db);
+
+        let db = init(&log);
+        assert_eq!(db.get("alice").await, Ok(Some("second".into())));
+        assert_eq!(db.exists("bob").await, Ok(false));
+    }
+
+    #[tokio::test]
+    async fn should_truncate_torn_record() {
+        let log = TempLog::new("torn");
+        let db = LogRepository::<UserDTO>::open(&log.0, CompactionPolicy::default()).unwrap();
+        assert_eq!(db.insert("alice", &user(16)).await, Ok(()));
+        drop(db);
+
+        let intact_len = fs::metadata(&log.0).unwrap().len();
+        let torn = record(OP_PUT, "bob", &user(25).encode().unwrap());
+        let mut file = OpenOptions::new().append(true).open(&log.0).unwrap();
+        file.write_all(&torn[..torn.len() - 3]).unwrap();
+        drop(file);
+
+        let db = LogRepository::<UserDTO>::open(&log.0, CompactionPolicy::default()).unwrap();
+        assert!(db.get("alice").await.unwrap() == Some(user(16)));
+        assert_eq!(db.exists("bob").await, Ok(false));
+        assert_eq!(fs::metadata(&log.0).unwrap().len(), intact_len);
+
+        // New records are appended after the last intact one.
+        assert_eq!(db.insert("bob", &user(25)).await, Ok(()));
+        drop(db);
+        let db = LogRepository::<UserDTO>::open(&log.0, CompactionPolicy::default()).unwrap();
+        assert!(db.get("bob").await.unwrap() == Some(user(25)));
+    }
+
+    #[test]
+    fn should_compact_garbage() {
+        let log = TempLog::new("compaction");
+        let policy = CompactionPolicy {
+            min_log_bytes: 256,
+            max_garbage_ratio: 0.5,
+        };
+        let mut store = LogStore::open(&log.0, policy).unwrap();
+        for i in 0..100u8 {
+            store.put("alice", vec![i; 16]).unwrap();
+        }
+        let live_bytes = record_len("alice", &[0; 16]);
+        assert!(fs::metadata(&log.0).unwrap().len() < 256 + live_bytes);
+        drop(store);
+
+        let store = LogStore::open(&log.0, policy).unwrap();
+        assert_eq!(store.get("alice"), Some(&[99; 16][..]));
+    }
+
+    #[tokio::test]
+    async fn should_rotate_user_stored_as_first_version() {
+        let log = TempLog::new("rotate-v1");
+        let mut json = serde_json::to_value(user(16)).unwrap();
+        json.as_object_mut().unwrap().remove("key_version");
+        let mut encoded = vec![1];
+        encoded.extend(serde_json::to_vec(&json).unwrap());
+        let mut store = LogStore::open(&log.0, CompactionPolicy::default()).unwrap();
+        store.put("alice", encoded).unwrap();
+        drop(store);
+
+        let db = LogRepository::<UserDTO>::open(&log.0, CompactionPolicy::default()).unwrap();
+        let stored = db.get("alice").await.unwrap().unwrap();
+        assert_eq!(stored.key_version, 0);
+        let rotated = UserDTO {
+            key_version: 1,
+            ..user(25)
+        };
+        assert_eq!(
+            db.compare_and_swap("alice", &stored, &rotated).await,
+            Ok(())
+        );
+        assert!(db.get("alice").await.unwrap() == Some(rotated.clone()));
+        assert_eq!(
+            db.compare_and_swap("alice", &stored, &rotated).await,
+            Err(RepositoryError::ConcurrentModification)
+        );
+    }
+
+    #[test]
+    fn should_reject_unknown_value_version() {
+        let mut encoded = user(16).encode().unwrap();
+        assert_eq!(encoded[0], UserDTO::VERSION);
+        encoded[0] = UserDTO::VERSION + 1;
+        assert!(matches!(
+            UserDTO::decode(&encoded),
+            Err(RepositoryError::Storage(_))
+        ));
+    }
+
+    #[test]
+    fn should_decode_challenge_without_creation_time() {
+        let challenge = ChallengeDTO::new(
+            "alice".into(),
+            CommitmentDTO::Okamoto { t: 16 },
+            4321,
+            None,
+            1_700_000_000,
+        );
+        let mut json = serde_json::to_value(&challenge).unwrap();
+        json.as_object_mut().unwrap().remove("created_at");
+        let mut encoded = vec![1];
+        encoded.extend(serde_json::to_vec(&json).unwrap());
+
+        let decoded = ChallengeDTO::decode(&encoded).unwrap();
+        assert_eq!(decoded.created_at, 0);
+        assert!(
+            decoded
+                == ChallengeDTO {
+                    created_at: 0,
+                    ..challenge
+                }
+        );
+    }
+
+    #[test]
+    fn should_decode_session_without_timestamps() {
+        let json = serde_json::json!({
+            "session_id": "ab",
+            "username": "alice",
+        });
+        let mut encoded = vec![1];
+        encoded.extend(serde_json::to_vec(&json).unwrap());
+
+        let decoded = SessionDTO::decode(&encoded).unwrap();
+        assert_eq!(decoded.username, "alice");
+        assert!(decoded.session_key.is_none());
+        assert_eq!(decoded.last_counter, 0);
+        assert_eq!(decoded.expires_at, 0);
+        assert!(decoded.client == Default::default());
+    }
+}
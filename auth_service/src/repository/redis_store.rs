@@ -0,0 +1,227 @@
+use std::marker::PhantomData;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use redis::{aio::ConnectionManager, AsyncCommands, Script, SetExpiry, SetOptions};
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::repository::{
+    error::RepositoryError, ChallengeDTO, DBResult, PendingChallengesDTO, Repository, SessionDTO,
+};
+
+/// Replaces the value only if it is still `ARGV[1]`, keeping its TTL.
+const COMPARE_AND_SWAP: &str = r"
+local current = redis.call('GET', KEYS[1])
+if not current then
+    return -1
+end
+if current ~= ARGV[1] then
+    return 0
+end
+redis.call('SET', KEYS[1], ARGV[2], 'KEEPTTL')
+return 1
+";
+
+/// Redis server shared by all `auth_service` replicas.
+///
+/// Challenges and sessions expire with native key TTLs, so no replica has to clean them up.
+#[derive(Clone)]
+pub struct RedisDatabase {
+    connection: ConnectionManager,
+    prefix: String,
+}
+
+/// Repository keeping JSON encoded values under `<prefix>:<kind>:<key>` in Redis.
+pub struct RedisRepository<T> {
+    connection: ConnectionManager,
+    prefix: String,
+    ttl: Duration,
+    value: PhantomData<fn() -> T>,
+}
+
+impl RedisDatabase {
+    pub async fn connect(url: &str, prefix: &str) -> DBResult<Self> {
+        let client = redis::Client::open(url)?;
+        Ok(Self {
+            connection: ConnectionManager::new(client).await?,
+            prefix: prefix.into(),
+        })
+    }
+
+    pub fn repository<T>(&self, kind: &str, ttl: Duration) -> RedisRepository<T> {
+        RedisRepository {
+            connection: self.connection.clone(),
+            prefix: format!("{}:{}:", self.prefix, kind),
+            ttl,
+            value: PhantomData,
+        }
+    }
+
+    pub fn challenges(&self, ttl: Duration) -> RedisRepository<ChallengeDTO> {
+        self.repository("challenge", ttl)
+    }
+
+    /// Every update keeps the record for another `ttl`, the lifetime of the newest challenge in it.
+    pub fn pending_challenges(&self, ttl: Duration) -> RedisRepository<PendingChallengesDTO> {
+        self.repository("pending_challenges", ttl)
+    }
+
+    pub fn sessions(&self, ttl: Duration) -> RedisRepository<SessionDTO> {
+        self.repository("session", ttl)
+    }
+}
+
+impl<T> RedisRepository<T> {
+    fn key(&self, key: &str) -> String {
+        format!("{}{}", self.prefix, key)
+    }
+
+    fn set_options(&self) -> SetOptions {
+        SetOptions::default().with_expiration(SetExpiry::PX(self.ttl.as_millis() as u64))
+    }
+}
+
+#[async_trait]
+impl<T: Serialize + DeserializeOwned + Send + Sync> Repository<T> for RedisRepository<T> {
+    async fn insert(&self, key: &str, value: &T) -> DBResult<()> {
+        let options = self
+            .set_options()
+            .conditional_set(redis::ExistenceCheck::NX);
+        let inserted: bool = self
+            .connection
+            .clone()
+            .set_options(self.key(key), serde_json::to_string(value)?, options)
+            .await?;
+        match inserted {
+            true => Ok(()),
+            false => Err(RepositoryError::ValueAlreadyExists),
+        }
+    }
+
+    async fn put(&self, key: &str, value: &T) -> DBResult<()> {
+        let _: () = self
+            .connection
+            .clone()
+            .set_options(
+                self.key(key),
+                serde_json::to_string(value)?,
+                self.set_options(),
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn get(&self, key: &str) -> DBResult<Option<T>> {
+        let value: Option<String> = self.connection.clone().get(self.key(key)).await?;
+        Ok(value
+            .map(|value| serde_json::from_str(&value))
+            .transpose()?)
+    }
+
+    async fn delete(&self, key: &str) -> DBResult<()> {
+        let deleted: usize = self.connection.clone().del(self.key(key)).await?;
+        match deleted {
+            0 => Err(RepositoryError::ValueNotFound),
+            _ => Ok(()),
+        }
+    }
+
+    async fn exists(&self, key: &str) -> DBResult<bool> {
+        Ok(self.connection.clone().exists(self.key(key)).await?)
+    }
+
+    /// Compares the stored value in its current encoding, as values written by an older release
+    /// may lack fields that default, then swaps only if it is still the same text.
+    async fn compare_and_swap(&self, key: &str, expected: &T, value: &T) -> DBResult<()> {
+        let current: Option<String> = self.connection.clone().get(self.key(key)).await?;
+        let current = current.ok_or(RepositoryError::ValueNotFound)?;
+        if serde_json::to_string(&serde_json::from_str::<T>(&current)?)?
+            != serde_json::to_string(expected)?
+        {
+            return Err(RepositoryError::ConcurrentModification);
+        }
+
+        let swapped: i64 = Script::new(COMPARE_AND_SWAP)
+            .key(self.key(key))
+            .arg(current)
+            .arg(serde_json::to_string(value)?)
+            .invoke_async(&mut self.connection.clone())
+            .await?;
+        match swapped {
+            1 => Ok(()),
+            0 => Err(RepositoryError::ConcurrentModification),
+            _ => Err(RepositoryError::ValueNotFound),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    // Run with a local server: `redis-server & cargo test -p auth_service -- --ignored`.
+    // `REDIS_URL` points the tests at another one.
+
+    use super::*;
+    use crate::repository::conformance::repository_tests;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    static PREFIXES: AtomicUsize = AtomicUsize::new(0);
+
+    /// Connects with a prefix of its own, so tests running at the same time see distinct keys.
+    async fn connect() -> RedisDatabase {
+        let url = std::env::var("REDIS_URL").unwrap_or_else(|_| "redis://127.0.0.1:6379".into());
+        let prefix = format!(
+            "zkp_auth_test:{}:{}",
+            std::process::id(),
+            PREFIXES.fetch_add(1, Ordering::Relaxed)
+        );
+        RedisDatabase::connect(&url, &prefix).await.unwrap()
+    }
+
+    async fn init() -> RedisRepository<String> {
+        connect().await.repository("test", Duration::from_secs(60))
+    }
+
+    repository_tests!(init().await, #[ignore = "requires redis-server"]);
+
+    #[tokio::test]
+    #[ignore = "requires redis-server"]
+    async fn should_expire_values() {
+        let db = connect()
+            .await
+            .repository::<String>("test", Duration::from_millis(100));
+        assert_eq!(db.insert("alice", &"challenge".into()).await, Ok(()));
+        assert_eq!(
+            db.compare_and_swap("alice", &"challenge".into(), &"answered".into())
+                .await,
+            Ok(())
+        );
+        assert!(db.exists("alice").await.unwrap());
+
+        tokio::time::sleep(Duration::from_millis(250)).await;
+        assert!(!db.exists("alice").await.unwrap());
+        assert_eq!(db.insert("alice", &"challenge".into()).await, Ok(()));
+    }
+
+    #[tokio::test]
+    #[ignore = "requires redis-server"]
+    async fn should_share_values_between_replicas() {
+        let (first, second) = (connect().await, connect().await);
+        let second = RedisDatabase {
+            prefix: first.prefix.clone(),
+            ..second
+        };
+        let ttl = Duration::from_secs(60);
+
+        assert_eq!(
+            first
+                .repository::<String>("test", ttl)
+                .insert("auth", &"challenge".into())
+                .await,
+            Ok(())
+        );
+        assert_eq!(
+            second.repository::<String>("test", ttl).get("auth").await,
+            Ok(Some("challenge".into()))
+        );
+    }
+}
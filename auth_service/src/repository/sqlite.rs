@@ -0,0 +1,342 @@
+use std::marker::PhantomData;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use async_trait::async_trait;
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::repository::{
+    error::RepositoryError, ChallengeDTO, DBResult, PendingChallengesDTO, Repository, SessionDTO,
+    UserDTO,
+};
+
+/// Schema migrations, applied in order. The number of applied ones is kept in `user_version`.
+const MIGRATIONS: &[&str] = &[
+    "
+    CREATE TABLE users (key TEXT PRIMARY KEY NOT NULL, value TEXT NOT NULL);
+    CREATE TABLE challenges (key TEXT PRIMARY KEY NOT NULL, value TEXT NOT NULL);
+    CREATE TABLE sessions (key TEXT PRIMARY KEY NOT NULL, value TEXT NOT NULL);
+    ",
+    "
+    -- Challenges were keyed by username before auth_ids became random.
+    DELETE FROM challenges;
+    CREATE TABLE pending_challenges (key TEXT PRIMARY KEY NOT NULL, value TEXT NOT NULL);
+    ",
+];
+
+#[derive(Clone, Copy)]
+pub enum Table {
+    Users,
+    Challenges,
+    PendingChallenges,
+    Sessions,
+}
+
+impl Table {
+    fn name(self) -> &'static str {
+        match self {
+            Table::Users => "users",
+            Table::Challenges => "challenges",
+            Table::PendingChallenges => "pending_challenges",
+            Table::Sessions => "sessions",
+        }
+    }
+}
+
+/// SQLite database file shared by the repositories of `auth_service`.
+#[derive(Clone)]
+pub struct SqliteDatabase {
+    connection: Arc<Mutex<Connection>>,
+}
+
+/// Repository keeping JSON encoded values in one table of a [`SqliteDatabase`].
+pub struct SqliteRepository<T> {
+    connection: Arc<Mutex<Connection>>,
+    table: &'static str,
+    value: PhantomData<fn() -> T>,
+}
+
+impl SqliteDatabase {
+    /// Opens or creates the database at `path` and migrates it to the latest schema.
+    pub fn open(path: impl AsRef<Path>) -> DBResult<Self> {
+        Self::migrate(Connection::open(path)?)
+    }
+
+    #[cfg(test)]
+    pub fn open_in_memory() -> DBResult<Self> {
+        Self::migrate(Connection::open_in_memory()?)
+    }
+
+    fn migrate(mut connection: Connection) -> DBResult<Self> {
+        let version: usize = connection.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+        if version > MIGRATIONS.len() {
+            return Err(RepositoryError::Storage(format!(
+                "database schema version {} is newer than the supported {}",
+                version,
+                MIGRATIONS.len()
+            )));
+        }
+
+        for (applied, migration) in MIGRATIONS.iter().enumerate().skip(version) {
+            let transaction = connection.transaction()?;
+            transaction.execute_batch(migration)?;
+            transaction.pragma_update(None, "user_version", applied + 1)?;
+            transaction.commit()?;
+        }
+
+        Ok(Self {
+            connection: Arc::new(Mutex::new(connection)),
+        })
+    }
+
+    pub fn repository<T>(&self, table: Table) -> SqliteRepository<T> {
+        SqliteRepository {
+            connection: self.connection.clone(),
+            table: table.name(),
+            value: PhantomData,
+        }
+    }
+
+    pub fn users(&self) -> SqliteRepository<UserDTO> {
+        self.repository(Table::Users)
+    }
+
+    pub fn challenges(&self) -> SqliteRepository<ChallengeDTO> {
+        self.repository(Table::Challenges)
+    }
+
+    pub fn pending_challenges(&self) -> SqliteRepository<PendingChallengesDTO> {
+        self.repository(Table::PendingChallenges)
+    }
+
+    pub fn sessions(&self) -> SqliteRepository<SessionDTO> {
+        self.repository(Table::Sessions)
+    }
+}
+
+impl<T> SqliteRepository<T> {
+    /// Runs the query on the blocking thread pool, so it does not stall the runtime.
+    async fn run<V, F>(&self, query: F) -> DBResult<V>
+    where
+        V: Send + 'static,
+        F: FnOnce(&Connection, &str) -> DBResult<V> + Send + 'static,
+    {
+        let connection = self.connection.clone();
+        let table = self.table;
+        tokio::task::spawn_blocking(move || {
+            let connection = connection
+                .lock()
+                .expect("SqliteRepository lock must not be poisoned");
+            query(&connection, table)
+        })
+        .await
+        .map_err(|e| RepositoryError::Storage(e.to_string()))?
+    }
+}
+
+#[async_trait]
+impl<T: Serialize + DeserializeOwned + Send + Sync> Repository<T> for SqliteRepository<T> {
+    async fn insert(&self, key: &str, value: &T) -> DBResult<()> {
+        let (key, value) = (key.to_owned(), serde_json::to_string(value)?);
+        self.run(move |connection, table| {
+            let sql = format!("INSERT OR IGNORE INTO {table} (key, value) VALUES (?1, ?2)");
+            match connection.execute(&sql, params![key, value])? {
+                0 => Err(RepositoryError::ValueAlreadyExists),
+                _ => Ok(()),
+            }
+        })
+        .await
+    }
+
+    async fn put(&self, key: &str, value: &T) -> DBResult<()> {
+        let (key, value) = (key.to_owned(), serde_json::to_string(value)?);
+        self.run(move |connection, table| {
+            let sql = format!(
+                "INSERT INTO {table} (key, value) VALUES (?1, ?2) \
+                 ON CONFLICT (key) DO UPDATE SET value = excluded.value"
+            );
+            connection.execute(&sql, params![key, value])?;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn get(&self, key: &str) -> DBResult<Option<T>> {
+        let key = key.to_owned();
+        let value: Option<String> = self
+            .run(move |connection, table| {
+                let sql = format!("SELECT value FROM {table} WHERE key = ?1");
+                Ok(connection
+                    .query_row(&sql, params![key], |row| row.get(0))
+                    .optional()?)
+            })
+            .await?;
+
+        Ok(value
+            .map(|value| serde_json::from_str(&value))
+            .transpose()?)
+    }
+
+    async fn delete(&self, key: &str) -> DBResult<()> {
+        let key = key.to_owned();
+        self.run(move |connection, table| {
+            let sql = format!("DELETE FROM {table} WHERE key = ?1");
+            match connection.execute(&sql, params![key])? {
+                0 => Err(RepositoryError::ValueNotFound),
+                _ => Ok(()),
+            }
+        })
+        .await
+    }
+
+    async fn exists(&self, key: &str) -> DBResult<bool> {
+        let key = key.to_owned();
+        self.run(move |connection, table| exists(connection, table, &key))
+            .await
+    }
+
+    async fn compare_and_swap(&self, key: &str, expected: &T, value: &T) -> DBResult<()> {
+        let (key, expected, value) = (
+            key.to_owned(),
+            serde_json::to_string(expected)?,
+            serde_json::to_string(value)?,
+        );
+        // Rows written by an older release may lack fields that default, so the stored value is
+        // compared in its current encoding rather than as text.
+        let reencode: fn(&str) -> DBResult<String> =
+            |stored| Ok(serde_json::to_string(&serde_json::from_str::<T>(stored)?)?);
+        self.run(move |connection, table| {
+            let sql = format!("SELECT value FROM {table} WHERE key = ?1");
+            let current: Option<String> = connection
+                .query_row(&sql, params![key], |row| row.get(0))
+                .optional()?;
+            match current {
+                None => Err(RepositoryError::ValueNotFound),
+                Some(current) if reencode(&current)? != expected => {
+                    Err(RepositoryError::ConcurrentModification)
+                }
+                Some(current) => {
+                    let sql =
+                        format!("UPDATE {table} SET value = ?3 WHERE key = ?1 AND value = ?2");
+                    match connection.execute(&sql, params![key, current, value])? {
+                        0 => Err(RepositoryError::ConcurrentModification),
+                        _ => Ok(()),
+                    }
+                }
+            }
+        })
+        .await
+    }
+}
+
+fn exists(connection: &Connection, table: &str, key: &str) -> DBResult<bool> {
+    let sql = format!("SELECT EXISTS (SELECT 1 FROM {table} WHERE key = ?1)");
+    Ok(connection.query_row(&sql, params![key], |row| row.get(0))?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repository::{conformance::repository_tests, CredentialDTO};
+
+    fn init() -> SqliteRepository<String> {
+        SqliteDatabase::open_in_memory()
+            .unwrap()
+            .repository(Table::Users)
+    }
+
+    repository_tests!(init());
+
+    #[tokio::test]
+    async fn should_keep_users_after_reopening() {
+        let path = std::env::temp_dir().join(format!("auth_service-{}.db", std::process::id()));
+        let user = UserDTO::new(
+            "alice".into(),
+            CredentialDTO::ChaumPedersen { y1: 16, y2: 81 },
+        );
+
+        let db = SqliteDatabase::open(&path).unwrap();
+        assert_eq!(db.users().insert("alice", &user).await, Ok(()));
+        drop(db);
+
+        let db = SqliteDatabase::open(&path).unwrap();
+        assert!(db.users().get("alice").await.unwrap() == Some(user));
+        assert!(!db.challenges().exists("alice").await.unwrap());
+        drop(db);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn should_rotate_user_stored_without_key_version() {
+        let db = SqliteDatabase::open_in_memory().unwrap();
+        let user = UserDTO::new(
+            "alice".into(),
+            CredentialDTO::ChaumPedersen { y1: 16, y2: 81 },
+        );
+        let mut json = serde_json::to_value(&user).unwrap();
+        json.as_object_mut().unwrap().remove("key_version");
+        db.connection
+            .lock()
+            .unwrap()
+            .execute(
+                "INSERT INTO users (key, value) VALUES ('alice', ?1)",
+                params![json.to_string()],
+            )
+            .unwrap();
+
+        let stored = db.users().get("alice").await.unwrap().unwrap();
+        let rotated = UserDTO {
+            key_version: 1,
+            ..stored.clone()
+        };
+        assert_eq!(
+            db.users()
+                .compare_and_swap("alice", &stored, &rotated)
+                .await,
+            Ok(())
+        );
+        assert_eq!(
+            db.users()
+                .compare_and_swap("alice", &stored, &rotated)
+                .await,
+            Err(RepositoryError::ConcurrentModification)
+        );
+    }
+
+    #[tokio::test]
+    async fn should_migrate_from_first_schema() {
+        let connection = Connection::open_in_memory().unwrap();
+        connection.execute_batch(MIGRATIONS[0]).unwrap();
+        connection.pragma_update(None, "user_version", 1).unwrap();
+        connection
+            .execute(
+                "INSERT INTO challenges (key, value) VALUES ('alice', '{}')",
+                [],
+            )
+            .unwrap();
+
+        let db = SqliteDatabase::migrate(connection).unwrap();
+        assert!(!db.challenges().exists("alice").await.unwrap());
+        assert!(!db.pending_challenges().exists("alice").await.unwrap());
+        let version: usize = db
+            .connection
+            .lock()
+            .unwrap()
+            .query_row("PRAGMA user_version", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(version, MIGRATIONS.len());
+    }
+
+    #[test]
+    fn should_reject_newer_schema() {
+        let connection = Connection::open_in_memory().unwrap();
+        connection
+            .pragma_update(None, "user_version", MIGRATIONS.len() + 1)
+            .unwrap();
+        assert!(matches!(
+            SqliteDatabase::migrate(connection),
+            Err(RepositoryError::Storage(_))
+        ));
+    }
+}
@@ -0,0 +1,104 @@
+use ring::aead::{Aad, LessSafeKey, Nonce, UnboundKey, CHACHA20_POLY1305, NONCE_LEN};
+use zkp::ake::SessionKey;
+
+use crate::repository::SealedKeyDTO;
+
+pub const SEALING_KEY_LEN: usize = 32;
+
+#[derive(Debug, PartialEq)]
+pub enum SealingError {
+    InvalidSealingKey,
+    /// The key was sealed with another sealing key, for another session or tampered with.
+    CannotOpen,
+}
+
+/// Encrypts session keys with ChaCha20-Poly1305 before they are stored.
+///
+/// Every key is bound to its session id, so a sealed key copied to another session cannot be
+/// opened.
+pub struct KeySealer {
+    key: LessSafeKey,
+}
+
+/// Parses a hex encoded 32-byte sealing key.
+pub fn parse_sealing_key(key_hex: &str) -> Result<[u8; SEALING_KEY_LEN], SealingError> {
+    let mut key = [0u8; SEALING_KEY_LEN];
+    hex::decode_to_slice(key_hex.trim(), &mut key).map_err(|_| SealingError::InvalidSealingKey)?;
+    Ok(key)
+}
+
+impl KeySealer {
+    pub fn new(key: &[u8; SEALING_KEY_LEN]) -> Self {
+        let key = UnboundKey::new(&CHACHA20_POLY1305, key)
+            .expect("ChaCha20-Poly1305 accepts 32-byte keys");
+        Self {
+            key: LessSafeKey::new(key),
+        }
+    }
+
+    /// Seals with a key drawn at startup, so keys stored before a restart cannot be opened.
+    pub fn random() -> Self {
+        Self::new(&rand::random())
+    }
+
+    pub fn seal(&self, session_id: &str, session_key: &SessionKey) -> SealedKeyDTO {
+        let nonce = rand::random::<[u8; NONCE_LEN]>();
+        let mut ciphertext = session_key.as_bytes().to_vec();
+        self.key
+            .seal_in_place_append_tag(
+                Nonce::assume_unique_for_key(nonce),
+                Aad::from(session_id.as_bytes()),
+                &mut ciphertext,
+            )
+            .expect("session keys are far below the ChaCha20-Poly1305 message limit");
+        SealedKeyDTO { nonce, ciphertext }
+    }
+
+    pub fn open(
+        &self,
+        session_id: &str,
+        sealed: &SealedKeyDTO,
+    ) -> Result<SessionKey, SealingError> {
+        let mut ciphertext = sealed.ciphertext.clone();
+        let plaintext = self
+            .key
+            .open_in_place(
+                Nonce::assume_unique_for_key(sealed.nonce),
+                Aad::from(session_id.as_bytes()),
+                &mut ciphertext,
+            )
+            .map_err(|_| SealingError::CannotOpen)?;
+        let key: [u8; 32] = plaintext.try_into().map_err(|_| SealingError::CannotOpen)?;
+        Ok(SessionKey::from(key))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_open_only_with_same_key_and_session() {
+        let sealer = KeySealer::new(&[1; SEALING_KEY_LEN]);
+        let session_key = SessionKey::from([7; 32]);
+
+        let sealed = sealer.seal("session", &session_key);
+        assert!(!sealed
+            .ciphertext
+            .windows(32)
+            .any(|window| window == session_key.as_bytes()));
+        assert_eq!(sealer.open("session", &sealed), Ok(session_key));
+        assert_eq!(sealer.open("other", &sealed), Err(SealingError::CannotOpen));
+        assert_eq!(
+            KeySealer::new(&[2; SEALING_KEY_LEN]).open("session", &sealed),
+            Err(SealingError::CannotOpen)
+        );
+    }
+
+    #[test]
+    fn should_parse_sealing_key() {
+        let hex = "01".repeat(SEALING_KEY_LEN);
+        assert_eq!(parse_sealing_key(&hex).unwrap(), [1; SEALING_KEY_LEN]);
+        assert!(parse_sealing_key("01").is_err());
+    }
+}
@@ -0,0 +1,269 @@
+use std::time::Duration;
+
+use jsonwebtoken::{
+    errors::Error as JwtError, Algorithm, DecodingKey, EncodingKey, Header, Validation,
+};
+use ring::signature::{Ed25519KeyPair, KeyPair};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use zkp::chaum_pedersen::Context;
+
+pub const DEFAULT_TOKEN_TTL: Duration = Duration::from_secs(15 * 60);
+pub const TOKEN_ALGORITHM: &str = "EdDSA";
+
+/// PKCS#8 encoding of an Ed25519 private key without the public key, followed by the 32-byte seed,
+/// see RFC 8410.
+const ED25519_PKCS8_PREFIX: [u8; 16] = [
+    0x30, 0x2e, 0x02, 0x01, 0x00, 0x30, 0x05, 0x06, 0x03, 0x2b, 0x65, 0x70, 0x04, 0x22, 0x04, 0x20,
+];
+const SEED_LEN: usize = 32;
+
+#[derive(Debug)]
+pub enum TokenError {
+    InvalidSigningKey,
+    MissingSigningKey,
+    UnknownKey,
+    ParamsMismatch,
+    Expired,
+    InvalidToken(JwtError),
+}
+
+impl From<JwtError> for TokenError {
+    fn from(val: JwtError) -> Self {
+        TokenError::InvalidToken(val)
+    }
+}
+
+/// Claims of a session token.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TokenClaims {
+    /// Username.
+    pub sub: String,
+    /// Unix times in seconds.
+    pub iat: u64,
+    pub exp: u64,
+    /// Session id, agreed on with the key exchange if there was one.
+    pub sid: String,
+    /// Group parameters the user proved knowledge of the secret in, see [`Context::id`].
+    pub grp: String,
+}
+
+/// Public key published for verifying tokens offline.
+#[derive(Clone)]
+pub struct VerificationKey {
+    pub key_id: String,
+    /// Raw 32-byte Ed25519 public key.
+    pub public_key: Vec<u8>,
+}
+
+struct SigningKey {
+    encoding_key: EncodingKey,
+    verification_key: VerificationKey,
+}
+
+/// Issues session tokens signed with Ed25519.
+///
+/// Only the first key signs. The others stay published after a rotation, so the tokens they
+/// signed can still be verified until they expire.
+pub struct TokenSigner {
+    keys: Vec<SigningKey>,
+    ttl: Duration,
+    params_id: String,
+}
+
+/// Parses a hex encoded 32-byte Ed25519 seed.
+pub fn parse_seed(seed_hex: &str) -> Result<[u8; SEED_LEN], TokenError> {
+    let mut seed = [0u8; SEED_LEN];
+    hex::decode_to_slice(seed_hex.trim(), &mut seed).map_err(|_| TokenError::InvalidSigningKey)?;
+    Ok(seed)
+}
+
+impl SigningKey {
+    fn from_seed(seed: &[u8; SEED_LEN]) -> Result<Self, TokenError> {
+        let mut pkcs8 = ED25519_PKCS8_PREFIX.to_vec();
+        pkcs8.extend_from_slice(seed);
+        let key_pair =
+            Ed25519KeyPair::from_seed_unchecked(seed).map_err(|_| TokenError::InvalidSigningKey)?;
+        let public_key = key_pair.public_key().as_ref().to_vec();
+
+        Ok(Self {
+            encoding_key: EncodingKey::from_ed_der(&pkcs8),
+            verification_key: VerificationKey {
+                key_id: hex::encode(&Sha256::digest(&public_key)[..8]),
+                public_key,
+            },
+        })
+    }
+}
+
+impl TokenSigner {
+    /// Signs with the first of `seeds`, publishing all of them.
+    pub fn new(seeds: &[[u8; SEED_LEN]], ttl: Duration, ctx: &Context) -> Result<Self, TokenError> {
+        if seeds.is_empty() {
+            return Err(TokenError::MissingSigningKey);
+        }
+
+        Ok(Self {
+            keys: seeds
+                .iter()
+                .map(SigningKey::from_seed)
+                .collect::<Result<_, _>>()?,
+            ttl,
+            params_id: ctx.id(),
+        })
+    }
+
+    pub fn issue(
+        &self,
+        username: &str,
+        session_id: &str,
+        now: u64,
+    ) -> Result<(String, TokenClaims), TokenError> {
+        let signing_key = &self.keys[0];
+        let claims = TokenClaims {
+            sub: username.into(),
+            iat: now,
+            exp: now + self.ttl.as_secs(),
+            sid: session_id.into(),
+            grp: self.params_id.clone(),
+        };
+        let header = Header {
+            kid: Some(signing_key.verification_key.key_id.clone()),
+            ..Header::new(Algorithm::EdDSA)
+        };
+
+        let token = jsonwebtoken::encode(&header, &claims, &signing_key.encoding_key)
+            .map_err(|_| TokenError::InvalidSigningKey)?;
+        Ok((token, claims))
+    }
+
+    pub fn signing_key_id(&self) -> &str {
+        &self.keys[0].verification_key.key_id
+    }
+
+    pub fn verification_keys(&self) -> Vec<VerificationKey> {
+        self.keys
+            .iter()
+            .map(|key| key.verification_key.clone())
+            .collect()
+    }
+
+    /// Verifies a token as an edge service would with the published keys.
+    pub fn verify(&self, token: &str, now: u64) -> Result<TokenClaims, TokenError> {
+        let claims = verify(token, &self.verification_keys(), now)?;
+        match claims.grp == self.params_id {
+            true => Ok(claims),
+            false => Err(TokenError::ParamsMismatch),
+        }
+    }
+}
+
+/// Verifies the signature of a token with the key it names and its expiry at `now`.
+pub fn verify(token: &str, keys: &[VerificationKey], now: u64) -> Result<TokenClaims, TokenError> {
+    let kid = jsonwebtoken::decode_header(token)?.kid;
+    let key = keys
+        .iter()
+        .find(|key| Some(&key.key_id) == kid.as_ref())
+        .ok_or(TokenError::UnknownKey)?;
+    let mut validation = Validation::new(Algorithm::EdDSA);
+    validation.set_required_spec_claims(&["exp", "sub"]);
+    validation.validate_exp = false;
+
+    let claims: TokenClaims = jsonwebtoken::decode(
+        token,
+        &DecodingKey::from_ed_der(&key.public_key),
+        &validation,
+    )?
+    .claims;
+    match now < claims.exp {
+        true => Ok(claims),
+        false => Err(TokenError::Expired),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOW: u64 = 1_700_000_000;
+
+    fn ctx() -> Context {
+        Context::new(4, 9, 20123, 10061).unwrap()
+    }
+
+    fn signer(seeds: &[[u8; SEED_LEN]]) -> TokenSigner {
+        TokenSigner::new(seeds, DEFAULT_TOKEN_TTL, &ctx()).unwrap()
+    }
+
+    #[test]
+    fn should_verify_issued_token() {
+        let signer = signer(&[[1; SEED_LEN]]);
+        let (token, claims) = signer.issue("alice", "ab", NOW).unwrap();
+
+        let verified = signer.verify(&token, NOW).unwrap();
+        assert_eq!(verified, claims);
+        assert_eq!(verified.sub, "alice");
+        assert_eq!(verified.grp, ctx().id());
+        assert_eq!(verified.exp - verified.iat, DEFAULT_TOKEN_TTL.as_secs());
+    }
+
+    #[test]
+    fn should_verify_tokens_of_previous_key_after_rotation() {
+        let old = signer(&[[1; SEED_LEN]]);
+        let rotated = signer(&[[2; SEED_LEN], [1; SEED_LEN]]);
+        let (token, _) = old.issue("alice", "ab", NOW).unwrap();
+
+        assert_ne!(rotated.signing_key_id(), old.signing_key_id());
+        assert!(verify(&token, &rotated.verification_keys(), NOW).is_ok());
+        assert!(matches!(
+            signer(&[[2; SEED_LEN]]).verify(&token, NOW),
+            Err(TokenError::UnknownKey)
+        ));
+    }
+
+    #[test]
+    fn should_reject_expired_and_forged_tokens() {
+        let signer = signer(&[[1; SEED_LEN]]);
+        let (token, claims) = signer.issue("alice", "ab", NOW).unwrap();
+        assert!(matches!(
+            signer.verify(&token, claims.exp),
+            Err(TokenError::Expired)
+        ));
+
+        let forged = TokenClaims {
+            sub: "mallory".into(),
+            ..claims
+        };
+        let mut parts: Vec<_> = token.split('.').map(String::from).collect();
+        parts[1] =
+            jsonwebtoken::encode(&Header::default(), &forged, &EncodingKey::from_secret(b""))
+                .unwrap()
+                .split('.')
+                .nth(1)
+                .unwrap()
+                .into();
+        assert!(matches!(
+            signer.verify(&parts.join("."), NOW),
+            Err(TokenError::InvalidToken(_))
+        ));
+
+        let other_group = TokenSigner::new(
+            &[[1; SEED_LEN]],
+            DEFAULT_TOKEN_TTL,
+            &Context::new(4, 9, 23, 11).unwrap(),
+        )
+        .unwrap();
+        assert!(matches!(
+            other_group.verify(&token, NOW),
+            Err(TokenError::ParamsMismatch)
+        ));
+    }
+
+    #[test]
+    fn should_parse_seed() {
+        let hex = "01".repeat(SEED_LEN);
+        assert_eq!(parse_seed(&hex).unwrap(), [1; SEED_LEN]);
+        assert!(parse_seed("01").is_err());
+        assert!(parse_seed(&"zz".repeat(SEED_LEN)).is_err());
+    }
+}
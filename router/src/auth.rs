@@ -1,18 +1,36 @@
 use actix_web::{http::header::ContentType, HttpResponse};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
 
-use tonic::{transport::Channel, Code, Request};
+use tonic::{transport::Channel, Code, Request, Status};
 
 use crate::{
-    zkp_auth::{AuthenticationAnswerRequest, AuthenticationChallengeRequest, RegisterRequest},
+    zkp_auth::{
+        AuthenticationAnswerRequest, AuthenticationChallengeRequest, ClientMetadata,
+        GetTokenKeysRequest, KeyShare, OkamotoAnswer, OkamotoCommitment, OkamotoCredential,
+        RefreshSessionRequest, RegisterRequest, RevokeSessionRequest, RotateCredentialsRequest,
+        Session, TokenKey, ValidateSessionRequest,
+    },
     AuthClient,
 };
 
 use error::AuthClientError;
 
-use zkp::chaum_pedersen::ChaumPedersenProtocol;
+use zkp::{
+    ake::{key_exchange_transcript, EphemeralSecret, KeyAgreement, KeyExchange},
+    chaum_pedersen::{ChaumPedersenProtocol, Proof},
+    okamoto::OkamotoProtocol,
+    rotation::{rotation_transcript, RotationProtocol},
+    server_auth::{challenge_transcript, ServerAuthProtocol},
+};
 
 mod error;
 
+/// Pinned identity of `AuthService` that every challenge is checked against.
+pub struct ServerIdentity {
+    pub server_auth: ServerAuthProtocol,
+    pub public_key: Vec<i64>,
+}
+
 #[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
 pub struct RegisterCalculateRequest {
     user: String,
@@ -50,6 +68,7 @@ impl From<RegisterData> for RegisterRequest {
             user: val.username,
             y1: val.y1,
             y2: val.y2,
+            okamoto: None,
         }
     }
 }
@@ -69,6 +88,34 @@ pub struct LoginData {
     pub k: i64,
 }
 
+#[derive(Clone, serde::Serialize, serde::Deserialize, Debug)]
+pub struct RotateData {
+    pub user: String,
+    pub x: i64,
+    pub k: i64,
+    pub new_x: i64,
+    pub new_k: i64,
+    /// Version of the current key, `0` before the first rotation.
+    #[serde(default)]
+    pub key_version: u64,
+}
+
+#[derive(Clone, serde::Serialize, serde::Deserialize, Debug)]
+pub struct RotatedData {
+    pub user: String,
+    pub y1: i64,
+    pub y2: i64,
+    pub key_version: u64,
+}
+
+impl From<RotatedData> for HttpResponse {
+    fn from(val: RotatedData) -> Self {
+        HttpResponse::Ok()
+            .content_type(ContentType::json())
+            .body(serde_json::to_string(&val).expect("`RotatedData` is serializable to json"))
+    }
+}
+
 #[derive(Clone, serde::Serialize, serde::Deserialize)]
 pub struct AuthChallengeData {
     pub auth_id: String,
@@ -80,13 +127,82 @@ impl From<AuthChallengeData> for AuthenticationAnswerRequest {
         AuthenticationAnswerRequest {
             auth_id: val.auth_id,
             s: val.s,
+            okamoto: None,
+            client: None,
+        }
+    }
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct OkamotoRegisterCalculateRequest {
+    user: String,
+    a: i64,
+    b: i64,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+pub struct OkamotoRegisterCalculateResponse {
+    user: String,
+    y: i64,
+}
+
+impl From<OkamotoRegisterCalculateResponse> for HttpResponse {
+    fn from(val: OkamotoRegisterCalculateResponse) -> Self {
+        HttpResponse::Created()
+            .content_type(ContentType::json())
+            .body(
+                serde_json::to_string(&val)
+                    .expect("`OkamotoRegisterCalculateResponse` is serializable to json"),
+            )
+    }
+}
+
+#[derive(Clone, serde::Serialize, serde::Deserialize, Debug)]
+pub struct OkamotoRegisterData {
+    pub username: String,
+    pub y: i64,
+}
+
+impl From<OkamotoRegisterData> for RegisterRequest {
+    fn from(val: OkamotoRegisterData) -> Self {
+        RegisterRequest {
+            user: val.username,
+            y1: 0,
+            y2: 0,
+            okamoto: Some(OkamotoCredential { y: val.y }),
         }
     }
 }
 
+impl From<OkamotoRegisterData> for HttpResponse {
+    fn from(val: OkamotoRegisterData) -> Self {
+        HttpResponse::Created()
+            .content_type(ContentType::json())
+            .body(
+                serde_json::to_string(&val).expect("`OkamotoRegisterData` is serializable to json"),
+            )
+    }
+}
+
+#[derive(Clone, serde::Serialize, serde::Deserialize, Debug)]
+pub struct OkamotoLoginData {
+    pub user: String,
+    pub a: i64,
+    pub b: i64,
+    pub k1: i64,
+    pub k2: i64,
+}
+
 #[derive(Clone, serde::Serialize, serde::Deserialize)]
 pub struct SessionData {
     pub session_id: String,
+    /// Hex encoded key the client tags its session requests with, see [`SessionIdData`].
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub session_key: Option<String>,
+    pub expires_at: u64,
+    /// Signed session token, if `AuthService` issues them instead of storing sessions.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
 }
 
 impl From<SessionData> for HttpResponse {
@@ -97,6 +213,96 @@ impl From<SessionData> for HttpResponse {
     }
 }
 
+/// Session id of a request, tagged with the session key if the login returned one.
+///
+/// `tag` is the hex encoded HMAC-SHA256 of the request, see `ValidateSessionRequest` in
+/// `nillion.proto`, and `counter` has to grow with every request.
+#[derive(Clone, serde::Serialize, serde::Deserialize, Debug)]
+pub struct SessionIdData {
+    pub session_id: String,
+    #[serde(default)]
+    pub counter: u64,
+    #[serde(default)]
+    pub tag: String,
+}
+
+/// Either a tagged session id, or a signed token when `AuthService` issues them.
+#[derive(Clone, serde::Serialize, serde::Deserialize, Debug)]
+pub struct ValidateSessionData {
+    #[serde(default)]
+    pub session_id: String,
+    #[serde(default)]
+    pub counter: u64,
+    #[serde(default)]
+    pub tag: String,
+    #[serde(default)]
+    pub token: String,
+}
+
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
+pub struct SessionInfoData {
+    pub session_id: String,
+    pub user: String,
+    pub created_at: u64,
+    pub last_seen_at: u64,
+    pub expires_at: u64,
+}
+
+impl From<Session> for SessionInfoData {
+    fn from(val: Session) -> Self {
+        SessionInfoData {
+            session_id: val.session_id,
+            user: val.user,
+            created_at: val.created_at,
+            last_seen_at: val.last_seen_at,
+            expires_at: val.expires_at,
+        }
+    }
+}
+
+impl From<SessionInfoData> for HttpResponse {
+    fn from(val: SessionInfoData) -> Self {
+        HttpResponse::Ok()
+            .content_type(ContentType::json())
+            .body(serde_json::to_string(&val).expect("`SessionInfoData` is serializable to json"))
+    }
+}
+
+/// Token verification key as a JSON Web Key, see RFC 8037.
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
+pub struct JsonWebKey {
+    kty: String,
+    crv: String,
+    alg: String,
+    kid: String,
+    x: String,
+}
+
+impl From<TokenKey> for JsonWebKey {
+    fn from(val: TokenKey) -> Self {
+        JsonWebKey {
+            kty: "OKP".into(),
+            crv: "Ed25519".into(),
+            alg: val.algorithm,
+            kid: val.key_id,
+            x: URL_SAFE_NO_PAD.encode(val.public_key),
+        }
+    }
+}
+
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
+pub struct JsonWebKeySet {
+    keys: Vec<JsonWebKey>,
+}
+
+impl From<JsonWebKeySet> for HttpResponse {
+    fn from(val: JsonWebKeySet) -> Self {
+        HttpResponse::Ok()
+            .content_type(ContentType::json())
+            .body(serde_json::to_string(&val).expect("`JsonWebKeySet` is serializable to json"))
+    }
+}
+
 pub fn register_calculate(
     zkp: &ChaumPedersenProtocol,
     data: &RegisterCalculateRequest,
@@ -116,53 +322,343 @@ pub async fn register(
     let req = register_data.clone().into();
     log::info!("Sending gRPC request: {:?}", req);
 
-    auth_client.register(Request::new(req)).await.map_or_else(
-        |status| Err(status.code().into()),
-        |_response| Ok(register_data),
-    )
+    auth_client
+        .register(Request::new(req))
+        .await
+        .map_or_else(|status| Err(status.into()), |_response| Ok(register_data))
+}
+
+/// Requests a challenge and accepts it only with a valid proof of the server identity.
+async fn create_authentication_challenge(
+    auth_client: &mut AuthClient<Channel>,
+    server: &ServerIdentity,
+    req: AuthenticationChallengeRequest,
+) -> Result<(String, i64, Option<i64>), AuthClientError> {
+    log::info!("Sending gRPC request: {:?}", req);
+
+    let response = auth_client
+        .create_authentication_challenge(Request::new(req.clone()))
+        .await
+        .map_err(AuthClientError::from)?
+        .into_inner();
+    let server_share = response.key_share.map(|KeyShare { share }| share);
+    let commitments = match req.okamoto {
+        Some(OkamotoCommitment { t }) => vec![t],
+        None => vec![req.r1, req.r2],
+    };
+    let client_share = req.key_share.map(|KeyShare { share }| share);
+
+    let proof = response
+        .server_proof
+        .map(|proof| Proof {
+            c: proof.c,
+            s: proof.s,
+        })
+        .ok_or(AuthClientError::ServerAuthenticationFailure)?;
+    server
+        .server_auth
+        .verify(
+            &server.public_key,
+            &mut challenge_transcript(
+                &req.user,
+                &commitments,
+                client_share.zip(server_share),
+                &response.auth_id,
+                response.c,
+            ),
+            &proof,
+        )
+        .map_err(|_| AuthClientError::ServerAuthenticationFailure)?;
+
+    Ok((response.auth_id, response.c, server_share))
+}
+
+async fn verify_authentication(
+    auth_client: &mut AuthClient<Channel>,
+    req: AuthenticationAnswerRequest,
+    client: ClientMetadata,
+) -> Result<SessionData, AuthClientError> {
+    let req = AuthenticationAnswerRequest {
+        client: Some(client),
+        ..req
+    };
+    log::info!("Sending gRPC request: {:?}", req);
+
+    auth_client
+        .verify_authentication(Request::new(req))
+        .await
+        .map_or_else(
+            |status| Err(status.into()),
+            |response| {
+                let response = response.into_inner();
+                Ok(SessionData {
+                    session_id: response.session_id,
+                    session_key: None,
+                    expires_at: response.expires_at,
+                    token: Some(response.token).filter(|token| !token.is_empty()),
+                })
+            },
+        )
+}
+
+fn agree_session_key(
+    key_exchange: &KeyExchange,
+    user: &str,
+    secret: EphemeralSecret,
+    server_share: Option<i64>,
+    c: i64,
+) -> Result<KeyAgreement, AuthClientError> {
+    let server_share = server_share.ok_or(AuthClientError::UnexpectedResponse)?;
+    Ok(key_exchange.client_agree(&mut key_exchange_transcript(user), secret, server_share, c)?)
+}
+
+/// Accepts the session only if the server derived the same session id, i.e. holds the same key.
+///
+/// The key is handed to the client, which tags its session requests with it. Signed tokens are
+/// bearer credentials that no request is tagged for, so the key is left out with them.
+fn confirm_session(
+    session: SessionData,
+    agreement: KeyAgreement,
+) -> Result<SessionData, AuthClientError> {
+    if session.session_id != hex::encode(agreement.session_id) {
+        return Err(AuthClientError::UnexpectedResponse);
+    }
+
+    let session_key = match session.token {
+        None => Some(hex::encode(agreement.session_key.as_bytes())),
+        Some(_) => None,
+    };
+    Ok(SessionData {
+        session_key,
+        ..session
+    })
 }
 
 pub async fn login(
     auth_client: &mut AuthClient<Channel>,
+    server: &ServerIdentity,
     zkp: &ChaumPedersenProtocol,
+    key_exchange: &KeyExchange,
     login_data: LoginData,
+    client: ClientMetadata,
 ) -> Result<SessionData, AuthClientError> {
     let (r1, r2) = zkp.calculate_login_challenge_data(login_data.k)?;
+    let secret = key_exchange.generate(&mut rand::thread_rng());
     let req = AuthenticationChallengeRequest {
         user: login_data.user.clone(),
         r1,
         r2,
+        okamoto: None,
+        key_share: Some(KeyShare {
+            share: secret.share(),
+        }),
+        client: Some(client.clone()),
+    };
+
+    let (auth_id, c, server_share) =
+        create_authentication_challenge(auth_client, server, req).await?;
+    let agreement = agree_session_key(key_exchange, &login_data.user, secret, server_share, c)?;
+
+    let auth_challenge_data = AuthChallengeData {
+        auth_id,
+        s: zkp.calculate_challenge(login_data.k, agreement.c, login_data.x)?,
+    };
+
+    let session = verify_authentication(auth_client, auth_challenge_data.into(), client).await?;
+    confirm_session(session, agreement)
+}
+
+pub fn okamoto_register_calculate(
+    okamoto: &OkamotoProtocol,
+    data: &OkamotoRegisterCalculateRequest,
+) -> Result<OkamotoRegisterCalculateResponse, AuthClientError> {
+    let y = okamoto.calculate_registration_data(&[data.a, data.b])?;
+    Ok(OkamotoRegisterCalculateResponse {
+        user: data.user.clone(),
+        y,
+    })
+}
+
+pub async fn okamoto_register(
+    auth_client: &mut AuthClient<Channel>,
+    register_data: OkamotoRegisterData,
+) -> Result<OkamotoRegisterData, AuthClientError> {
+    let req = register_data.clone().into();
+    log::info!("Sending gRPC request: {:?}", req);
+
+    auth_client
+        .register(Request::new(req))
+        .await
+        .map_or_else(|status| Err(status.into()), |_response| Ok(register_data))
+}
+
+pub async fn okamoto_login(
+    auth_client: &mut AuthClient<Channel>,
+    server: &ServerIdentity,
+    okamoto: &OkamotoProtocol,
+    key_exchange: &KeyExchange,
+    login_data: OkamotoLoginData,
+    client: ClientMetadata,
+) -> Result<SessionData, AuthClientError> {
+    let nonces = [login_data.k1, login_data.k2];
+    let t = okamoto.calculate_login_challenge_data(&nonces)?;
+    let secret = key_exchange.generate(&mut rand::thread_rng());
+    let req = AuthenticationChallengeRequest {
+        user: login_data.user.clone(),
+        r1: 0,
+        r2: 0,
+        okamoto: Some(OkamotoCommitment { t }),
+        key_share: Some(KeyShare {
+            share: secret.share(),
+        }),
+        client: Some(client.clone()),
+    };
+
+    let (auth_id, c, server_share) =
+        create_authentication_challenge(auth_client, server, req).await?;
+    let agreement = agree_session_key(key_exchange, &login_data.user, secret, server_share, c)?;
+
+    let responses =
+        okamoto.calculate_challenge(&nonces, agreement.c, &[login_data.a, login_data.b])?;
+    let req = AuthenticationAnswerRequest {
+        auth_id,
+        s: 0,
+        okamoto: Some(OkamotoAnswer {
+            s1: responses[0],
+            s2: responses[1],
+        }),
+        client: None,
     };
 
+    let session = verify_authentication(auth_client, req, client).await?;
+    confirm_session(session, agreement)
+}
+
+pub async fn rotate_credentials(
+    auth_client: &mut AuthClient<Channel>,
+    rotation: &RotationProtocol,
+    rotate_data: RotateData,
+) -> Result<RotatedData, AuthClientError> {
+    let new_ys = rotation.calculate_new_registration_data(rotate_data.new_x);
+    let proof = rotation.create_proof(
+        &mut rotation_transcript(&rotate_data.user, rotate_data.key_version),
+        (rotate_data.x, rotate_data.k),
+        (rotate_data.new_x, rotate_data.new_k),
+    );
+    let req = RotateCredentialsRequest {
+        user: rotate_data.user.clone(),
+        new_y1: new_ys[0],
+        new_y2: new_ys[1],
+        c: proof.c,
+        s: proof.s,
+        new_s: proof.new_s,
+        key_version: rotate_data.key_version,
+    };
     log::info!("Sending gRPC request: {:?}", req);
 
-    let (auth_id, c) = auth_client
-        .create_authentication_challenge(Request::new(req))
+    auth_client
+        .rotate_credentials(Request::new(req))
         .await
         .map_or_else(
-            |status| Err(<Code as Into<AuthClientError>>::into(status.code())),
+            |status| Err(status.into()),
             |response| {
-                let response = response.into_inner();
-                Ok((response.auth_id, response.c))
+                Ok(RotatedData {
+                    user: rotate_data.user,
+                    y1: new_ys[0],
+                    y2: new_ys[1],
+                    key_version: response.into_inner().key_version,
+                })
             },
-        )?;
+        )
+}
 
-    let auth_challenge_data = AuthChallengeData {
-        auth_id,
-        s: zkp.calculate_challenge(login_data.k, c, login_data.x)?,
+/// Unknown and expired sessions are reported alike, as the caller has to log in again.
+fn session_error(status: Status) -> AuthClientError {
+    match status.code() {
+        Code::Unauthenticated => AuthClientError::InvalidSession,
+        _ => status.into(),
+    }
+}
+
+fn decode_tag(tag: &str) -> Result<Vec<u8>, AuthClientError> {
+    hex::decode(tag).map_err(|_| AuthClientError::InvalidArgument("Malformed session tag".into()))
+}
+
+pub async fn validate_session(
+    auth_client: &mut AuthClient<Channel>,
+    data: ValidateSessionData,
+) -> Result<SessionInfoData, AuthClientError> {
+    let req = ValidateSessionRequest {
+        session_id: data.session_id,
+        token: data.token,
+        counter: data.counter,
+        tag: decode_tag(&data.tag)?,
     };
 
-    let req = auth_challenge_data.into();
-    log::info!("Sending gRPC request: {:?}", req);
+    let response = auth_client
+        .validate_session(Request::new(req))
+        .await
+        .map_err(session_error)?
+        .into_inner();
+    response
+        .session
+        .map(SessionInfoData::from)
+        .ok_or(AuthClientError::UnexpectedResponse)
+}
+
+pub async fn refresh_session(
+    auth_client: &mut AuthClient<Channel>,
+    data: SessionIdData,
+) -> Result<SessionInfoData, AuthClientError> {
+    let req = RefreshSessionRequest {
+        session_id: data.session_id,
+        counter: data.counter,
+        tag: decode_tag(&data.tag)?,
+    };
+
+    let response = auth_client
+        .refresh_session(Request::new(req))
+        .await
+        .map_err(session_error)?
+        .into_inner();
+    response
+        .session
+        .map(SessionInfoData::from)
+        .ok_or(AuthClientError::UnexpectedResponse)
+}
+
+pub async fn revoke_session(
+    auth_client: &mut AuthClient<Channel>,
+    data: SessionIdData,
+) -> Result<(), AuthClientError> {
+    let req = RevokeSessionRequest {
+        session_id: data.session_id,
+        counter: data.counter,
+        tag: decode_tag(&data.tag)?,
+    };
 
     auth_client
-        .verify_authentication(Request::new(req))
+        .revoke_session(Request::new(req))
+        .await
+        .map_or_else(|status| Err(session_error(status)), |_response| Ok(()))
+}
+
+pub async fn token_keys(
+    auth_client: &mut AuthClient<Channel>,
+) -> Result<JsonWebKeySet, AuthClientError> {
+    auth_client
+        .get_token_keys(Request::new(GetTokenKeysRequest {}))
         .await
         .map_or_else(
-            |status| Err(status.code().into()),
+            |status| Err(status.into()),
             |response| {
-                Ok(SessionData {
-                    session_id: response.into_inner().session_id,
+                Ok(JsonWebKeySet {
+                    keys: response
+                        .into_inner()
+                        .keys
+                        .into_iter()
+                        .map(JsonWebKey::from)
+                        .collect(),
                 })
             },
         )
@@ -1,47 +1,110 @@
-use actix_web::HttpResponse;
+use actix_web::{http::header::RETRY_AFTER, HttpResponse, HttpResponseBuilder};
 
-use tonic::Code;
+use tonic::{Code, Status};
 
 use zkp::ZKPError;
 
 pub enum AuthClientError {
+    AccountLocked(Option<u64>),
     AuthenticationFailure,
+    ChallengeExpired,
+    ConcurrentModification,
     ConnectionFailed,
     InternalServerError,
-    ServerMathError,
+    InvalidArgument(String),
+    InvalidSession,
+    ServerAuthenticationFailure,
+    ServiceUnavailable,
+    TooManyRequests(Option<u64>),
     UserAlreadyRegistered,
     UserNotFound,
     UnexpectedResponse,
+    Unsupported,
 }
 
 impl From<ZKPError> for AuthClientError {
-    fn from(_val: ZKPError) -> Self {
-        AuthClientError::ServerMathError
+    fn from(val: ZKPError) -> Self {
+        match val {
+            ZKPError::LengthMismatch { .. }
+            | ZKPError::ElementNotInSubgroup { .. }
+            | ZKPError::ScalarOutOfRange { .. }
+            | ZKPError::MalformedEncoding { .. }
+            | ZKPError::InvalidShares { .. }
+            | ZKPError::InvalidMnemonic { .. }
+            | ZKPError::InvalidKeystore { .. }
+            | ZKPError::InvalidBackupShare { .. } => {
+                AuthClientError::InvalidArgument(val.to_string())
+            }
+            ZKPError::VerificationFailed | ZKPError::WrongPassword => {
+                AuthClientError::AuthenticationFailure
+            }
+            ZKPError::InvalidKeyShare => AuthClientError::UnexpectedResponse,
+            ZKPError::RngFailure => AuthClientError::ServiceUnavailable,
+            ZKPError::InvalidParameters { .. } | ZKPError::EmptyBases => {
+                AuthClientError::InternalServerError
+            }
+        }
     }
 }
 
-impl From<Code> for AuthClientError {
-    fn from(val: Code) -> Self {
-        match val {
+impl From<Status> for AuthClientError {
+    fn from(val: Status) -> Self {
+        match val.code() {
+            Code::Aborted => AuthClientError::ConcurrentModification,
             Code::AlreadyExists => AuthClientError::UserAlreadyRegistered,
+            Code::DeadlineExceeded => AuthClientError::ChallengeExpired,
+            Code::FailedPrecondition => AuthClientError::AccountLocked(retry_after(&val)),
+            Code::InvalidArgument => AuthClientError::InvalidArgument(val.message().to_string()),
             Code::NotFound => AuthClientError::UserNotFound,
-            Code::ResourceExhausted => AuthClientError::ServerMathError,
+            Code::ResourceExhausted => AuthClientError::TooManyRequests(retry_after(&val)),
             Code::Unauthenticated => AuthClientError::AuthenticationFailure,
+            Code::Unavailable => AuthClientError::ServiceUnavailable,
+            Code::Unimplemented => AuthClientError::Unsupported,
             _ => AuthClientError::InternalServerError,
         }
     }
 }
 
+/// Seconds the service asked to wait before retrying.
+fn retry_after(status: &Status) -> Option<u64> {
+    status
+        .metadata()
+        .get("retry-after")?
+        .to_str()
+        .ok()?
+        .parse()
+        .ok()
+}
+
+fn with_retry_after(mut response: HttpResponseBuilder, retry_after: Option<u64>) -> HttpResponse {
+    if let Some(secs) = retry_after {
+        response.insert_header((RETRY_AFTER, secs));
+    }
+    response.finish()
+}
+
 impl From<AuthClientError> for HttpResponse {
     fn from(val: AuthClientError) -> Self {
         match val {
+            AuthClientError::AccountLocked(retry_after) => {
+                with_retry_after(HttpResponse::Locked(), retry_after)
+            }
             AuthClientError::AuthenticationFailure => HttpResponse::Forbidden().into(),
+            AuthClientError::ChallengeExpired => HttpResponse::RequestTimeout().into(),
+            AuthClientError::ConcurrentModification => HttpResponse::Conflict().into(),
             AuthClientError::ConnectionFailed => HttpResponse::InternalServerError().into(),
             AuthClientError::InternalServerError => HttpResponse::InternalServerError().into(),
-            AuthClientError::ServerMathError => HttpResponse::InsufficientStorage().into(),
+            AuthClientError::InvalidArgument(msg) => HttpResponse::BadRequest().body(msg),
+            AuthClientError::InvalidSession => HttpResponse::Unauthorized().into(),
+            AuthClientError::ServerAuthenticationFailure => HttpResponse::BadGateway().into(),
+            AuthClientError::ServiceUnavailable => HttpResponse::ServiceUnavailable().into(),
+            AuthClientError::TooManyRequests(retry_after) => {
+                with_retry_after(HttpResponse::TooManyRequests(), retry_after)
+            }
             AuthClientError::UserAlreadyRegistered => HttpResponse::NotAcceptable().into(),
             AuthClientError::UserNotFound => HttpResponse::NotAcceptable().into(),
             AuthClientError::UnexpectedResponse => HttpResponse::InternalServerError().into(),
+            AuthClientError::Unsupported => HttpResponse::NotImplemented().into(),
         }
     }
 }
@@ -1,14 +1,20 @@
-use actix_web::{middleware, post, web, web::Data, App, HttpResponse, HttpServer};
+use actix_web::{
+    get, http::header, middleware, post, web, web::Data, App, HttpRequest, HttpResponse, HttpServer,
+};
 
 use std::env;
 use std::sync::Mutex;
 
-use auth::{LoginData, RegisterCalculateRequest, RegisterData};
-use zkp::chaum_pedersen;
+use auth::{
+    LoginData, OkamotoLoginData, OkamotoRegisterCalculateRequest, OkamotoRegisterData,
+    RegisterCalculateRequest, RegisterData, RotateData, ServerIdentity, SessionIdData,
+    ValidateSessionData,
+};
+use zkp::{ake, chaum_pedersen, okamoto, rotation, server_auth};
 
 use tonic::transport::Channel;
 
-use zkp_auth::auth_client::AuthClient;
+use zkp_auth::{auth_client::AuthClient, ClientMetadata};
 
 pub mod zkp_auth {
     tonic::include_proto!("zkp_auth");
@@ -20,13 +26,19 @@ const AUTH_SERVICE_URI_ENV: &str = "AUTH_SERVICE_URI";
 const HTTP_PORT_ENV: &str = "HTTP_PORT";
 const ZKP_G_ENV: &str = "ZKP_G";
 const ZKP_H_ENV: &str = "ZKP_H";
+const ZKP_P_ENV: &str = "ZKP_P";
 const ZKP_Q_ENV: &str = "ZKP_Q";
+const AUTH_SERVICE_PUBLIC_KEY_ENV: &str = "AUTH_SERVICE_PUBLIC_KEY";
 
 const LOG_TARGET: &str = "router";
 
 struct AppState {
     auth_client: Mutex<AuthClient<Channel>>,
     zkp: chaum_pedersen::ChaumPedersenProtocol,
+    okamoto: okamoto::OkamotoProtocol,
+    rotation: rotation::RotationProtocol,
+    key_exchange: ake::KeyExchange,
+    server: ServerIdentity,
 }
 
 #[post("/register/calculate")]
@@ -46,31 +58,55 @@ async fn register_calculate(
 async fn register(app_state: Data<AppState>, data: web::Json<RegisterData>) -> HttpResponse {
     log::info!("Handling register request: {:?}", data);
 
-    match auth::register(
-        &mut app_state
-            .auth_client
-            .lock()
-            .expect("Auth client must be available in `register` handler"),
-        data.into_inner(),
-    )
-    .await
-    {
+    let mut auth_client = app_state
+        .auth_client
+        .lock()
+        .expect("Auth client must be available in `register` handler")
+        .clone();
+
+    match auth::register(&mut auth_client, data.into_inner()).await {
         Ok(register_data) => register_data.into(),
         Err(e) => e.into(),
     }
 }
 
+/// Describes the HTTP client to `AuthService`, which records it with the session.
+fn client_metadata(req: &HttpRequest) -> ClientMetadata {
+    ClientMetadata {
+        address: req
+            .peer_addr()
+            .map(|address| address.ip().to_string())
+            .unwrap_or_default(),
+        user_agent: req
+            .headers()
+            .get(header::USER_AGENT)
+            .and_then(|user_agent| user_agent.to_str().ok())
+            .unwrap_or_default()
+            .to_string(),
+    }
+}
+
 #[post("/login")]
-async fn login(app_state: Data<AppState>, data: web::Json<LoginData>) -> HttpResponse {
+async fn login(
+    req: HttpRequest,
+    app_state: Data<AppState>,
+    data: web::Json<LoginData>,
+) -> HttpResponse {
     log::info!("Handling login request: {:?}", data);
 
+    let mut auth_client = app_state
+        .auth_client
+        .lock()
+        .expect("Auth client must be available in `login` handler")
+        .clone();
+
     match auth::login(
-        &mut app_state
-            .auth_client
-            .lock()
-            .expect("Auth client must be available in `login` handler"),
+        &mut auth_client,
+        &app_state.server,
         &app_state.zkp,
+        &app_state.key_exchange,
         data.into_inner(),
+        client_metadata(&req),
     )
     .await
     {
@@ -79,6 +115,153 @@ async fn login(app_state: Data<AppState>, data: web::Json<LoginData>) -> HttpRes
     }
 }
 
+#[post("/rotate")]
+async fn rotate(app_state: Data<AppState>, data: web::Json<RotateData>) -> HttpResponse {
+    log::info!("Handling rotate request for user: {:?}", data.user);
+
+    let mut auth_client = app_state
+        .auth_client
+        .lock()
+        .expect("Auth client must be available in `rotate` handler")
+        .clone();
+
+    match auth::rotate_credentials(&mut auth_client, &app_state.rotation, data.into_inner()).await {
+        Ok(rotated_data) => rotated_data.into(),
+        Err(e) => e.into(),
+    }
+}
+
+#[post("/okamoto/register/calculate")]
+async fn okamoto_register_calculate(
+    app_state: Data<AppState>,
+    data: web::Json<OkamotoRegisterCalculateRequest>,
+) -> HttpResponse {
+    log::info!("Handling Okamoto register calculate request: {:?}", data);
+
+    match auth::okamoto_register_calculate(&app_state.okamoto, &data.into_inner()) {
+        Ok(register_data) => register_data.into(),
+        Err(e) => e.into(),
+    }
+}
+
+#[post("/okamoto/register")]
+async fn okamoto_register(
+    app_state: Data<AppState>,
+    data: web::Json<OkamotoRegisterData>,
+) -> HttpResponse {
+    log::info!("Handling Okamoto register request: {:?}", data);
+
+    let mut auth_client = app_state
+        .auth_client
+        .lock()
+        .expect("Auth client must be available in `okamoto_register` handler")
+        .clone();
+
+    match auth::okamoto_register(&mut auth_client, data.into_inner()).await {
+        Ok(register_data) => register_data.into(),
+        Err(e) => e.into(),
+    }
+}
+
+#[post("/okamoto/login")]
+async fn okamoto_login(
+    req: HttpRequest,
+    app_state: Data<AppState>,
+    data: web::Json<OkamotoLoginData>,
+) -> HttpResponse {
+    log::info!("Handling Okamoto login request: {:?}", data);
+
+    let mut auth_client = app_state
+        .auth_client
+        .lock()
+        .expect("Auth client must be available in `okamoto_login` handler")
+        .clone();
+
+    match auth::okamoto_login(
+        &mut auth_client,
+        &app_state.server,
+        &app_state.okamoto,
+        &app_state.key_exchange,
+        data.into_inner(),
+        client_metadata(&req),
+    )
+    .await
+    {
+        Ok(session_data) => session_data.into(),
+        Err(e) => e.into(),
+    }
+}
+
+#[post("/session/validate")]
+async fn validate_session(
+    app_state: Data<AppState>,
+    data: web::Json<ValidateSessionData>,
+) -> HttpResponse {
+    log::info!("Handling validate session request");
+
+    let mut auth_client = app_state
+        .auth_client
+        .lock()
+        .expect("Auth client must be available in `validate_session` handler")
+        .clone();
+
+    match auth::validate_session(&mut auth_client, data.into_inner()).await {
+        Ok(session_info) => session_info.into(),
+        Err(e) => e.into(),
+    }
+}
+
+#[post("/session/refresh")]
+async fn refresh_session(
+    app_state: Data<AppState>,
+    data: web::Json<SessionIdData>,
+) -> HttpResponse {
+    log::info!("Handling refresh session request");
+
+    let mut auth_client = app_state
+        .auth_client
+        .lock()
+        .expect("Auth client must be available in `refresh_session` handler")
+        .clone();
+
+    match auth::refresh_session(&mut auth_client, data.into_inner()).await {
+        Ok(session_info) => session_info.into(),
+        Err(e) => e.into(),
+    }
+}
+
+#[post("/session/revoke")]
+async fn revoke_session(app_state: Data<AppState>, data: web::Json<SessionIdData>) -> HttpResponse {
+    log::info!("Handling revoke session request");
+
+    let mut auth_client = app_state
+        .auth_client
+        .lock()
+        .expect("Auth client must be available in `revoke_session` handler")
+        .clone();
+
+    match auth::revoke_session(&mut auth_client, data.into_inner()).await {
+        Ok(()) => HttpResponse::NoContent().into(),
+        Err(e) => e.into(),
+    }
+}
+
+#[get("/token/keys")]
+async fn token_keys(app_state: Data<AppState>) -> HttpResponse {
+    log::info!("Handling token keys request");
+
+    let mut auth_client = app_state
+        .auth_client
+        .lock()
+        .expect("Auth client must be available in `token_keys` handler")
+        .clone();
+
+    match auth::token_keys(&mut auth_client).await {
+        Ok(key_set) => key_set.into(),
+        Err(e) => e.into(),
+    }
+}
+
 fn read_env_var(name: &str) -> String {
     env::var(name).unwrap_or_else(|_| panic!("Missing env variable: {:?}", name))
 }
@@ -89,21 +272,29 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     let auth_service_address: String = read_env_var(AUTH_SERVICE_URI_ENV)
         .parse()
-        .expect(format!("Invalid value set for {:?}", AUTH_SERVICE_URI_ENV).as_str());
+        .unwrap_or_else(|_| panic!("Invalid value set for {:?}", AUTH_SERVICE_URI_ENV));
 
     let http_port: u16 = read_env_var(HTTP_PORT_ENV)
         .parse()
-        .expect(format!("Invalid value set for {:?}", AUTH_SERVICE_URI_ENV).as_str());
+        .unwrap_or_else(|_| panic!("Invalid value set for {:?}", AUTH_SERVICE_URI_ENV));
 
     let zkp_g = read_env_var(ZKP_G_ENV)
         .parse()
-        .expect(format!("Invalid value set for {:?}", ZKP_G_ENV).as_str());
+        .unwrap_or_else(|_| panic!("Invalid value set for {:?}", ZKP_G_ENV));
     let zkp_h = read_env_var(ZKP_H_ENV)
         .parse()
-        .expect(format!("Invalid value set for {:?}", ZKP_H_ENV).as_str());
+        .unwrap_or_else(|_| panic!("Invalid value set for {:?}", ZKP_H_ENV));
+    let zkp_p = read_env_var(ZKP_P_ENV)
+        .parse()
+        .unwrap_or_else(|_| panic!("Invalid value set for {:?}", ZKP_P_ENV));
     let zkp_q = read_env_var(ZKP_Q_ENV)
         .parse()
-        .expect(format!("Invalid value set for {:?}", ZKP_Q_ENV).as_str());
+        .unwrap_or_else(|_| panic!("Invalid value set for {:?}", ZKP_Q_ENV));
+    let server_public_key: Vec<i64> = read_env_var(AUTH_SERVICE_PUBLIC_KEY_ENV)
+        .split(',')
+        .map(|y| y.trim().parse())
+        .collect::<Result<_, _>>()
+        .unwrap_or_else(|_| panic!("Invalid value set for {:?}", AUTH_SERVICE_PUBLIC_KEY_ENV));
 
     log::info!(
         target: LOG_TARGET,
@@ -115,13 +306,21 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         .await
         .expect("Failed to connect to auth_service");
 
+    let zkp_ctx =
+        chaum_pedersen::Context::new(zkp_g, zkp_h, zkp_p, zkp_q).expect("Invalid ZKP parameters");
+
     HttpServer::new(move || {
         // Create thread-local auth_client
         let app_state = AppState {
             auth_client: Mutex::new(auth_client.clone()),
-            zkp: chaum_pedersen::ChaumPedersenProtocol::new(chaum_pedersen::Context::new(
-                zkp_g, zkp_h, zkp_q,
-            )),
+            zkp: chaum_pedersen::ChaumPedersenProtocol::new(zkp_ctx.clone()),
+            okamoto: okamoto::OkamotoProtocol::new(zkp_ctx.clone()),
+            rotation: rotation::RotationProtocol::new(zkp_ctx.clone()),
+            key_exchange: ake::KeyExchange::new(zkp_ctx.clone()),
+            server: ServerIdentity {
+                server_auth: server_auth::ServerAuthProtocol::new(zkp_ctx.clone()),
+                public_key: server_public_key.clone(),
+            },
         };
         let json_config = web::JsonConfig::default()
             .limit(4096)
@@ -145,6 +344,17 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             .service(register)
             // register `login` handler
             .service(login)
+            // register `rotate` handler
+            .service(rotate)
+            // register Okamoto credential handlers
+            .service(okamoto_register_calculate)
+            .service(okamoto_register)
+            .service(okamoto_login)
+            // register session handlers
+            .service(validate_session)
+            .service(refresh_session)
+            .service(revoke_session)
+            .service(token_keys)
     })
     .bind(("0.0.0.0", http_port))
     .map_err(Box::new)?
@@ -1,5 +1,6 @@
 const chai = require("chai");
 const chaiHttp = require("chai-http");
+const crypto = require("crypto");
 
 chai.use(chaiHttp);
 const expect = chai.expect;
@@ -7,8 +8,25 @@ const expect = chai.expect;
 const SERVER_URL = "http://localhost:8080";
 const USERNAME = "bidzyyys";
 const SECRET = 9;
+const NEW_SECRET = 11;
 const CHALLENGE_K = 27;
 
+// Tags request number `counter` of the session with its key, see `nillion.proto`.
+function sessionRequest(sessionId, sessionKey, counter) {
+	const length = Buffer.alloc(8);
+	length.writeBigUInt64BE(BigInt(sessionId.length));
+	const counterBytes = Buffer.alloc(8);
+	counterBytes.writeBigUInt64BE(BigInt(counter));
+	const tag = crypto
+		.createHmac("sha256", Buffer.from(sessionKey, "hex"))
+		.update("zkp-auth/session-request")
+		.update(length)
+		.update(sessionId)
+		.update(counterBytes)
+		.digest("hex");
+	return { session_id: sessionId, counter, tag };
+}
+
 // eslint-disable-next-line no-undef
 describe("Happy Path", () => {
 	// eslint-disable-next-line no-undef
@@ -45,6 +63,18 @@ describe("Happy Path", () => {
 			});
 	});
 
+	// eslint-disable-next-line no-undef
+	it("Should reject registration outside of the subgroup", (done) => {
+		chai.request(SERVER_URL)
+			.post("/register")
+			.send({ username: USERNAME, y1: y1, y2: 0 })
+			.end((err, res) => {
+				expect(res).to.have.status(400);
+				// eslint-disable-next-line no-unused-vars
+				done();
+			});
+	});
+
 	// eslint-disable-next-line no-undef
 	it("Should register user", (done) => {
 		chai.request(SERVER_URL)
@@ -70,6 +100,9 @@ describe("Happy Path", () => {
 			});
 	});
 
+	let sessionId = null;
+	let sessionKey = null;
+
 	// eslint-disable-next-line no-undef
 	it("Should accept valid login attempt", (done) => {
 		chai.request(SERVER_URL)
@@ -81,7 +114,250 @@ describe("Happy Path", () => {
 				expect(res).to.be.json;
 				expect(res.body)
 					.to.have.property("session_id")
-					.equal("test session");
+					.match(/^[0-9a-f]{64}$/);
+				expect(res.body)
+					.to.have.property("session_key")
+					.match(/^[0-9a-f]{64}$/);
+				sessionId = res.body.session_id;
+				sessionKey = res.body.session_key;
+				//eslint-disable-next-line no-unused-vars
+				done();
+			});
+	});
+
+	// eslint-disable-next-line no-undef
+	it("Should reject session request without tag", (done) => {
+		chai.request(SERVER_URL)
+			.post("/session/validate")
+			.send({ session_id: sessionId })
+			.end((err, res) => {
+				expect(res).to.have.status(401);
+				// eslint-disable-next-line no-unused-vars
+				done();
+			});
+	});
+
+	// eslint-disable-next-line no-undef
+	it("Should validate session", (done) => {
+		chai.request(SERVER_URL)
+			.post("/session/validate")
+			.send(sessionRequest(sessionId, sessionKey, 1))
+			.end((err, res) => {
+				expect(err).to.be.null;
+				expect(res).to.have.status(200);
+				expect(res.body).to.have.property("user", USERNAME);
+				expect(res.body).to.have.property("expires_at");
+				// eslint-disable-next-line no-unused-vars
+				done();
+			});
+	});
+
+	// eslint-disable-next-line no-undef
+	it("Should reject replayed session request", (done) => {
+		chai.request(SERVER_URL)
+			.post("/session/refresh")
+			.send(sessionRequest(sessionId, sessionKey, 1))
+			.end((err, res) => {
+				expect(res).to.have.status(401);
+				// eslint-disable-next-line no-unused-vars
+				done();
+			});
+	});
+
+	// eslint-disable-next-line no-undef
+	it("Should refresh session", (done) => {
+		chai.request(SERVER_URL)
+			.post("/session/refresh")
+			.send(sessionRequest(sessionId, sessionKey, 2))
+			.end((err, res) => {
+				expect(err).to.be.null;
+				expect(res).to.have.status(200);
+				expect(res.body).to.have.property("session_id", sessionId);
+				// eslint-disable-next-line no-unused-vars
+				done();
+			});
+	});
+
+	// eslint-disable-next-line no-undef
+	it("Should revoke session", (done) => {
+		chai.request(SERVER_URL)
+			.post("/session/revoke")
+			.send(sessionRequest(sessionId, sessionKey, 3))
+			.end((err, res) => {
+				expect(err).to.be.null;
+				expect(res).to.have.status(204);
+				// eslint-disable-next-line no-unused-vars
+				done();
+			});
+	});
+
+	// eslint-disable-next-line no-undef
+	it("Should reject revoked session", (done) => {
+		chai.request(SERVER_URL)
+			.post("/session/validate")
+			.send(sessionRequest(sessionId, sessionKey, 4))
+			.end((err, res) => {
+				expect(res).to.have.status(401);
+				// eslint-disable-next-line no-unused-vars
+				done();
+			});
+	});
+
+	// eslint-disable-next-line no-undef
+	it("Should reject credentials rotation without current secret", (done) => {
+		chai.request(SERVER_URL)
+			.post("/rotate")
+			.send({
+				user: USERNAME,
+				x: SECRET + 1,
+				k: CHALLENGE_K,
+				new_x: NEW_SECRET,
+				new_k: CHALLENGE_K,
+			})
+			.end((err, res) => {
+				expect(res).to.have.status(403);
+				// eslint-disable-next-line no-unused-vars
+				done();
+			});
+	});
+
+	// eslint-disable-next-line no-undef
+	it("Should rotate credentials", (done) => {
+		chai.request(SERVER_URL)
+			.post("/rotate")
+			.send({
+				user: USERNAME,
+				x: SECRET,
+				k: CHALLENGE_K,
+				new_x: NEW_SECRET,
+				new_k: CHALLENGE_K,
+			})
+			.end((err, res) => {
+				expect(err).to.be.null;
+				expect(res).to.have.status(200);
+				expect(res).to.be.json;
+				expect(res.body).to.have.property("y1");
+				expect(res.body).to.have.property("y2");
+				expect(res.body.key_version).to.equal(1);
+				// eslint-disable-next-line no-unused-vars
+				done();
+			});
+	});
+
+	// eslint-disable-next-line no-undef
+	it("Should reject rotation proof for a previous key version", (done) => {
+		chai.request(SERVER_URL)
+			.post("/rotate")
+			.send({
+				user: USERNAME,
+				x: NEW_SECRET,
+				k: CHALLENGE_K,
+				new_x: SECRET,
+				new_k: CHALLENGE_K,
+				key_version: 0,
+			})
+			.end((err, res) => {
+				expect(res).to.have.status(409);
+				// eslint-disable-next-line no-unused-vars
+				done();
+			});
+	});
+
+	// eslint-disable-next-line no-undef
+	it("Should reject login with rotated secret", (done) => {
+		chai.request(SERVER_URL)
+			.post("/login")
+			.send({ user: USERNAME, x: SECRET, k: CHALLENGE_K })
+			.end((err, res) => {
+				expect(res).to.have.status(403);
+				// eslint-disable-next-line no-unused-vars
+				done();
+			});
+	});
+
+	// eslint-disable-next-line no-undef
+	it("Should accept login with new secret", (done) => {
+		chai.request(SERVER_URL)
+			.post("/login")
+			.send({ user: USERNAME, x: NEW_SECRET, k: CHALLENGE_K })
+			.end((err, res) => {
+				expect(err).to.be.null;
+				expect(res).to.have.status(200);
+				// eslint-disable-next-line no-unused-vars
+				done();
+			});
+	});
+});
+
+// eslint-disable-next-line no-undef
+describe("Okamoto Happy Path", () => {
+	const OKAMOTO_USERNAME = "okamoto";
+	const SECRET_A = 3;
+	const SECRET_B = 2;
+	const CHALLENGE_K1 = 10;
+	const CHALLENGE_K2 = 5;
+
+	let y = null;
+
+	// eslint-disable-next-line no-undef
+	it("Should calculate Okamoto registration data", (done) => {
+		chai.request(SERVER_URL)
+			.post("/okamoto/register/calculate")
+			.send({ user: OKAMOTO_USERNAME, a: SECRET_A, b: SECRET_B })
+			.end((err, res) => {
+				expect(err).to.be.null;
+				expect(res).to.be.json;
+				expect(res).to.have.status(201);
+				expect(res.body).to.have.property("y");
+				y = res.body.y;
+
+				// eslint-disable-next-line no-unused-vars
+				done();
+			});
+	});
+
+	// eslint-disable-next-line no-undef
+	it("Should register Okamoto user", (done) => {
+		chai.request(SERVER_URL)
+			.post("/okamoto/register")
+			.send({ username: OKAMOTO_USERNAME, y: y })
+			.end((err, res) => {
+				expect(err).to.be.null;
+				expect(res).to.have.status(201);
+				// eslint-disable-next-line no-unused-vars
+				done();
+			});
+	});
+
+	// eslint-disable-next-line no-undef
+	it("Should reject Chaum-Pedersen login for Okamoto user", (done) => {
+		chai.request(SERVER_URL)
+			.post("/login")
+			.send({ user: OKAMOTO_USERNAME, x: SECRET_A, k: CHALLENGE_K1 })
+			.end((err, res) => {
+				expect(res).to.have.status(400);
+				// eslint-disable-next-line no-unused-vars
+				done();
+			});
+	});
+
+	// eslint-disable-next-line no-undef
+	it("Should accept valid Okamoto login attempt", (done) => {
+		chai.request(SERVER_URL)
+			.post("/okamoto/login")
+			.send({
+				user: OKAMOTO_USERNAME,
+				a: SECRET_A,
+				b: SECRET_B,
+				k1: CHALLENGE_K1,
+				k2: CHALLENGE_K2,
+			})
+			.end((err, res) => {
+				expect(err).to.be.null;
+				expect(res).to.have.status(200);
+				expect(res).to.be.json;
+				expect(res.body).to.have.property("session_id");
+				expect(res.body).to.have.property("session_key");
 				//eslint-disable-next-line no-unused-vars
 				done();
 			});
@@ -0,0 +1,357 @@
+use core::fmt;
+
+use hmac::{Hmac, Mac};
+use rand_core::{CryptoRng, RngCore};
+use sha2::Sha256;
+
+use crate::{chaum_pedersen::Context, transcript::Transcript, ZKPError};
+
+const KEY_LEN: usize = 32;
+const SESSION_REQUEST_LABEL: &[u8] = b"zkp-auth/session-request";
+
+/// Ephemeral Diffie-Hellman secret `e` of one login together with its share `g^e`.
+///
+/// It is consumed by the key agreement, so it can never be reused for a second session.
+pub struct EphemeralSecret {
+    e: i64,
+    share: i64,
+}
+
+/// Symmetric key shared by the client and the server after a successful login.
+#[derive(Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SessionKey([u8; KEY_LEN]);
+
+/// Outcome of the key exchange of one login.
+///
+/// `c` is the challenge the prover answers instead of the challenge issued by the verifier. It
+/// binds both key shares, so a login whose shares were replaced in transit fails verification.
+pub struct KeyAgreement {
+    pub c: i64,
+    pub session_id: [u8; 32],
+    pub session_key: SessionKey,
+}
+
+/// Ephemeral Diffie-Hellman key exchange bound to a Chaum-Pedersen or Okamoto login.
+///
+/// The client sends `A = g^a` together with its commitments and the server answers with
+/// `B = g^b` together with the challenge `c`. Both sides then derive the bound challenge, the
+/// session id and the session key from `c`, `A`, `B` and `g^ab`.
+pub struct KeyExchange {
+    ctx: Context,
+}
+
+/// Starts the transcript binding a key exchange to the logged in account.
+pub fn key_exchange_transcript(username: &str) -> Transcript {
+    let mut transcript = Transcript::new(b"zkp-auth/key-exchange");
+    transcript.append_message(b"username", username.as_bytes());
+    transcript
+}
+
+type HmacSha256 = Hmac<Sha256>;
+
+impl EphemeralSecret {
+    pub fn share(&self) -> i64 {
+        self.share
+    }
+}
+
+impl SessionKey {
+    pub fn as_bytes(&self) -> &[u8; KEY_LEN] {
+        &self.0
+    }
+
+    fn hmac(&self, message: &[u8]) -> HmacSha256 {
+        let mut mac = HmacSha256::new_from_slice(&self.0).expect("HMAC accepts any key length");
+        mac.update(message);
+        mac
+    }
+
+    /// Calculates the HMAC-SHA256 tag of a request made within the session.
+    pub fn mac(&self, message: &[u8]) -> [u8; 32] {
+        self.hmac(message).finalize().into_bytes().into()
+    }
+
+    /// Checks the tag of `message` in constant time.
+    pub fn verify_mac(&self, message: &[u8], tag: &[u8]) -> Result<(), ZKPError> {
+        self.hmac(message)
+            .verify_slice(tag)
+            .map_err(|_| ZKPError::VerificationFailed)
+    }
+
+    /// Calculates the tag of request number `counter` made within the session `session_id`.
+    ///
+    /// The tag is the HMAC-SHA256 of `zkp-auth/session-request`, the length of the session id as
+    /// a big-endian `u64`, the session id and the counter as a big-endian `u64`. The server
+    /// accepts every counter once and only in increasing order, so tags cannot be replayed.
+    pub fn request_tag(&self, session_id: &str, counter: u64) -> [u8; 32] {
+        self.request_hmac(session_id, counter)
+            .finalize()
+            .into_bytes()
+            .into()
+    }
+
+    /// Checks the tag of a request made within the session in constant time.
+    pub fn verify_request_tag(
+        &self,
+        session_id: &str,
+        counter: u64,
+        tag: &[u8],
+    ) -> Result<(), ZKPError> {
+        self.request_hmac(session_id, counter)
+            .verify_slice(tag)
+            .map_err(|_| ZKPError::VerificationFailed)
+    }
+
+    fn request_hmac(&self, session_id: &str, counter: u64) -> HmacSha256 {
+        let mut mac = self.hmac(SESSION_REQUEST_LABEL);
+        mac.update(&(session_id.len() as u64).to_be_bytes());
+        mac.update(session_id.as_bytes());
+        mac.update(&counter.to_be_bytes());
+        mac
+    }
+}
+
+impl From<[u8; KEY_LEN]> for SessionKey {
+    fn from(bytes: [u8; KEY_LEN]) -> Self {
+        Self(bytes)
+    }
+}
+
+impl fmt::Debug for SessionKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "SessionKey(..)")
+    }
+}
+
+impl KeyExchange {
+    pub fn new(ctx: Context) -> Self {
+        Self { ctx }
+    }
+
+    pub fn generate<R: RngCore + CryptoRng>(&self, rng: &mut R) -> EphemeralSecret {
+        let e = self.ctx.random_scalar(rng);
+        EphemeralSecret {
+            e,
+            share: self.ctx.pow(self.ctx.bases()[0], e),
+        }
+    }
+
+    pub fn client_agree(
+        &self,
+        transcript: &mut Transcript,
+        secret: EphemeralSecret,
+        server_share: i64,
+        c: i64,
+    ) -> Result<KeyAgreement, ZKPError> {
+        let client_share = secret.share;
+        self.agree(
+            transcript,
+            secret,
+            server_share,
+            (client_share, server_share),
+            c,
+        )
+    }
+
+    pub fn server_agree(
+        &self,
+        transcript: &mut Transcript,
+        secret: EphemeralSecret,
+        client_share: i64,
+        c: i64,
+    ) -> Result<KeyAgreement, ZKPError> {
+        let server_share = secret.share;
+        self.agree(
+            transcript,
+            secret,
+            client_share,
+            (client_share, server_share),
+            c,
+        )
+    }
+
+    fn agree(
+        &self,
+        transcript: &mut Transcript,
+        secret: EphemeralSecret,
+        peer_share: i64,
+        (client_share, server_share): (i64, i64),
+        c: i64,
+    ) -> Result<KeyAgreement, ZKPError> {
+        self.ctx.check_element(peer_share)?;
+        if peer_share == 1 {
+            return Err(ZKPError::InvalidKeyShare);
+        }
+        self.ctx.check_scalar(c)?;
+
+        self.ctx.append_to_transcript(transcript);
+        transcript.append_i64(b"c", c);
+        transcript.append_i64(b"client-share", client_share);
+        transcript.append_i64(b"server-share", server_share);
+        let c = transcript.challenge_scalar(b"bound-c", self.ctx.q());
+
+        transcript.append_i64(b"shared-secret", self.ctx.pow(peer_share, secret.e));
+        Ok(KeyAgreement {
+            c,
+            session_id: transcript.challenge_bytes(b"session-id"),
+            session_key: SessionKey(transcript.challenge_bytes(b"session-key")),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chaum_pedersen::ChaumPedersenProtocol;
+    use rand_chacha::ChaCha20Rng;
+    use rand_core::SeedableRng;
+
+    const ZKP_G: i64 = 4;
+    const ZKP_H: i64 = 9;
+    const ZKP_P: i64 = 20123;
+    const ZKP_Q: i64 = 10061;
+    const SECRET: i64 = 9;
+    const NONCE_K: i64 = 1234;
+    const CHALLENGE_C: i64 = 4321;
+    const USERNAME: &str = "alice";
+
+    fn init() -> (KeyExchange, ChaumPedersenProtocol) {
+        let ctx = Context::new(ZKP_G, ZKP_H, ZKP_P, ZKP_Q).unwrap();
+        (
+            KeyExchange::new(ctx.clone()),
+            ChaumPedersenProtocol::new(ctx),
+        )
+    }
+
+    #[test]
+    fn should_derive_same_session_on_both_sides() {
+        let (ake, zkp) = init();
+        let mut rng = ChaCha20Rng::seed_from_u64(42);
+        let (client_secret, server_secret) = (ake.generate(&mut rng), ake.generate(&mut rng));
+        let (client_share, server_share) = (client_secret.share(), server_secret.share());
+
+        let server = ake
+            .server_agree(
+                &mut key_exchange_transcript(USERNAME),
+                server_secret,
+                client_share,
+                CHALLENGE_C,
+            )
+            .unwrap();
+        let client = ake
+            .client_agree(
+                &mut key_exchange_transcript(USERNAME),
+                client_secret,
+                server_share,
+                CHALLENGE_C,
+            )
+            .unwrap();
+        assert_eq!(client.c, server.c);
+        assert_eq!(client.session_id, server.session_id);
+        assert_eq!(client.session_key, server.session_key);
+        assert_ne!(client.session_id, *client.session_key.as_bytes());
+
+        let (y1, y2) = zkp.calculate_registration_data(SECRET).unwrap();
+        let (r1, r2) = zkp.calculate_login_challenge_data(NONCE_K).unwrap();
+        let s = zkp.calculate_challenge(NONCE_K, client.c, SECRET).unwrap();
+        assert_eq!(
+            zkp.verify_auth_challenge(y1, y2, r1, r2, server.c, s),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn should_reject_login_with_replaced_key_share() {
+        let (ake, _zkp) = init();
+        let mut rng = ChaCha20Rng::seed_from_u64(42);
+        let (client_secret, server_secret) = (ake.generate(&mut rng), ake.generate(&mut rng));
+        let attacker_secret = ake.generate(&mut rng);
+        let server_share = server_secret.share();
+
+        // The server only sees the share of the attacker.
+        let server = ake
+            .server_agree(
+                &mut key_exchange_transcript(USERNAME),
+                server_secret,
+                attacker_secret.share(),
+                CHALLENGE_C,
+            )
+            .unwrap();
+        let client = ake
+            .client_agree(
+                &mut key_exchange_transcript(USERNAME),
+                client_secret,
+                server_share,
+                CHALLENGE_C,
+            )
+            .unwrap();
+        assert_ne!(client.c, server.c);
+        assert_ne!(client.session_key, server.session_key);
+    }
+
+    #[test]
+    fn should_reject_invalid_key_shares() {
+        let (ake, _zkp) = init();
+        let mut rng = ChaCha20Rng::seed_from_u64(42);
+        for (share, error) in [
+            (1, ZKPError::InvalidKeyShare),
+            (2, ZKPError::ElementNotInSubgroup { element: 2 }),
+        ] {
+            assert_eq!(
+                ake.server_agree(
+                    &mut key_exchange_transcript(USERNAME),
+                    ake.generate(&mut rng),
+                    share,
+                    CHALLENGE_C,
+                )
+                .err(),
+                Some(error)
+            );
+        }
+    }
+
+    #[test]
+    fn should_authenticate_requests_with_session_key() {
+        let (ake, _zkp) = init();
+        let mut rng = ChaCha20Rng::seed_from_u64(42);
+        let server_secret = ake.generate(&mut rng);
+        let client_share = ake.generate(&mut rng).share();
+        let key = ake
+            .server_agree(
+                &mut key_exchange_transcript(USERNAME),
+                server_secret,
+                client_share,
+                CHALLENGE_C,
+            )
+            .unwrap()
+            .session_key;
+
+        let tag = key.mac(b"GET /profile");
+        assert_eq!(key.verify_mac(b"GET /profile", &tag), Ok(()));
+        assert_eq!(
+            key.verify_mac(b"GET /admin", &tag),
+            Err(ZKPError::VerificationFailed)
+        );
+    }
+
+    #[test]
+    fn should_bind_request_tag_to_session_and_counter() {
+        let key = SessionKey::from([7; KEY_LEN]);
+
+        let tag = key.request_tag("session", 1);
+        assert_eq!(key.verify_request_tag("session", 1, &tag), Ok(()));
+        assert_eq!(
+            key.verify_request_tag("session", 2, &tag),
+            Err(ZKPError::VerificationFailed)
+        );
+        assert_eq!(
+            key.verify_request_tag("other", 1, &tag),
+            Err(ZKPError::VerificationFailed)
+        );
+        assert_eq!(
+            SessionKey::from([8; KEY_LEN]).verify_request_tag("session", 1, &tag),
+            Err(ZKPError::VerificationFailed)
+        );
+    }
+}
@@ -0,0 +1,289 @@
+use alloc::vec::Vec;
+use core::{fmt, str::FromStr};
+
+use rand_core::{CryptoRng, RngCore};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    chaum_pedersen::{ChaumPedersenProtocol, Context},
+    threshold::{Share, ThresholdProtocol},
+    ZKPError,
+};
+
+const VERSION: u8 = 1;
+const PAYLOAD_LEN: usize = 11;
+const CHECKSUM_LEN: usize = 4;
+const ENCODED_LEN: usize = PAYLOAD_LEN + CHECKSUM_LEN;
+const GROUP_LEN: usize = 5;
+const CHECKSUM_DOMAIN: &[u8] = b"zkp-backup-share";
+
+/// Printable backup share of the secret `x`.
+///
+/// It is written as dash separated groups of five hex digits encoding the version, threshold,
+/// share index, share value and a 4-byte checksum catching typos when the share is typed back.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BackupShare {
+    pub threshold: u8,
+    pub index: u8,
+    pub value: i64,
+}
+
+/// Splits the secret `x` into backup shares and recovers it, see [`ThresholdProtocol`].
+pub struct BackupProtocol {
+    threshold: ThresholdProtocol,
+    zkp: ChaumPedersenProtocol,
+}
+
+fn checksum(payload: &[u8]) -> [u8; CHECKSUM_LEN] {
+    let digest = Sha256::new()
+        .chain_update(CHECKSUM_DOMAIN)
+        .chain_update(payload)
+        .finalize();
+    digest[..CHECKSUM_LEN]
+        .try_into()
+        .expect("digest is longer than the checksum")
+}
+
+fn invalid(reason: &'static str) -> ZKPError {
+    ZKPError::InvalidBackupShare { reason }
+}
+
+impl BackupShare {
+    fn to_bytes(&self) -> [u8; ENCODED_LEN] {
+        let mut bytes = [0u8; ENCODED_LEN];
+        bytes[0] = VERSION;
+        bytes[1] = self.threshold;
+        bytes[2] = self.index;
+        bytes[3..PAYLOAD_LEN].copy_from_slice(&self.value.to_be_bytes());
+        let checksum = checksum(&bytes[..PAYLOAD_LEN]);
+        bytes[PAYLOAD_LEN..].copy_from_slice(&checksum);
+        bytes
+    }
+
+    fn from_bytes(bytes: &[u8; ENCODED_LEN]) -> Result<Self, ZKPError> {
+        let (payload, expected) = bytes.split_at(PAYLOAD_LEN);
+        if checksum(payload) != expected {
+            return Err(invalid("checksum mismatch"));
+        }
+        if payload[0] != VERSION {
+            return Err(invalid("unsupported version"));
+        }
+
+        Ok(Self {
+            threshold: payload[1],
+            index: payload[2],
+            value: i64::from_be_bytes(payload[3..].try_into().expect("8 bytes of value")),
+        })
+    }
+}
+
+impl fmt::Display for BackupShare {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let hex: Vec<u8> = self
+            .to_bytes()
+            .iter()
+            .flat_map(|byte| [byte >> 4, byte & 0xf])
+            .collect();
+        for (position, group) in hex.chunks(GROUP_LEN).enumerate() {
+            if position > 0 {
+                write!(f, "-")?;
+            }
+            for digit in group {
+                write!(f, "{digit:X}")?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl FromStr for BackupShare {
+    type Err = ZKPError;
+
+    /// Parses a share, ignoring case, whitespace and dashes.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let digits = s
+            .chars()
+            .filter(|c| *c != '-' && !c.is_whitespace())
+            .map(|c| c.to_digit(16).ok_or_else(|| invalid("not a hex digit")))
+            .collect::<Result<Vec<u32>, ZKPError>>()?;
+        if digits.len() != 2 * ENCODED_LEN {
+            return Err(ZKPError::MalformedEncoding {
+                expected: 2 * ENCODED_LEN,
+                actual: digits.len(),
+            });
+        }
+
+        let mut bytes = [0u8; ENCODED_LEN];
+        for (byte, pair) in bytes.iter_mut().zip(digits.chunks_exact(2)) {
+            *byte = (pair[0] << 4 | pair[1]) as u8;
+        }
+        Self::from_bytes(&bytes)
+    }
+}
+
+impl BackupProtocol {
+    pub fn new(ctx: Context) -> Self {
+        Self {
+            threshold: ThresholdProtocol::new(ctx.clone()),
+            zkp: ChaumPedersenProtocol::new(ctx),
+        }
+    }
+
+    /// Splits `x` into `shares` printable shares, any `threshold` of which recover it.
+    pub fn create_shares<R: RngCore + CryptoRng>(
+        &self,
+        rng: &mut R,
+        x: i64,
+        threshold: u8,
+        shares: u8,
+    ) -> Result<Vec<BackupShare>, ZKPError> {
+        Ok(self
+            .threshold
+            .split_secret(rng, x, threshold.into(), shares.into())?
+            .into_iter()
+            .map(|Share { index, value }| BackupShare {
+                threshold,
+                index: index as u8,
+                value,
+            })
+            .collect())
+    }
+
+    /// Recovers `x` and accepts it only if it matches the registered public keys `ys`.
+    pub fn recover_secret(&self, shares: &[BackupShare], ys: &[i64]) -> Result<i64, ZKPError> {
+        let threshold = match shares.first() {
+            None => return Err(invalid("no shares")),
+            Some(share) => share.threshold,
+        };
+        if shares.iter().any(|share| share.threshold != threshold) {
+            return Err(invalid("shares belong to different backups"));
+        }
+        if shares.len() < threshold.into() {
+            return Err(invalid("not enough shares"));
+        }
+
+        let shares: Vec<Share> = shares
+            .iter()
+            .map(|share| Share {
+                index: share.index.into(),
+                value: share.value,
+            })
+            .collect();
+        let x = self.threshold.recover_secret(&shares, threshold.into())?;
+
+        if self.zkp.calculate_multi_registration_data(x)? != ys {
+            return Err(ZKPError::VerificationFailed);
+        }
+
+        Ok(x)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::ToString;
+    use rand_chacha::ChaCha20Rng;
+    use rand_core::SeedableRng;
+
+    const ZKP_G: i64 = 4;
+    const ZKP_H: i64 = 9;
+    const ZKP_P: i64 = 20123;
+    const ZKP_Q: i64 = 10061;
+    const SECRET: i64 = 9;
+
+    fn init() -> (BackupProtocol, Vec<i64>) {
+        let ctx = Context::new(ZKP_G, ZKP_H, ZKP_P, ZKP_Q).unwrap();
+        let ys = ChaumPedersenProtocol::new(ctx.clone())
+            .calculate_multi_registration_data(SECRET)
+            .unwrap();
+        (BackupProtocol::new(ctx), ys)
+    }
+
+    fn shares(backup: &BackupProtocol) -> Vec<BackupShare> {
+        backup
+            .create_shares(&mut ChaCha20Rng::seed_from_u64(42), SECRET, 2, 3)
+            .unwrap()
+    }
+
+    #[test]
+    fn should_round_trip_printable_shares() {
+        let (backup, _ys) = init();
+        for share in shares(&backup) {
+            let printed = share.to_string();
+            assert_eq!(printed.len(), 2 * ENCODED_LEN + 5);
+            assert_eq!(printed.parse(), Ok(share.clone()));
+            assert_eq!(printed.to_lowercase().replace('-', " ").parse(), Ok(share));
+        }
+    }
+
+    #[test]
+    fn should_detect_typos() {
+        let (backup, _ys) = init();
+        let printed = shares(&backup)[0].to_string();
+        let typo = if printed.starts_with("00") {
+            "01"
+        } else {
+            "00"
+        };
+        let mistyped = typo.to_string() + &printed[2..];
+        assert_eq!(
+            mistyped.parse::<BackupShare>(),
+            Err(ZKPError::InvalidBackupShare {
+                reason: "checksum mismatch"
+            })
+        );
+        assert!(matches!(
+            printed[1..].parse::<BackupShare>(),
+            Err(ZKPError::MalformedEncoding { .. })
+        ));
+        assert!(matches!(
+            printed.replacen('0', "O", 1).parse::<BackupShare>(),
+            Err(ZKPError::InvalidBackupShare { .. })
+        ));
+    }
+
+    #[test]
+    fn should_recover_secret_from_threshold_of_shares() {
+        let (backup, ys) = init();
+        let shares = shares(&backup);
+        assert_eq!(backup.recover_secret(&shares[1..], &ys), Ok(SECRET));
+        assert_eq!(
+            backup.recover_secret(&[shares[0].clone(), shares[2].clone()], &ys),
+            Ok(SECRET)
+        );
+    }
+
+    #[test]
+    fn should_reject_recovery_not_matching_public_keys() {
+        let (backup, _ys) = init();
+        let other_ys =
+            ChaumPedersenProtocol::new(Context::new(ZKP_G, ZKP_H, ZKP_P, ZKP_Q).unwrap())
+                .calculate_multi_registration_data(SECRET + 1)
+                .unwrap();
+        assert_eq!(
+            backup.recover_secret(&shares(&backup)[1..], &other_ys),
+            Err(ZKPError::VerificationFailed)
+        );
+    }
+
+    #[test]
+    fn should_reject_insufficient_or_mixed_shares() {
+        let (backup, ys) = init();
+        let shares = shares(&backup);
+        assert_eq!(
+            backup.recover_secret(&shares[..1], &ys),
+            Err(ZKPError::InvalidBackupShare {
+                reason: "not enough shares"
+            })
+        );
+        let mut mixed = shares[..2].to_vec();
+        mixed[1].threshold = 3;
+        assert!(matches!(
+            backup.recover_secret(&mixed, &ys),
+            Err(ZKPError::InvalidBackupShare { .. })
+        ));
+    }
+}
@@ -1,18 +1,209 @@
-use crate::ZKPError;
+use alloc::{format, string::String, vec, vec::Vec};
+
+use rand_core::{CryptoRng, RngCore};
+
+use crate::{
+    math::{mod_mul, mod_pow, mod_sub},
+    transcript::Transcript,
+    ZKPError,
+};
 
 pub struct ChaumPedersenProtocol {
     ctx: Context,
 }
 
+/// Group of prime order `q` modulo `p` together with the bases `g_1..g_n` the secret is shared
+/// across; [`Context::new`] is the classic `(g, h)` case.
+#[derive(Clone)]
 pub struct Context {
-    g: i64,
-    h: i64,
+    bases: Vec<i64>,
+    p: i64,
     q: i64,
 }
 
 impl Context {
-    pub fn new(g: i64, h: i64, q: i64) -> Self {
-        Self { g, h, q }
+    pub fn new(g: i64, h: i64, p: i64, q: i64) -> Result<Self, ZKPError> {
+        Self::with_bases(vec![g, h], p, q)
+    }
+
+    pub fn with_bases(bases: Vec<i64>, p: i64, q: i64) -> Result<Self, ZKPError> {
+        if bases.is_empty() {
+            return Err(ZKPError::EmptyBases);
+        }
+        if p < 2 || q < 2 {
+            return Err(ZKPError::InvalidParameters {
+                reason: "p and q must be at least 2",
+            });
+        }
+        if (p - 1) % q != 0 {
+            return Err(ZKPError::InvalidParameters {
+                reason: "q must divide p - 1",
+            });
+        }
+
+        let ctx = Self { bases, p, q };
+        if ctx
+            .bases
+            .iter()
+            .any(|base| *base == 1 || ctx.check_element(*base).is_err())
+        {
+            return Err(ZKPError::InvalidParameters {
+                reason: "bases must generate the order q subgroup",
+            });
+        }
+
+        Ok(ctx)
+    }
+
+    pub fn bases(&self) -> &[i64] {
+        &self.bases
+    }
+
+    pub fn p(&self) -> i64 {
+        self.p
+    }
+
+    pub fn q(&self) -> i64 {
+        self.q
+    }
+
+    /// Draws a uniformly random scalar from `[0, q)`, e.g. a secret `x` or a nonce `k`.
+    pub fn random_scalar<R: RngCore + CryptoRng>(&self, rng: &mut R) -> i64 {
+        loop {
+            let candidate = (rng.next_u64() & self.scalar_mask()) as i64;
+            if candidate < self.q {
+                return candidate;
+            }
+        }
+    }
+
+    /// Like [`Context::random_scalar`], but reports a failing generator instead of panicking.
+    pub fn try_random_scalar<R: RngCore + CryptoRng>(&self, rng: &mut R) -> Result<i64, ZKPError> {
+        loop {
+            let mut bytes = [0u8; 8];
+            rng.try_fill_bytes(&mut bytes)
+                .map_err(|_| ZKPError::RngFailure)?;
+            let candidate = (u64::from_le_bytes(bytes) & self.scalar_mask()) as i64;
+            if candidate < self.q {
+                return Ok(candidate);
+            }
+        }
+    }
+
+    /// Rejection sampling over the bit length of `q` keeps random scalars unbiased.
+    fn scalar_mask(&self) -> u64 {
+        u64::MAX >> (self.q - 1).leading_zeros()
+    }
+
+    /// Checks that `element` belongs to the order `q` subgroup modulo `p`.
+    pub fn check_element(&self, element: i64) -> Result<(), ZKPError> {
+        if element < 1 || element >= self.p || mod_pow(element, self.q, self.p) != 1 {
+            return Err(ZKPError::ElementNotInSubgroup { element });
+        }
+
+        Ok(())
+    }
+
+    /// Checks that `scalar` lies in `[0, q)`.
+    pub fn check_scalar(&self, scalar: i64) -> Result<(), ZKPError> {
+        if !(0..self.q).contains(&scalar) {
+            return Err(ZKPError::ScalarOutOfRange { scalar, q: self.q });
+        }
+
+        Ok(())
+    }
+
+    /// Checks that there is one subgroup element per base.
+    pub(crate) fn check_elements(&self, elements: &[i64]) -> Result<(), ZKPError> {
+        self.check_len(elements.len())?;
+        elements
+            .iter()
+            .try_for_each(|element| self.check_element(*element))
+    }
+
+    pub(crate) fn check_len(&self, len: usize) -> Result<(), ZKPError> {
+        if len != self.bases.len() {
+            return Err(ZKPError::LengthMismatch {
+                expected: self.bases.len(),
+                actual: len,
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Short hex id of the group parameters and bases, e.g. to tell which group a keystore or a
+    /// token belongs to.
+    pub fn id(&self) -> String {
+        let mut transcript = Transcript::new(b"zkp/context-id");
+        self.append_to_transcript(&mut transcript);
+        let id = transcript.challenge_scalar(b"id", i64::MAX);
+        format!("{id:016x}")
+    }
+
+    pub fn append_to_transcript(&self, transcript: &mut Transcript) {
+        transcript.append_i64(b"p", self.p);
+        transcript.append_i64(b"q", self.q);
+        transcript.append_i64s(b"bases", &self.bases);
+    }
+
+    pub(crate) fn pow(&self, base: i64, exp: i64) -> i64 {
+        mod_pow(base, exp.rem_euclid(self.q), self.p)
+    }
+
+    pub(crate) fn mul(&self, l: i64, r: i64) -> i64 {
+        mod_mul(l, r, self.p)
+    }
+
+    /// Calculates `prod(g_i^exps_i)`.
+    #[cfg_attr(not(any(feature = "okamoto", feature = "pedersen")), allow(dead_code))]
+    pub(crate) fn multi_pow(&self, exps: &[i64]) -> Result<i64, ZKPError> {
+        self.check_len(exps.len())?;
+
+        Ok(self
+            .bases
+            .iter()
+            .zip(exps)
+            .fold(1, |acc, (base, exp)| self.mul(acc, self.pow(*base, *exp))))
+    }
+
+    /// Calculates the response `k - c * x mod q`.
+    pub(crate) fn response(&self, k: i64, c: i64, x: i64) -> i64 {
+        mod_sub(k, mod_mul(c, x, self.q), self.q)
+    }
+}
+
+/// Non-interactive proof that the same `x` is the discrete logarithm of every `y_i`.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Proof {
+    pub c: i64,
+    pub s: i64,
+}
+
+impl Proof {
+    pub const ENCODED_LEN: usize = 16;
+
+    /// Encodes the proof as `c || s`, both as 8-byte big-endian integers.
+    pub fn to_bytes(&self) -> [u8; Self::ENCODED_LEN] {
+        let mut bytes = [0u8; Self::ENCODED_LEN];
+        bytes[..8].copy_from_slice(&self.c.to_be_bytes());
+        bytes[8..].copy_from_slice(&self.s.to_be_bytes());
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, ZKPError> {
+        let bytes: [u8; Self::ENCODED_LEN] =
+            bytes.try_into().map_err(|_| ZKPError::MalformedEncoding {
+                expected: Self::ENCODED_LEN,
+                actual: bytes.len(),
+            })?;
+        let (c, s) = bytes.split_at(8);
+
+        Ok(Self {
+            c: i64::from_be_bytes(c.try_into().expect("8 bytes")),
+            s: i64::from_be_bytes(s.try_into().expect("8 bytes")),
+        })
     }
 }
 
@@ -22,39 +213,88 @@ impl ChaumPedersenProtocol {
     }
 
     pub fn calculate_challenge(&self, k: i64, c: i64, x: i64) -> Result<i64, ZKPError> {
-        Ok(
-            (ChaumPedersenProtocol::safe_sub(k, ChaumPedersenProtocol::safe_mul(c, x)?)?)
-                % self.ctx.q,
-        )
+        Ok(self.ctx.response(k, c, x))
     }
 
-    fn safe_sub(l: i64, value: i64) -> Result<i64, ZKPError> {
-        l.checked_sub(value)
-            .map_or_else(|| Err(ZKPError::MathOverflow), Ok)
+    fn as_pair(values: Vec<i64>) -> Result<(i64, i64), ZKPError> {
+        match values[..] {
+            [first, second] => Ok((first, second)),
+            _ => Err(ZKPError::LengthMismatch {
+                expected: 2,
+                actual: values.len(),
+            }),
+        }
     }
-    fn safe_pow(base: i64, exp: i64) -> Result<i64, ZKPError> {
-        base.checked_pow(exp.try_into().map_err(|_| ZKPError::CastError)?)
-            .map_or_else(|| Err(ZKPError::MathOverflow), Ok)
+
+    pub fn ctx(&self) -> &Context {
+        &self.ctx
     }
 
-    fn safe_mul(l: i64, r: i64) -> Result<i64, ZKPError> {
-        l.checked_mul(r)
-            .map_or_else(|| Err(ZKPError::MathOverflow), Ok)
+    /// Calculates `y_i = g_i^x` for every base of the context.
+    pub fn calculate_multi_registration_data(&self, x: i64) -> Result<Vec<i64>, ZKPError> {
+        Ok(self.public_keys(x))
     }
-    pub fn calculate_registration_data(&self, x: i64) -> Result<(i64, i64), ZKPError> {
-        let y1 = ChaumPedersenProtocol::safe_pow(self.ctx.g, x)?;
-        let y2 = ChaumPedersenProtocol::safe_pow(self.ctx.h, x)?;
 
-        Ok((y1, y2))
+    pub(crate) fn public_keys(&self, x: i64) -> Vec<i64> {
+        self.ctx
+            .bases
+            .iter()
+            .map(|base| self.ctx.pow(*base, x))
+            .collect()
+    }
+
+    /// Calculates `r_i = g_i^k` for every base of the context.
+    pub fn calculate_multi_login_challenge_data(&self, k: i64) -> Result<Vec<i64>, ZKPError> {
+        self.calculate_multi_registration_data(k)
+    }
+
+    pub fn calculate_registration_data(&self, x: i64) -> Result<(i64, i64), ZKPError> {
+        ChaumPedersenProtocol::as_pair(self.calculate_multi_registration_data(x)?)
     }
 
     pub fn calculate_login_challenge_data(&self, k: i64) -> Result<(i64, i64), ZKPError> {
-        self.calculate_registration_data(k)
+        ChaumPedersenProtocol::as_pair(self.calculate_multi_login_challenge_data(k)?)
+    }
+
+    /// Binds the protocol parameters, public keys and commitments to the transcript.
+    pub fn append_to_transcript(&self, transcript: &mut Transcript, ys: &[i64], rs: &[i64]) {
+        self.ctx.append_to_transcript(transcript);
+        transcript.append_i64s(b"y", ys);
+        transcript.append_i64s(b"r", rs);
+    }
+
+    /// Derives the verifier challenge of the interactive protocol.
+    ///
+    /// The transcript is expected to already carry the commitments, see
+    /// [`ChaumPedersenProtocol::append_to_transcript`]. A fresh nonce keeps the challenge
+    /// unpredictable for the prover.
+    pub fn create_auth_challenge<R: RngCore + CryptoRng>(
+        &self,
+        rng: &mut R,
+        transcript: &mut Transcript,
+    ) -> Result<i64, ZKPError> {
+        transcript.append_verifier_nonce(rng)?;
+        Ok(transcript.challenge_scalar(b"c", self.ctx.q))
     }
 
-    pub fn create_auth_challenge(&self) -> i64 {
-        2
-        // rand::random::<i64>()
+    /// Checks `r_i == g_i^s * y_i^c` for every base of the context.
+    pub fn verify_multi_auth_challenge(
+        &self,
+        ys: &[i64],
+        rs: &[i64],
+        c: i64,
+        s: i64,
+    ) -> Result<(), ZKPError> {
+        self.ctx.check_elements(ys)?;
+        self.ctx.check_elements(rs)?;
+        self.ctx.check_scalar(c)?;
+        self.ctx.check_scalar(s)?;
+
+        if self.calculate_multi_verification_data(ys, c, s) != rs {
+            return Err(ZKPError::VerificationFailed);
+        }
+
+        Ok(())
     }
 
     pub fn verify_auth_challenge(
@@ -65,71 +305,137 @@ impl ChaumPedersenProtocol {
         r2: i64,
         c: i64,
         s: i64,
-    ) -> Result<bool, ZKPError> {
-        let test_left_r1 = ChaumPedersenProtocol::safe_pow(self.ctx.g, s)?;
-        let test_right_r1 = ChaumPedersenProtocol::safe_pow(y1, c)?;
-        let test_r1 = ChaumPedersenProtocol::safe_mul(test_left_r1, test_right_r1)?;
+    ) -> Result<(), ZKPError> {
+        self.verify_multi_auth_challenge(&[y1, y2], &[r1, r2], c, s)
+    }
+
+    /// Creates a non-interactive proof with the challenge derived from the transcript.
+    pub fn create_proof(
+        &self,
+        transcript: &mut Transcript,
+        x: i64,
+        k: i64,
+    ) -> Result<Proof, ZKPError> {
+        let ys = self.calculate_multi_registration_data(x)?;
+        let rs = self.calculate_multi_login_challenge_data(k)?;
+        self.append_to_transcript(transcript, &ys, &rs);
 
-        let test_left_r2 = ChaumPedersenProtocol::safe_pow(self.ctx.h, s)?;
-        let test_right_r2 = ChaumPedersenProtocol::safe_pow(y2, c)?;
-        let test_r2 = ChaumPedersenProtocol::safe_mul(test_left_r2, test_right_r2)?;
+        let c = transcript.challenge_scalar(b"c", self.ctx.q);
+        let s = self.calculate_challenge(k, c, x)?;
 
-        Ok((test_r1 == r1) && (test_r2 == r2))
+        Ok(Proof { c, s })
+    }
+
+    /// Verifies a non-interactive proof against a transcript built the same way as the prover's.
+    pub fn verify_proof(
+        &self,
+        transcript: &mut Transcript,
+        ys: &[i64],
+        proof: &Proof,
+    ) -> Result<(), ZKPError> {
+        self.ctx.check_elements(ys)?;
+        self.ctx.check_scalar(proof.c)?;
+        self.ctx.check_scalar(proof.s)?;
+
+        let rs = self.calculate_multi_verification_data(ys, proof.c, proof.s);
+        self.append_to_transcript(transcript, ys, &rs);
+
+        if transcript.challenge_scalar(b"c", self.ctx.q) != proof.c {
+            return Err(ZKPError::VerificationFailed);
+        }
+
+        Ok(())
+    }
+
+    /// Calculates `g_i^s * y_i^c` for every base of the context.
+    pub(crate) fn calculate_multi_verification_data(&self, ys: &[i64], c: i64, s: i64) -> Vec<i64> {
+        self.ctx
+            .bases
+            .iter()
+            .zip(ys)
+            .map(|(base, y)| self.ctx.mul(self.ctx.pow(*base, s), self.ctx.pow(*y, c)))
+            .collect()
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use rand_chacha::ChaCha20Rng;
+    use rand_core::SeedableRng;
 
-    const ZKP_G: i64 = 3;
-    const ZKP_H: i64 = 5;
-    const ZKP_Q: i64 = 10009;
+    const ZKP_G: i64 = 4;
+    const ZKP_H: i64 = 9;
+    const ZKP_I: i64 = 25;
+    const ZKP_P: i64 = 20123;
+    const ZKP_Q: i64 = 10061;
     const CHALLENGE_K: i64 = 27;
+    const USERNAME: &[u8] = b"bidzyyys";
 
     fn protocol() -> ChaumPedersenProtocol {
-        ChaumPedersenProtocol::new(Context::new(ZKP_G, ZKP_H, ZKP_Q))
+        ChaumPedersenProtocol::new(Context::new(ZKP_G, ZKP_H, ZKP_P, ZKP_Q).unwrap())
     }
 
     fn init() -> (i64, ChaumPedersenProtocol) {
         (9, protocol())
     }
 
+    fn multi_base_protocol() -> ChaumPedersenProtocol {
+        ChaumPedersenProtocol::new(
+            Context::with_bases(vec![ZKP_G, ZKP_H, ZKP_I], ZKP_P, ZKP_Q).unwrap(),
+        )
+    }
+
+    fn transcript() -> Transcript {
+        let mut transcript = Transcript::new(b"test");
+        transcript.append_message(b"username", USERNAME);
+        transcript
+    }
+
+    fn rng() -> ChaCha20Rng {
+        ChaCha20Rng::seed_from_u64(42)
+    }
+
+    fn auth_challenge(
+        rng: &mut ChaCha20Rng,
+        zkp: &ChaumPedersenProtocol,
+        ys: &[i64],
+        rs: &[i64],
+    ) -> i64 {
+        let mut transcript = transcript();
+        zkp.append_to_transcript(&mut transcript, ys, rs);
+        zkp.create_auth_challenge(rng, &mut transcript).unwrap()
+    }
+
     #[test]
     fn should_calculate_proper_registration_data() {
         let (secret, zkp) = init();
         let (y1, y2) = zkp.calculate_registration_data(secret).unwrap();
-        assert_eq!(y1, ZKP_G.checked_pow(secret.try_into().unwrap()).unwrap());
-        assert_eq!(y2, ZKP_H.checked_pow(secret.try_into().unwrap()).unwrap());
+        assert_eq!(y1, mod_pow(ZKP_G, secret, ZKP_P));
+        assert_eq!(y2, mod_pow(ZKP_H, secret, ZKP_P));
     }
 
     #[test]
     fn should_calculate_proper_login_challenge_data() {
         let (_secret, zkp) = init();
         let (r1, r2) = zkp.calculate_login_challenge_data(CHALLENGE_K).unwrap();
-        assert_eq!(
-            r1,
-            ZKP_G.checked_pow(CHALLENGE_K.try_into().unwrap()).unwrap()
-        );
-        assert_eq!(
-            r2,
-            ZKP_H.checked_pow(CHALLENGE_K.try_into().unwrap()).unwrap()
-        );
+        assert_eq!(r1, mod_pow(ZKP_G, CHALLENGE_K, ZKP_P));
+        assert_eq!(r2, mod_pow(ZKP_H, CHALLENGE_K, ZKP_P));
     }
 
     #[test]
     fn should_calculate_proper_auth_challenge() {
         let (secret, zkp) = init();
-        let challenge_c = zkp.create_auth_challenge();
+        let (y1, y2) = zkp.calculate_registration_data(secret).unwrap();
+        let (r1, r2) = zkp.calculate_login_challenge_data(CHALLENGE_K).unwrap();
+        let challenge_c = auth_challenge(&mut rng(), &zkp, &[y1, y2], &[r1, r2]);
         let challenge_s = zkp
             .calculate_challenge(CHALLENGE_K, challenge_c, secret)
             .unwrap();
+        assert!((0..ZKP_Q).contains(&challenge_c));
         assert_eq!(
             challenge_s,
-            CHALLENGE_K
-                .checked_sub(challenge_c.checked_mul(secret).unwrap())
-                .unwrap()
-                % ZKP_Q
+            (CHALLENGE_K - challenge_c * secret).rem_euclid(ZKP_Q)
         );
     }
 
@@ -138,13 +444,13 @@ mod tests {
         let (secret, zkp) = init();
         let (y1, y2) = zkp.calculate_registration_data(secret).unwrap();
         let (r1, r2) = zkp.calculate_login_challenge_data(CHALLENGE_K).unwrap();
-        let challenge_c = zkp.create_auth_challenge();
+        let challenge_c = auth_challenge(&mut rng(), &zkp, &[y1, y2], &[r1, r2]);
         let challenge_s = zkp
             .calculate_challenge(CHALLENGE_K, challenge_c, secret)
             .unwrap();
         assert!(zkp
             .verify_auth_challenge(y1, y2, r1, r2, challenge_c, challenge_s)
-            .unwrap());
+            .is_ok());
     }
 
     #[test]
@@ -152,12 +458,261 @@ mod tests {
         let (secret, zkp) = init();
         let (y1, y2) = zkp.calculate_registration_data(secret).unwrap();
         let (r1, r2) = zkp.calculate_login_challenge_data(CHALLENGE_K).unwrap();
-        let challenge_c = zkp.create_auth_challenge();
+        let challenge_c = auth_challenge(&mut rng(), &zkp, &[y1, y2], &[r1, r2]);
         let invalid_challenge_s = zkp
             .calculate_challenge(CHALLENGE_K, challenge_c, secret.checked_add(1).unwrap())
             .unwrap();
-        assert!(!zkp
-            .verify_auth_challenge(y1, y2, r1, r2, challenge_c, invalid_challenge_s)
-            .unwrap());
+        assert_eq!(
+            zkp.verify_auth_challenge(y1, y2, r1, r2, challenge_c, invalid_challenge_s),
+            Err(ZKPError::VerificationFailed)
+        );
+    }
+
+    #[test]
+    fn should_derive_fresh_interactive_challenges() {
+        let (secret, zkp) = init();
+        let ys = zkp.calculate_multi_registration_data(secret).unwrap();
+        let rs = zkp
+            .calculate_multi_login_challenge_data(CHALLENGE_K)
+            .unwrap();
+        let mut rng = rng();
+        let challenges: Vec<i64> = (0..4)
+            .map(|_| auth_challenge(&mut rng, &zkp, &ys, &rs))
+            .collect();
+        assert!(challenges.iter().any(|c| *c != challenges[0]));
+    }
+
+    #[test]
+    fn should_not_create_context_without_bases() {
+        assert!(matches!(
+            Context::with_bases(vec![], ZKP_P, ZKP_Q),
+            Err(ZKPError::EmptyBases)
+        ));
+    }
+
+    #[test]
+    fn should_not_create_context_with_invalid_modulus() {
+        assert!(matches!(
+            Context::new(ZKP_G, ZKP_H, 0, ZKP_Q),
+            Err(ZKPError::InvalidParameters { .. })
+        ));
+        assert!(matches!(
+            Context::new(ZKP_G, ZKP_H, ZKP_P, 1),
+            Err(ZKPError::InvalidParameters { .. })
+        ));
+        assert!(matches!(
+            Context::new(ZKP_G, ZKP_H, ZKP_P, 7),
+            Err(ZKPError::InvalidParameters { .. })
+        ));
+    }
+
+    #[test]
+    fn should_not_create_context_with_bases_outside_of_subgroup() {
+        assert!(matches!(
+            Context::new(ZKP_G, 1, ZKP_P, ZKP_Q),
+            Err(ZKPError::InvalidParameters { .. })
+        ));
+        assert!(matches!(
+            Context::new(ZKP_G, ZKP_P - 1, ZKP_P, ZKP_Q),
+            Err(ZKPError::InvalidParameters { .. })
+        ));
+    }
+
+    #[test]
+    fn should_identify_context_by_parameters_and_bases() {
+        let ctx = Context::new(ZKP_G, ZKP_H, ZKP_P, ZKP_Q).unwrap();
+        assert_eq!(ctx.id(), ctx.clone().id());
+        assert_eq!(ctx.id().len(), 16);
+        assert_ne!(
+            ctx.id(),
+            Context::new(ZKP_H, ZKP_G, ZKP_P, ZKP_Q).unwrap().id()
+        );
+    }
+
+    #[test]
+    fn should_reject_elements_outside_of_subgroup() {
+        let (secret, zkp) = init();
+        let (y1, y2) = zkp.calculate_registration_data(secret).unwrap();
+        let (r1, r2) = zkp.calculate_login_challenge_data(CHALLENGE_K).unwrap();
+        for element in [0, ZKP_P - 1, ZKP_P + 4] {
+            assert_eq!(
+                zkp.verify_auth_challenge(y1, element, r1, r2, 2, 9),
+                Err(ZKPError::ElementNotInSubgroup { element })
+            );
+            assert_eq!(
+                zkp.verify_auth_challenge(y1, y2, element, r2, 2, 9),
+                Err(ZKPError::ElementNotInSubgroup { element })
+            );
+        }
+    }
+
+    #[test]
+    fn should_reject_scalars_out_of_range() {
+        let (secret, zkp) = init();
+        let (y1, y2) = zkp.calculate_registration_data(secret).unwrap();
+        let (r1, r2) = zkp.calculate_login_challenge_data(CHALLENGE_K).unwrap();
+        for scalar in [-1, ZKP_Q] {
+            assert_eq!(
+                zkp.verify_auth_challenge(y1, y2, r1, r2, scalar, 9),
+                Err(ZKPError::ScalarOutOfRange { scalar, q: ZKP_Q })
+            );
+            assert_eq!(
+                zkp.verify_proof(&mut transcript(), &[y1, y2], &Proof { c: 2, s: scalar }),
+                Err(ZKPError::ScalarOutOfRange { scalar, q: ZKP_Q })
+            );
+        }
+    }
+
+    #[test]
+    fn should_round_trip_proof_encoding() {
+        let proof = Proof { c: 2, s: -9 };
+        assert_eq!(Proof::from_bytes(&proof.to_bytes()), Ok(proof));
+        assert_eq!(
+            Proof::from_bytes(&[0; 15]),
+            Err(ZKPError::MalformedEncoding {
+                expected: 16,
+                actual: 15
+            })
+        );
+    }
+
+    #[test]
+    fn should_calculate_proper_multi_registration_data() {
+        let secret = 9;
+        let zkp = multi_base_protocol();
+        let ys = zkp.calculate_multi_registration_data(secret).unwrap();
+        assert_eq!(
+            ys,
+            [ZKP_G, ZKP_H, ZKP_I]
+                .iter()
+                .map(|base| mod_pow(*base, secret, ZKP_P))
+                .collect::<Vec<i64>>()
+        );
+    }
+
+    #[test]
+    fn should_accept_valid_multi_base_challenge() {
+        let secret = 9;
+        let zkp = multi_base_protocol();
+        let ys = zkp.calculate_multi_registration_data(secret).unwrap();
+        let rs = zkp
+            .calculate_multi_login_challenge_data(CHALLENGE_K)
+            .unwrap();
+        let challenge_c = auth_challenge(&mut rng(), &zkp, &ys, &rs);
+        let challenge_s = zkp
+            .calculate_challenge(CHALLENGE_K, challenge_c, secret)
+            .unwrap();
+        assert!(zkp
+            .verify_multi_auth_challenge(&ys, &rs, challenge_c, challenge_s)
+            .is_ok());
+    }
+
+    #[test]
+    fn should_reject_multi_base_challenge_with_foreign_public_key() {
+        let secret = 9;
+        let zkp = multi_base_protocol();
+        let mut ys = zkp.calculate_multi_registration_data(secret).unwrap();
+        ys[2] = mod_pow(ZKP_I, secret + 1, ZKP_P);
+        let rs = zkp
+            .calculate_multi_login_challenge_data(CHALLENGE_K)
+            .unwrap();
+        let challenge_c = auth_challenge(&mut rng(), &zkp, &ys, &rs);
+        let challenge_s = zkp
+            .calculate_challenge(CHALLENGE_K, challenge_c, secret)
+            .unwrap();
+        assert_eq!(
+            zkp.verify_multi_auth_challenge(&ys, &rs, challenge_c, challenge_s),
+            Err(ZKPError::VerificationFailed)
+        );
+    }
+
+    #[test]
+    fn should_reject_mismatched_number_of_values() {
+        let secret = 9;
+        let zkp = multi_base_protocol();
+        let ys = zkp.calculate_multi_registration_data(secret).unwrap();
+        let rs = zkp
+            .calculate_multi_login_challenge_data(CHALLENGE_K)
+            .unwrap();
+        assert_eq!(
+            zkp.verify_multi_auth_challenge(&ys[..2], &rs, 2, 9),
+            Err(ZKPError::LengthMismatch {
+                expected: 3,
+                actual: 2
+            })
+        );
+        assert_eq!(
+            zkp.calculate_registration_data(secret),
+            Err(ZKPError::LengthMismatch {
+                expected: 2,
+                actual: 3
+            })
+        );
+    }
+
+    #[test]
+    fn should_accept_valid_non_interactive_proof() {
+        let (secret, zkp) = init();
+        let ys = zkp.calculate_multi_registration_data(secret).unwrap();
+        let proof = zkp
+            .create_proof(&mut transcript(), secret, CHALLENGE_K)
+            .unwrap();
+        assert!(zkp.verify_proof(&mut transcript(), &ys, &proof).is_ok());
+    }
+
+    #[test]
+    fn should_reject_non_interactive_proof_for_other_context() {
+        let (secret, zkp) = init();
+        let ys = zkp.calculate_multi_registration_data(secret).unwrap();
+        let proof = zkp
+            .create_proof(&mut transcript(), secret, CHALLENGE_K)
+            .unwrap();
+
+        let mut other_transcript = Transcript::new(b"test");
+        other_transcript.append_message(b"username", b"mallory");
+        assert_eq!(
+            zkp.verify_proof(&mut other_transcript, &ys, &proof),
+            Err(ZKPError::VerificationFailed)
+        );
+    }
+
+    #[test]
+    fn should_reject_forged_non_interactive_proof() {
+        let (secret, zkp) = init();
+        let ys = zkp.calculate_multi_registration_data(secret).unwrap();
+        let proof = zkp
+            .create_proof(&mut transcript(), secret + 1, CHALLENGE_K)
+            .unwrap();
+        assert_eq!(
+            zkp.verify_proof(&mut transcript(), &ys, &proof),
+            Err(ZKPError::VerificationFailed)
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn should_serialize_proof() {
+        let (secret, zkp) = init();
+        let proof = zkp
+            .create_proof(&mut transcript(), secret, CHALLENGE_K)
+            .unwrap();
+        let json = serde_json::to_string(&proof).unwrap();
+        assert_eq!(serde_json::from_str::<Proof>(&json).unwrap(), proof);
+    }
+
+    #[test]
+    fn should_draw_random_scalar_in_range() {
+        let zkp_ctx = Context::new(ZKP_G, ZKP_H, ZKP_P, ZKP_Q).unwrap();
+        let mut rng = rng();
+        assert!((0..32)
+            .map(|_| zkp_ctx.random_scalar(&mut rng))
+            .all(|scalar| (0..ZKP_Q).contains(&scalar)));
+        assert!((0..32)
+            .map(|_| zkp_ctx.try_random_scalar(&mut rng).unwrap())
+            .all(|scalar| (0..ZKP_Q).contains(&scalar)));
+        assert_eq!(
+            zkp_ctx.try_random_scalar(&mut crate::test_utils::FailingRng),
+            Err(ZKPError::RngFailure)
+        );
     }
 }
@@ -0,0 +1,73 @@
+use core::fmt;
+
+/// Errors returned by the protocols of this crate.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ZKPError {
+    /// The group parameters do not describe a subgroup of prime order `q` modulo `p`.
+    InvalidParameters { reason: &'static str },
+    /// A context was created without any base.
+    EmptyBases,
+    /// The number of values does not match the number of bases of the context.
+    LengthMismatch { expected: usize, actual: usize },
+    /// A group element is outside of `[1, p)` or not in the order `q` subgroup.
+    ElementNotInSubgroup { element: i64 },
+    /// A scalar is outside of `[0, q)`.
+    ScalarOutOfRange { scalar: i64, q: i64 },
+    /// A byte encoding has an unexpected length.
+    MalformedEncoding { expected: usize, actual: usize },
+    /// Shamir shares or sharing parameters are inconsistent.
+    InvalidShares { reason: &'static str },
+    /// A backup share cannot be parsed or does not fit the other shares.
+    InvalidBackupShare { reason: &'static str },
+    /// A mnemonic has unknown words, a wrong number of words or a wrong checksum.
+    InvalidMnemonic { reason: &'static str },
+    /// A keystore cannot be parsed, uses unsupported algorithms or belongs to another group.
+    InvalidKeystore { reason: &'static str },
+    /// The keystore password is wrong or the keystore was tampered with.
+    WrongPassword,
+    /// A Diffie-Hellman key share is the identity element.
+    InvalidKeyShare,
+    /// The proof does not hold for the given statement.
+    VerificationFailed,
+    /// The random number generator failed to produce a verifier nonce.
+    RngFailure,
+}
+
+impl fmt::Display for ZKPError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ZKPError::InvalidParameters { reason } => {
+                write!(f, "invalid group parameters: {reason}")
+            }
+            ZKPError::EmptyBases => write!(f, "context needs at least one base"),
+            ZKPError::LengthMismatch { expected, actual } => {
+                write!(f, "expected {expected} values, got {actual}")
+            }
+            ZKPError::ElementNotInSubgroup { element } => {
+                write!(f, "element {element} is not in the order q subgroup")
+            }
+            ZKPError::ScalarOutOfRange { scalar, q } => {
+                write!(f, "scalar {scalar} is out of range [0, {q})")
+            }
+            ZKPError::MalformedEncoding { expected, actual } => {
+                write!(
+                    f,
+                    "malformed encoding: expected {expected} bytes, got {actual}"
+                )
+            }
+            ZKPError::InvalidShares { reason } => write!(f, "invalid shares: {reason}"),
+            ZKPError::InvalidBackupShare { reason } => {
+                write!(f, "invalid backup share: {reason}")
+            }
+            ZKPError::InvalidMnemonic { reason } => write!(f, "invalid mnemonic: {reason}"),
+            ZKPError::InvalidKeystore { reason } => write!(f, "invalid keystore: {reason}"),
+            ZKPError::WrongPassword => write!(f, "wrong keystore password"),
+            ZKPError::InvalidKeyShare => write!(f, "key share must not be the identity"),
+            ZKPError::VerificationFailed => write!(f, "proof verification failed"),
+            ZKPError::RngFailure => write!(f, "random number generator failure"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ZKPError {}
@@ -0,0 +1,347 @@
+use chacha20poly1305::{
+    aead::{Aead, KeyInit, Payload},
+    ChaCha20Poly1305, Key, Nonce,
+};
+use rand_core::{CryptoRng, RngCore};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    chaum_pedersen::{ChaumPedersenProtocol, Context},
+    ZKPError,
+};
+
+const VERSION: u32 = 2;
+const KDF: &str = "scrypt";
+const CIPHER: &str = "chacha20poly1305";
+const SALT_LEN: usize = 32;
+const NONCE_LEN: usize = 12;
+const KEY_LEN: usize = 32;
+/// Upper bounds on the scrypt parameters of a keystore, so a crafted file cannot make unlocking
+/// take gigabytes of memory or hours of work. At the maxima scrypt takes 1 GiB.
+const MAX_LOG_N: u8 = 20;
+const MAX_R: u32 = 8;
+const MAX_P: u32 = 4;
+
+/// Encrypted keystore holding the secret key `x`, stored as JSON.
+///
+/// The secret is encrypted with ChaCha20-Poly1305 under a key derived from the password with
+/// scrypt. The header (version, group id, public key fingerprint and scrypt parameters) is
+/// authenticated as associated data, so it cannot be swapped between keystores.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Keystore {
+    pub version: u32,
+    /// Identifies the group parameters the secret belongs to, see [`Context::id`].
+    pub group: String,
+    /// SHA-256 of the public keys `y_i = g_i^x`, see [`fingerprint`].
+    pub fingerprint: String,
+    pub crypto: KeystoreCrypto,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct KeystoreCrypto {
+    pub kdf: String,
+    pub kdfparams: KdfParams,
+    pub cipher: String,
+    pub nonce: String,
+    pub ciphertext: String,
+}
+
+/// scrypt cost parameters together with the hex encoded salt, at most `log_n = 20`, `r = 8` and
+/// `p = 4`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct KdfParams {
+    pub log_n: u8,
+    pub r: u32,
+    pub p: u32,
+    #[serde(default)]
+    pub salt: String,
+}
+
+impl Default for KdfParams {
+    /// The scrypt parameters recommended for interactive logins.
+    fn default() -> Self {
+        Self {
+            log_n: 15,
+            r: 8,
+            p: 1,
+            salt: String::new(),
+        }
+    }
+}
+
+fn invalid(reason: &'static str) -> ZKPError {
+    ZKPError::InvalidKeystore { reason }
+}
+
+fn from_hex(data: &str) -> Result<Vec<u8>, ZKPError> {
+    hex::decode(data).map_err(|_| invalid("malformed hex"))
+}
+
+/// Fingerprint of the public keys `ys` of the secret.
+pub fn fingerprint(ys: &[i64]) -> String {
+    let mut hasher = Sha256::new();
+    for y in ys {
+        hasher.update(y.to_be_bytes());
+    }
+    hex::encode(hasher.finalize())
+}
+
+fn derive_key(password: &str, params: &KdfParams) -> Result<[u8; KEY_LEN], ZKPError> {
+    if params.log_n > MAX_LOG_N || params.r > MAX_R || params.p > MAX_P {
+        return Err(invalid("scrypt parameters above the maxima"));
+    }
+    let salt = from_hex(&params.salt)?;
+    let scrypt_params = scrypt::Params::new(params.log_n, params.r, params.p, KEY_LEN)
+        .map_err(|_| invalid("invalid scrypt parameters"))?;
+    let mut key = [0u8; KEY_LEN];
+    scrypt::scrypt(password.as_bytes(), &salt, &scrypt_params, &mut key)
+        .map_err(|_| invalid("invalid scrypt parameters"))?;
+    Ok(key)
+}
+
+impl Keystore {
+    /// Encrypts the secret key `x` with a key derived from `password`.
+    pub fn create<R: RngCore + CryptoRng>(
+        rng: &mut R,
+        ctx: &Context,
+        x: i64,
+        password: &str,
+        kdfparams: KdfParams,
+    ) -> Result<Self, ZKPError> {
+        ctx.check_scalar(x)?;
+        let ys = ChaumPedersenProtocol::new(ctx.clone()).calculate_multi_registration_data(x)?;
+
+        let mut salt = [0u8; SALT_LEN];
+        let mut nonce = [0u8; NONCE_LEN];
+        rng.try_fill_bytes(&mut salt)
+            .and_then(|_| rng.try_fill_bytes(&mut nonce))
+            .map_err(|_| ZKPError::RngFailure)?;
+
+        let mut keystore = Self {
+            version: VERSION,
+            group: ctx.id(),
+            fingerprint: fingerprint(&ys),
+            crypto: KeystoreCrypto {
+                kdf: KDF.into(),
+                kdfparams: KdfParams {
+                    salt: hex::encode(salt),
+                    ..kdfparams
+                },
+                cipher: CIPHER.into(),
+                nonce: hex::encode(nonce),
+                ciphertext: String::new(),
+            },
+        };
+        let key = derive_key(password, &keystore.crypto.kdfparams)?;
+        let ciphertext = ChaCha20Poly1305::new(Key::from_slice(&key))
+            .encrypt(
+                Nonce::from_slice(&nonce),
+                Payload {
+                    msg: &x.to_be_bytes(),
+                    aad: keystore.associated_data().as_bytes(),
+                },
+            )
+            .map_err(|_| invalid("encryption failed"))?;
+        keystore.crypto.ciphertext = hex::encode(&ciphertext);
+
+        Ok(keystore)
+    }
+
+    /// Decrypts the secret key, checking it against the group and the public key fingerprint.
+    pub fn unlock(&self, ctx: &Context, password: &str) -> Result<i64, ZKPError> {
+        if self.version != VERSION {
+            return Err(invalid("unsupported version"));
+        }
+        if self.crypto.kdf != KDF || self.crypto.cipher != CIPHER {
+            return Err(invalid("unsupported kdf or cipher"));
+        }
+        if self.group != ctx.id() {
+            return Err(invalid("keystore belongs to other group parameters"));
+        }
+
+        let nonce = from_hex(&self.crypto.nonce)?;
+        if nonce.len() != NONCE_LEN {
+            return Err(invalid("malformed nonce"));
+        }
+        let key = derive_key(password, &self.crypto.kdfparams)?;
+        let plaintext = ChaCha20Poly1305::new(Key::from_slice(&key))
+            .decrypt(
+                Nonce::from_slice(&nonce),
+                Payload {
+                    msg: &from_hex(&self.crypto.ciphertext)?,
+                    aad: self.associated_data().as_bytes(),
+                },
+            )
+            .map_err(|_| ZKPError::WrongPassword)?;
+
+        let x = i64::from_be_bytes(
+            plaintext
+                .try_into()
+                .map_err(|_| invalid("malformed secret"))?,
+        );
+        ctx.check_scalar(x)?;
+        let ys = ChaumPedersenProtocol::new(ctx.clone()).calculate_multi_registration_data(x)?;
+        if fingerprint(&ys) != self.fingerprint {
+            return Err(invalid("public key fingerprint mismatch"));
+        }
+
+        Ok(x)
+    }
+
+    /// Re-encrypts the secret under `new_password` with a fresh salt and nonce.
+    pub fn change_password<R: RngCore + CryptoRng>(
+        &self,
+        rng: &mut R,
+        ctx: &Context,
+        old_password: &str,
+        new_password: &str,
+    ) -> Result<Self, ZKPError> {
+        let x = self.unlock(ctx, old_password)?;
+        Self::create(rng, ctx, x, new_password, self.crypto.kdfparams.clone())
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("`Keystore` is serializable to json")
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, ZKPError> {
+        serde_json::from_str(json).map_err(|_| invalid("malformed json"))
+    }
+
+    fn associated_data(&self) -> String {
+        let kdfparams = &self.crypto.kdfparams;
+        format!(
+            "{}:{}:{}:{}:{}:{}:{}:{}",
+            self.version,
+            self.group,
+            self.fingerprint,
+            self.crypto.kdf,
+            kdfparams.log_n,
+            kdfparams.r,
+            kdfparams.p,
+            kdfparams.salt
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand_chacha::ChaCha20Rng;
+    use rand_core::SeedableRng;
+
+    const ZKP_G: i64 = 4;
+    const ZKP_H: i64 = 9;
+    const ZKP_P: i64 = 20123;
+    const ZKP_Q: i64 = 10061;
+    const SECRET: i64 = 9;
+    const PASSWORD: &str = "correct horse battery staple";
+
+    fn ctx() -> Context {
+        Context::new(ZKP_G, ZKP_H, ZKP_P, ZKP_Q).unwrap()
+    }
+
+    /// Cheap scrypt parameters keeping the tests fast.
+    fn kdfparams() -> KdfParams {
+        KdfParams {
+            log_n: 4,
+            ..KdfParams::default()
+        }
+    }
+
+    fn keystore() -> Keystore {
+        let mut rng = ChaCha20Rng::seed_from_u64(42);
+        Keystore::create(&mut rng, &ctx(), SECRET, PASSWORD, kdfparams()).unwrap()
+    }
+
+    #[test]
+    fn should_unlock_with_password() {
+        let keystore = Keystore::from_json(&keystore().to_json()).unwrap();
+        assert!(!keystore
+            .crypto
+            .ciphertext
+            .contains(&hex::encode(SECRET.to_be_bytes())));
+        assert_eq!(keystore.unlock(&ctx(), PASSWORD), Ok(SECRET));
+        assert_eq!(
+            keystore.unlock(&ctx(), "wrong password"),
+            Err(ZKPError::WrongPassword)
+        );
+    }
+
+    #[test]
+    fn should_change_password() {
+        let mut rng = ChaCha20Rng::seed_from_u64(7);
+        let keystore = keystore();
+        let changed = keystore
+            .change_password(&mut rng, &ctx(), PASSWORD, "new password")
+            .unwrap();
+        assert_ne!(
+            changed.crypto.kdfparams.salt,
+            keystore.crypto.kdfparams.salt
+        );
+        assert_eq!(changed.unlock(&ctx(), "new password"), Ok(SECRET));
+        assert_eq!(
+            changed.unlock(&ctx(), PASSWORD),
+            Err(ZKPError::WrongPassword)
+        );
+        assert_eq!(
+            keystore.change_password(&mut rng, &ctx(), "wrong password", "new password"),
+            Err(ZKPError::WrongPassword)
+        );
+    }
+
+    #[test]
+    fn should_reject_tampered_keystore() {
+        let mut tampered = keystore();
+        tampered.fingerprint = fingerprint(&[1, 1]);
+        assert_eq!(
+            tampered.unlock(&ctx(), PASSWORD),
+            Err(ZKPError::WrongPassword)
+        );
+
+        let other_ctx = Context::new(ZKP_H, ZKP_G, ZKP_P, ZKP_Q).unwrap();
+        assert!(matches!(
+            keystore().unlock(&other_ctx, PASSWORD),
+            Err(ZKPError::InvalidKeystore { .. })
+        ));
+        assert!(matches!(
+            Keystore::from_json("{}"),
+            Err(ZKPError::InvalidKeystore { .. })
+        ));
+    }
+
+    #[test]
+    fn should_reject_scrypt_parameters_above_maxima() {
+        let mut rng = ChaCha20Rng::seed_from_u64(42);
+        for kdfparams in [
+            KdfParams {
+                log_n: MAX_LOG_N + 1,
+                ..kdfparams()
+            },
+            KdfParams {
+                r: MAX_R + 1,
+                ..kdfparams()
+            },
+            KdfParams {
+                p: MAX_P + 1,
+                ..kdfparams()
+            },
+        ] {
+            assert!(matches!(
+                Keystore::create(&mut rng, &ctx(), SECRET, PASSWORD, kdfparams.clone()),
+                Err(ZKPError::InvalidKeystore { .. })
+            ));
+
+            let mut tampered = keystore();
+            tampered.crypto.kdfparams = KdfParams {
+                salt: tampered.crypto.kdfparams.salt.clone(),
+                ..kdfparams
+            };
+            assert!(matches!(
+                tampered.unlock(&ctx(), PASSWORD),
+                Err(ZKPError::InvalidKeystore { .. })
+            ));
+        }
+    }
+}
@@ -1,7 +1,33 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+#[cfg(feature = "ake")]
+pub mod ake;
+#[cfg(feature = "backup")]
+pub mod backup;
 pub mod chaum_pedersen;
+mod error;
+#[cfg(feature = "keystore")]
+pub mod keystore;
+mod math;
+#[cfg(feature = "mnemonic")]
+pub mod mnemonic;
+#[cfg(feature = "okamoto")]
+pub mod okamoto;
+#[cfg(feature = "pedersen")]
+pub mod pedersen;
+#[cfg(feature = "rotation")]
+pub mod rotation;
+#[cfg(feature = "server-auth")]
+pub mod server_auth;
+#[cfg(any(test, feature = "test-utils"))]
+pub mod test_utils;
+#[cfg(feature = "threshold")]
+pub mod threshold;
+pub mod transcript;
 
-#[derive(Debug)]
-pub enum ZKPError {
-    MathOverflow,
-    CastError,
-}
+pub use chaum_pedersen::{ChaumPedersenProtocol, Context, Proof};
+pub use error::ZKPError;
+pub use rand_core::{CryptoRng, RngCore};
+pub use transcript::Transcript;
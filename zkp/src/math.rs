@@ -0,0 +1,65 @@
+/// Calculates `l * r mod m`, always in `[0, m)`.
+pub(crate) fn mod_mul(l: i64, r: i64, m: i64) -> i64 {
+    (l as i128 * r as i128).rem_euclid(m as i128) as i64
+}
+
+/// Calculates `l + r mod m`, always in `[0, m)`.
+#[cfg_attr(
+    not(any(feature = "pedersen", feature = "threshold")),
+    allow(dead_code)
+)]
+pub(crate) fn mod_add(l: i64, r: i64, m: i64) -> i64 {
+    (l as i128 + r as i128).rem_euclid(m as i128) as i64
+}
+
+/// Calculates `l - r mod m`, always in `[0, m)`.
+pub(crate) fn mod_sub(l: i64, r: i64, m: i64) -> i64 {
+    (l as i128 - r as i128).rem_euclid(m as i128) as i64
+}
+
+/// Calculates `base^exp mod m` for a non-negative `exp`.
+pub(crate) fn mod_pow(base: i64, exp: i64, m: i64) -> i64 {
+    let mut result = 1 % m;
+    let mut base = base.rem_euclid(m);
+    let mut exp = exp;
+    while exp > 0 {
+        if exp & 1 == 1 {
+            result = mod_mul(result, base, m);
+        }
+        base = mod_mul(base, base, m);
+        exp >>= 1;
+    }
+    result
+}
+
+/// Calculates the inverse of a non-zero `value` modulo a prime `m`.
+#[cfg_attr(not(feature = "threshold"), allow(dead_code))]
+pub(crate) fn mod_inv(value: i64, m: i64) -> i64 {
+    mod_pow(value, m - 2, m)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_reduce_into_modulus() {
+        assert_eq!(mod_mul(-3, 5, 7), 6);
+        assert_eq!(mod_add(5, 4, 7), 2);
+        assert_eq!(mod_sub(2, 5, 7), 4);
+        assert_eq!(mod_mul(i64::MAX, i64::MAX, 1_000_000_007), 737_564_071);
+    }
+
+    #[test]
+    fn should_calculate_modular_power() {
+        assert_eq!(mod_pow(4, 0, 20123), 1);
+        assert_eq!(mod_pow(4, 9, 20123), 4_i64.pow(9) % 20123);
+        assert_eq!(mod_pow(3, 20122, 20123), 1);
+    }
+
+    #[test]
+    fn should_calculate_modular_inverse() {
+        assert_eq!(mod_inv(3, 7), 5);
+        assert_eq!(mod_mul(mod_inv(-9, 10061), -9, 10061), 1);
+    }
+}
@@ -0,0 +1,285 @@
+use alloc::{format, string::ToString, vec, vec::Vec};
+use core::{fmt, str::FromStr};
+
+use pbkdf2::pbkdf2_hmac;
+use rand_core::{CryptoRng, RngCore};
+use sha2::{Digest, Sha256, Sha512};
+
+use crate::{chaum_pedersen::Context, ZKPError};
+
+const WORDLIST: &str = include_str!("mnemonic/english.txt");
+const BITS_PER_WORD: usize = 11;
+const SEED_ROUNDS: u32 = 2048;
+const SEED_LEN: usize = 64;
+
+/// BIP39-style mnemonic over the English BIP39 wordlist.
+///
+/// The entropy is followed by the first `len / 32` bits of its SHA-256 hash as a checksum, and
+/// every 11 bits select one word. Unlike BIP39, 64-bit entropy (6 words) is allowed so a secret
+/// key fits exactly.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Mnemonic {
+    entropy: Vec<u8>,
+}
+
+/// Encodes secret keys as mnemonics and derives secret keys from mnemonics.
+pub struct MnemonicProtocol {
+    ctx: Context,
+}
+
+fn wordlist() -> Vec<&'static str> {
+    WORDLIST.lines().collect()
+}
+
+fn invalid(reason: &'static str) -> ZKPError {
+    ZKPError::InvalidMnemonic { reason }
+}
+
+fn bits(bytes: &[u8]) -> impl Iterator<Item = bool> + '_ {
+    bytes
+        .iter()
+        .flat_map(|byte| (0..8).rev().map(move |bit| byte >> bit & 1 == 1))
+}
+
+fn checksum_bits(entropy: &[u8]) -> Vec<bool> {
+    let digest = Sha256::digest(entropy);
+    bits(&digest).take(entropy.len() / 4).collect()
+}
+
+impl Mnemonic {
+    /// Accepts 8 to 32 bytes of entropy in steps of 4, i.e. 6 to 24 words.
+    pub fn from_entropy(entropy: &[u8]) -> Result<Self, ZKPError> {
+        if !(8..=32).contains(&entropy.len()) || !entropy.len().is_multiple_of(4) {
+            return Err(invalid("entropy must be 8 to 32 bytes in steps of 4"));
+        }
+
+        Ok(Self {
+            entropy: entropy.to_vec(),
+        })
+    }
+
+    /// Generates a mnemonic of `words` words, 12 to 24 in steps of 3 for a BIP39 mnemonic.
+    pub fn generate<R: RngCore + CryptoRng>(rng: &mut R, words: usize) -> Result<Self, ZKPError> {
+        if !words.is_multiple_of(3) {
+            return Err(invalid("number of words must be a multiple of 3"));
+        }
+
+        let mut entropy = vec![0u8; words * 4 / 3];
+        rng.try_fill_bytes(&mut entropy)
+            .map_err(|_| ZKPError::RngFailure)?;
+        Self::from_entropy(&entropy)
+    }
+
+    pub fn entropy(&self) -> &[u8] {
+        &self.entropy
+    }
+
+    pub fn word_count(&self) -> usize {
+        self.entropy.len() * 3 / 4
+    }
+
+    /// Stretches the mnemonic and the optional passphrase into a 64-byte seed as in BIP39.
+    ///
+    /// The passphrase is used as given, without Unicode normalization.
+    pub fn to_seed(&self, passphrase: &str) -> [u8; SEED_LEN] {
+        let mut seed = [0u8; SEED_LEN];
+        pbkdf2_hmac::<Sha512>(
+            self.to_string().as_bytes(),
+            format!("mnemonic{passphrase}").as_bytes(),
+            SEED_ROUNDS,
+            &mut seed,
+        );
+        seed
+    }
+}
+
+impl fmt::Display for Mnemonic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let wordlist = wordlist();
+        let bits: Vec<bool> = bits(&self.entropy)
+            .chain(checksum_bits(&self.entropy))
+            .collect();
+        for (position, word_bits) in bits.chunks(BITS_PER_WORD).enumerate() {
+            if position > 0 {
+                write!(f, " ")?;
+            }
+            let index = word_bits
+                .iter()
+                .fold(0, |acc, bit| acc << 1 | usize::from(*bit));
+            write!(f, "{}", wordlist[index])?;
+        }
+
+        Ok(())
+    }
+}
+
+impl FromStr for Mnemonic {
+    type Err = ZKPError;
+
+    /// Parses words separated by whitespace, ignoring case.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let wordlist = wordlist();
+        let bits = s
+            .split_whitespace()
+            .map(|word| {
+                wordlist
+                    .binary_search(&word.to_lowercase().as_str())
+                    .map_err(|_| invalid("unknown word"))
+            })
+            .collect::<Result<Vec<usize>, ZKPError>>()?
+            .into_iter()
+            .flat_map(|index| {
+                (0..BITS_PER_WORD)
+                    .rev()
+                    .map(move |bit| index >> bit & 1 == 1)
+            })
+            .collect::<Vec<bool>>();
+        if bits.len() % (3 * BITS_PER_WORD) != 0 {
+            return Err(invalid("number of words must be a multiple of 3"));
+        }
+
+        let (entropy_bits, checksum) = bits.split_at(bits.len() * 32 / 33);
+        let entropy: Vec<u8> = entropy_bits
+            .chunks(8)
+            .map(|byte| byte.iter().fold(0, |acc, bit| acc << 1 | u8::from(*bit)))
+            .collect();
+        let mnemonic = Self::from_entropy(&entropy)?;
+        if checksum_bits(&entropy) != checksum {
+            return Err(invalid("checksum mismatch"));
+        }
+
+        Ok(mnemonic)
+    }
+}
+
+impl MnemonicProtocol {
+    pub fn new(ctx: Context) -> Self {
+        Self { ctx }
+    }
+
+    /// Encodes the secret key `x` as a 6-word mnemonic that decodes back to exactly `x`.
+    pub fn encode_secret(&self, x: i64) -> Result<Mnemonic, ZKPError> {
+        self.ctx.check_scalar(x)?;
+        Mnemonic::from_entropy(&x.to_be_bytes())
+    }
+
+    pub fn decode_secret(&self, mnemonic: &Mnemonic) -> Result<i64, ZKPError> {
+        let entropy: [u8; 8] = mnemonic
+            .entropy()
+            .try_into()
+            .map_err(|_| invalid("secret keys are encoded in 6 words"))?;
+        let x = i64::from_be_bytes(entropy);
+        self.ctx.check_scalar(x)?;
+
+        Ok(x)
+    }
+
+    /// Derives the secret key `x` from the BIP39 seed of the mnemonic and the passphrase.
+    pub fn derive_secret(&self, mnemonic: &Mnemonic, passphrase: &str) -> i64 {
+        let seed = mnemonic.to_seed(passphrase);
+        let wide = u128::from_be_bytes(seed[..16].try_into().expect("seed has 64 bytes"));
+        (wide % self.ctx.q() as u128) as i64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand_chacha::ChaCha20Rng;
+    use rand_core::SeedableRng;
+
+    const ZKP_G: i64 = 4;
+    const ZKP_H: i64 = 9;
+    const ZKP_P: i64 = 4503599627370023;
+    const ZKP_Q: i64 = 2251799813685011;
+    const SECRET: i64 = 1234567890123;
+
+    fn init() -> MnemonicProtocol {
+        MnemonicProtocol::new(Context::new(ZKP_G, ZKP_H, ZKP_P, ZKP_Q).unwrap())
+    }
+
+    fn hex(bytes: &[u8]) -> alloc::string::String {
+        bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+    }
+
+    #[test]
+    fn should_match_bip39_test_vector() {
+        let mnemonic = Mnemonic::from_entropy(&[0; 16]).unwrap();
+        let phrase = mnemonic.to_string();
+        assert_eq!(phrase, "abandon ".repeat(11) + "about");
+        assert_eq!(
+            hex(&mnemonic.to_seed("TREZOR")),
+            "c55257c360c07c72029aebc1b53c05ed0362ada38ead3e3e9efa3708e5349553\
+             1f09a6987599d18264c1e1c92f2cf141630c7a3c4ab7c81b2f001698e7463b04"
+        );
+        assert_eq!(
+            "legal winner thank year wave sausage worth useful legal winner thank yellow"
+                .parse::<Mnemonic>()
+                .unwrap()
+                .entropy(),
+            [0x7f; 16]
+        );
+    }
+
+    #[test]
+    fn should_round_trip_secret_exactly() {
+        let zkp = init();
+        for x in [0, 1, SECRET, ZKP_Q - 1] {
+            let mnemonic = zkp.encode_secret(x).unwrap();
+            assert_eq!(mnemonic.word_count(), 6);
+            let parsed: Mnemonic = mnemonic.to_string().to_uppercase().parse().unwrap();
+            assert_eq!(zkp.decode_secret(&parsed), Ok(x));
+        }
+        assert!(matches!(
+            zkp.encode_secret(ZKP_Q),
+            Err(ZKPError::ScalarOutOfRange { .. })
+        ));
+    }
+
+    #[test]
+    fn should_reject_invalid_mnemonics() {
+        let zkp = init();
+        let phrase = zkp.encode_secret(SECRET).unwrap().to_string();
+        let mut words: Vec<&str> = phrase.split(' ').collect();
+
+        // The lowest bit of the last word is the last checksum bit.
+        let wordlist = wordlist();
+        let last = wordlist.binary_search(&words[5]).unwrap();
+        words[5] = wordlist[last ^ 1];
+        assert_eq!(
+            words.join(" ").parse::<Mnemonic>(),
+            Err(ZKPError::InvalidMnemonic {
+                reason: "checksum mismatch"
+            })
+        );
+        words[0] = "zkp";
+        assert_eq!(
+            words.join(" ").parse::<Mnemonic>(),
+            Err(ZKPError::InvalidMnemonic {
+                reason: "unknown word"
+            })
+        );
+        assert!(matches!(
+            words[1..].join(" ").parse::<Mnemonic>(),
+            Err(ZKPError::InvalidMnemonic { .. })
+        ));
+    }
+
+    #[test]
+    fn should_derive_secret_bound_to_passphrase() {
+        let zkp = init();
+        let mnemonic = Mnemonic::generate(&mut ChaCha20Rng::seed_from_u64(42), 12).unwrap();
+        assert_eq!(mnemonic.word_count(), 12);
+        let x = zkp.derive_secret(&mnemonic, "");
+        assert!((0..ZKP_Q).contains(&x));
+        assert_eq!(
+            x,
+            zkp.derive_secret(&mnemonic.to_string().parse().unwrap(), "")
+        );
+        assert_ne!(x, zkp.derive_secret(&mnemonic, "passphrase"));
+        assert!(matches!(
+            zkp.decode_secret(&mnemonic),
+            Err(ZKPError::InvalidMnemonic { .. })
+        ));
+    }
+}
@@ -0,0 +1,175 @@
+use alloc::vec::Vec;
+
+use rand_core::{CryptoRng, RngCore};
+
+use crate::{chaum_pedersen::Context, transcript::Transcript, ZKPError};
+
+/// Okamoto proof of knowledge of a representation `y = g_1^a_1 * ... * g_n^a_n`.
+///
+/// With the two-base context `(g, h)` this proves knowledge of both `a` and `b` in `y = g^a * h^b`.
+pub struct OkamotoProtocol {
+    ctx: Context,
+}
+
+impl OkamotoProtocol {
+    pub fn new(ctx: Context) -> Self {
+        Self { ctx }
+    }
+
+    /// Calculates the public key `y = prod(g_i^a_i)`.
+    pub fn calculate_registration_data(&self, secrets: &[i64]) -> Result<i64, ZKPError> {
+        self.ctx.multi_pow(secrets)
+    }
+
+    /// Calculates the commitment `t = prod(g_i^k_i)`.
+    pub fn calculate_login_challenge_data(&self, nonces: &[i64]) -> Result<i64, ZKPError> {
+        self.ctx.multi_pow(nonces)
+    }
+
+    /// Binds the protocol parameters, public key and commitment to the transcript.
+    pub fn append_to_transcript(&self, transcript: &mut Transcript, y: i64, t: i64) {
+        self.ctx.append_to_transcript(transcript);
+        transcript.append_i64(b"y", y);
+        transcript.append_i64(b"t", t);
+    }
+
+    /// Derives the verifier challenge of the interactive protocol, see
+    /// [`crate::chaum_pedersen::ChaumPedersenProtocol::create_auth_challenge`].
+    pub fn create_auth_challenge<R: RngCore + CryptoRng>(
+        &self,
+        rng: &mut R,
+        transcript: &mut Transcript,
+    ) -> Result<i64, ZKPError> {
+        transcript.append_verifier_nonce(rng)?;
+        Ok(transcript.challenge_scalar(b"c", self.ctx.q()))
+    }
+
+    /// Calculates the responses `s_i = k_i - c * a_i`.
+    pub fn calculate_challenge(
+        &self,
+        nonces: &[i64],
+        c: i64,
+        secrets: &[i64],
+    ) -> Result<Vec<i64>, ZKPError> {
+        self.ctx.check_len(nonces.len())?;
+        self.ctx.check_len(secrets.len())?;
+
+        nonces
+            .iter()
+            .zip(secrets)
+            .map(|(k, a)| Ok(self.ctx.response(*k, c, *a)))
+            .collect()
+    }
+
+    /// Checks `t == prod(g_i^s_i) * y^c`.
+    pub fn verify_auth_challenge(
+        &self,
+        y: i64,
+        t: i64,
+        c: i64,
+        responses: &[i64],
+    ) -> Result<(), ZKPError> {
+        self.ctx.check_element(y)?;
+        self.ctx.check_element(t)?;
+        self.ctx.check_scalar(c)?;
+        self.ctx.check_len(responses.len())?;
+        responses
+            .iter()
+            .try_for_each(|s| self.ctx.check_scalar(*s))?;
+
+        let test_left = self.ctx.multi_pow(responses)?;
+        let test_right = self.ctx.pow(y, c);
+        if self.ctx.mul(test_left, test_right) != t {
+            return Err(ZKPError::VerificationFailed);
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand_chacha::ChaCha20Rng;
+    use rand_core::SeedableRng;
+
+    const ZKP_G: i64 = 4;
+    const ZKP_H: i64 = 9;
+    const ZKP_P: i64 = 20123;
+    const ZKP_Q: i64 = 10061;
+    const CHALLENGE_C: i64 = 2;
+    const SECRETS: [i64; 2] = [3, 2];
+    const NONCES: [i64; 2] = [10, 5];
+
+    fn init() -> OkamotoProtocol {
+        OkamotoProtocol::new(Context::new(ZKP_G, ZKP_H, ZKP_P, ZKP_Q).unwrap())
+    }
+
+    fn auth_challenge(zkp: &OkamotoProtocol, y: i64, t: i64) -> i64 {
+        let mut transcript = Transcript::new(b"test");
+        zkp.append_to_transcript(&mut transcript, y, t);
+        zkp.create_auth_challenge(&mut ChaCha20Rng::seed_from_u64(42), &mut transcript)
+            .unwrap()
+    }
+
+    #[test]
+    fn should_calculate_proper_registration_data() {
+        let zkp = init();
+        let y = zkp.calculate_registration_data(&SECRETS).unwrap();
+        assert_eq!(y, ZKP_G.pow(3) * ZKP_H.pow(2) % ZKP_P);
+    }
+
+    #[test]
+    fn should_accept_valid_challenge() {
+        let zkp = init();
+        let y = zkp.calculate_registration_data(&SECRETS).unwrap();
+        let t = zkp.calculate_login_challenge_data(&NONCES).unwrap();
+        let challenge_c = auth_challenge(&zkp, y, t);
+        let responses = zkp
+            .calculate_challenge(&NONCES, challenge_c, &SECRETS)
+            .unwrap();
+        assert!(zkp
+            .verify_auth_challenge(y, t, challenge_c, &responses)
+            .is_ok());
+    }
+
+    #[test]
+    fn should_reject_invalid_challenge() {
+        let zkp = init();
+        let y = zkp.calculate_registration_data(&SECRETS).unwrap();
+        let t = zkp.calculate_login_challenge_data(&NONCES).unwrap();
+        let challenge_c = auth_challenge(&zkp, y, t);
+        let invalid_responses = zkp
+            .calculate_challenge(&NONCES, challenge_c, &[SECRETS[0] - 1, SECRETS[1]])
+            .unwrap();
+        assert_eq!(
+            zkp.verify_auth_challenge(y, t, challenge_c, &invalid_responses),
+            Err(ZKPError::VerificationFailed)
+        );
+    }
+
+    #[test]
+    fn should_reject_mismatched_number_of_secrets() {
+        let zkp = init();
+        let mismatch = Err(ZKPError::LengthMismatch {
+            expected: 2,
+            actual: 1,
+        });
+        assert_eq!(zkp.calculate_registration_data(&SECRETS[..1]), mismatch);
+        assert_eq!(
+            zkp.calculate_challenge(&NONCES, CHALLENGE_C, &SECRETS[..1])
+                .map(|_| 0),
+            mismatch
+        );
+    }
+
+    #[test]
+    fn should_reject_commitment_outside_of_subgroup() {
+        let zkp = init();
+        let y = zkp.calculate_registration_data(&SECRETS).unwrap();
+        assert_eq!(
+            zkp.verify_auth_challenge(y, ZKP_P - 1, CHALLENGE_C, &NONCES),
+            Err(ZKPError::ElementNotInSubgroup { element: ZKP_P - 1 })
+        );
+    }
+}
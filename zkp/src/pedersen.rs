@@ -0,0 +1,255 @@
+use rand_core::{CryptoRng, RngCore};
+
+use crate::{chaum_pedersen::Context, math::mod_add, transcript::Transcript, ZKPError};
+
+/// Pedersen commitment `C = g^m * h^r` to the message `m`.
+///
+/// It hides `m` perfectly and binds the committer to it as long as nobody knows `log_g(h)`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Commitment(pub i64);
+
+/// Message `m` and blinding factor `r` opening a [`Commitment`].
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Opening {
+    pub m: i64,
+    pub r: i64,
+}
+
+/// Non-interactive proof of knowledge of the opening of a commitment.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct OpeningProof {
+    pub c: i64,
+    pub s_m: i64,
+    pub s_r: i64,
+}
+
+/// Pedersen commitments over the two-base context `(g, h)`.
+///
+/// A commitment is an Okamoto public key, so the opening proof is the Okamoto Sigma protocol
+/// made non-interactive with the transcript. Appending further statements to the same transcript
+/// folds the commitment into e.g. a login proof.
+pub struct PedersenProtocol {
+    ctx: Context,
+}
+
+impl PedersenProtocol {
+    pub fn new(ctx: Context) -> Result<Self, ZKPError> {
+        ctx.check_len(2)?;
+        Ok(Self { ctx })
+    }
+
+    /// Commits to `m` with a fresh blinding factor.
+    pub fn commit<R: RngCore + CryptoRng>(
+        &self,
+        rng: &mut R,
+        m: i64,
+    ) -> Result<(Commitment, Opening), ZKPError> {
+        let opening = Opening {
+            m,
+            r: self.ctx.random_scalar(rng),
+        };
+        Ok((self.commit_with(&opening)?, opening))
+    }
+
+    pub fn commit_with(&self, opening: &Opening) -> Result<Commitment, ZKPError> {
+        self.ctx.check_scalar(opening.m)?;
+        self.ctx.check_scalar(opening.r)?;
+        Ok(Commitment(self.ctx.multi_pow(&[opening.m, opening.r])?))
+    }
+
+    /// Checks that `opening` opens `commitment`.
+    pub fn open(&self, commitment: Commitment, opening: &Opening) -> Result<(), ZKPError> {
+        self.ctx.check_element(commitment.0)?;
+        if self.commit_with(opening)? != commitment {
+            return Err(ZKPError::VerificationFailed);
+        }
+
+        Ok(())
+    }
+
+    /// Calculates `C_1 * C_2`, the commitment to `m_1 + m_2`.
+    pub fn add(&self, left: Commitment, right: Commitment) -> Result<Commitment, ZKPError> {
+        self.ctx.check_element(left.0)?;
+        self.ctx.check_element(right.0)?;
+        Ok(Commitment(self.ctx.mul(left.0, right.0)))
+    }
+
+    /// Calculates the opening of [`PedersenProtocol::add`].
+    pub fn add_openings(&self, left: &Opening, right: &Opening) -> Opening {
+        let q = self.ctx.q();
+        Opening {
+            m: mod_add(left.m, right.m, q),
+            r: mod_add(left.r, right.r, q),
+        }
+    }
+
+    pub fn append_to_transcript(&self, transcript: &mut Transcript, commitment: Commitment) {
+        self.ctx.append_to_transcript(transcript);
+        transcript.append_i64(b"commitment", commitment.0);
+    }
+
+    /// Proves knowledge of `(m, r)` with `C = g^m * h^r` without revealing them.
+    pub fn prove_opening<R: RngCore + CryptoRng>(
+        &self,
+        rng: &mut R,
+        transcript: &mut Transcript,
+        commitment: Commitment,
+        opening: &Opening,
+    ) -> Result<OpeningProof, ZKPError> {
+        let nonces = [self.ctx.random_scalar(rng), self.ctx.random_scalar(rng)];
+        let t = self.ctx.multi_pow(&nonces)?;
+        self.append_to_transcript(transcript, commitment);
+        transcript.append_i64(b"t", t);
+
+        let c = transcript.challenge_scalar(b"c", self.ctx.q());
+        Ok(OpeningProof {
+            c,
+            s_m: self.ctx.response(nonces[0], c, opening.m),
+            s_r: self.ctx.response(nonces[1], c, opening.r),
+        })
+    }
+
+    /// Verifies an opening proof against a transcript built the same way as the prover's.
+    pub fn verify_opening(
+        &self,
+        transcript: &mut Transcript,
+        commitment: Commitment,
+        proof: &OpeningProof,
+    ) -> Result<(), ZKPError> {
+        self.ctx.check_element(commitment.0)?;
+        self.ctx.check_scalar(proof.c)?;
+        self.ctx.check_scalar(proof.s_m)?;
+        self.ctx.check_scalar(proof.s_r)?;
+
+        let t = self.ctx.mul(
+            self.ctx.multi_pow(&[proof.s_m, proof.s_r])?,
+            self.ctx.pow(commitment.0, proof.c),
+        );
+        self.append_to_transcript(transcript, commitment);
+        transcript.append_i64(b"t", t);
+
+        if transcript.challenge_scalar(b"c", self.ctx.q()) != proof.c {
+            return Err(ZKPError::VerificationFailed);
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        chaum_pedersen::ChaumPedersenProtocol,
+        test_utils::{test_context, TEST_G, TEST_H, TEST_P, TEST_Q},
+    };
+    use rand_chacha::ChaCha20Rng;
+    use rand_core::SeedableRng;
+
+    const ACCOUNT_TIER: i64 = 3;
+    const SECRET: i64 = 9;
+    const NONCE_K: i64 = 27;
+
+    fn init() -> (PedersenProtocol, ChaCha20Rng) {
+        (
+            PedersenProtocol::new(test_context()).unwrap(),
+            ChaCha20Rng::seed_from_u64(42),
+        )
+    }
+
+    fn transcript() -> Transcript {
+        let mut transcript = Transcript::new(b"test");
+        transcript.append_message(b"username", b"alice");
+        transcript
+    }
+
+    #[test]
+    fn should_open_commitment() {
+        let (pedersen, mut rng) = init();
+        let (commitment, opening) = pedersen.commit(&mut rng, ACCOUNT_TIER).unwrap();
+        assert_eq!(pedersen.open(commitment, &opening), Ok(()));
+        assert_eq!(
+            pedersen.open(
+                commitment,
+                &Opening {
+                    m: ACCOUNT_TIER + 1,
+                    ..opening
+                }
+            ),
+            Err(ZKPError::VerificationFailed)
+        );
+    }
+
+    #[test]
+    fn should_add_commitments() {
+        let (pedersen, mut rng) = init();
+        let (left, left_opening) = pedersen.commit(&mut rng, ACCOUNT_TIER).unwrap();
+        let (right, right_opening) = pedersen.commit(&mut rng, TEST_Q - 1).unwrap();
+        let sum = pedersen.add(left, right).unwrap();
+        let opening = pedersen.add_openings(&left_opening, &right_opening);
+        assert_eq!(opening.m, ACCOUNT_TIER - 1);
+        assert_eq!(pedersen.open(sum, &opening), Ok(()));
+    }
+
+    #[test]
+    fn should_prove_knowledge_of_opening() {
+        let (pedersen, mut rng) = init();
+        let (commitment, opening) = pedersen.commit(&mut rng, ACCOUNT_TIER).unwrap();
+        let proof = pedersen
+            .prove_opening(&mut rng, &mut transcript(), commitment, &opening)
+            .unwrap();
+        assert_eq!(
+            pedersen.verify_opening(&mut transcript(), commitment, &proof),
+            Ok(())
+        );
+
+        let (other, _) = pedersen.commit(&mut rng, ACCOUNT_TIER).unwrap();
+        assert_eq!(
+            pedersen.verify_opening(&mut transcript(), other, &proof),
+            Err(ZKPError::VerificationFailed)
+        );
+        assert_eq!(
+            pedersen.verify_opening(&mut Transcript::new(b"test"), commitment, &proof),
+            Err(ZKPError::VerificationFailed)
+        );
+    }
+
+    #[test]
+    fn should_fold_commitment_into_login_proof() {
+        let (pedersen, mut rng) = init();
+        let zkp = ChaumPedersenProtocol::new(test_context());
+        let ys = zkp.calculate_multi_registration_data(SECRET).unwrap();
+        let (commitment, opening) = pedersen.commit(&mut rng, ACCOUNT_TIER).unwrap();
+
+        let mut prover = transcript();
+        let opening_proof = pedersen
+            .prove_opening(&mut rng, &mut prover, commitment, &opening)
+            .unwrap();
+        let login_proof = zkp.create_proof(&mut prover, SECRET, NONCE_K).unwrap();
+
+        let mut verifier = transcript();
+        assert_eq!(
+            pedersen.verify_opening(&mut verifier, commitment, &opening_proof),
+            Ok(())
+        );
+        assert_eq!(zkp.verify_proof(&mut verifier, &ys, &login_proof), Ok(()));
+
+        // The login proof is bound to the commitment proven before it.
+        assert_eq!(
+            zkp.verify_proof(&mut transcript(), &ys, &login_proof),
+            Err(ZKPError::VerificationFailed)
+        );
+    }
+
+    #[test]
+    fn should_require_two_bases() {
+        let ctx = Context::with_bases(alloc::vec![TEST_G, TEST_H, 25], TEST_P, TEST_Q).unwrap();
+        assert!(matches!(
+            PedersenProtocol::new(ctx),
+            Err(ZKPError::LengthMismatch { .. })
+        ));
+    }
+}
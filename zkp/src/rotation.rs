@@ -0,0 +1,206 @@
+use alloc::vec::Vec;
+
+use crate::{
+    chaum_pedersen::{ChaumPedersenProtocol, Context},
+    transcript::Transcript,
+    ZKPError,
+};
+
+/// Proof of knowledge of both the current secret `x` and the new secret `x'`.
+///
+/// Both Chaum-Pedersen statements share a single challenge, so the proof cannot be assembled from
+/// two independent proofs.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RotationProof {
+    pub c: i64,
+    pub s: i64,
+    pub new_s: i64,
+}
+
+/// Two Chaum-Pedersen statements over the group arithmetic of [`ChaumPedersenProtocol`].
+pub struct RotationProtocol {
+    zkp: ChaumPedersenProtocol,
+}
+
+/// Starts the transcript binding a rotation proof to the rotated account and the version of its
+/// current key, so a proof cannot be replayed once the key has been rotated, even back to the same
+/// public keys.
+pub fn rotation_transcript(username: &str, key_version: u64) -> Transcript {
+    let mut transcript = Transcript::new(b"zkp-auth/rotate-credentials");
+    transcript.append_message(b"username", username.as_bytes());
+    transcript.append_message(b"key-version", &key_version.to_be_bytes());
+    transcript
+}
+
+impl RotationProtocol {
+    pub fn new(ctx: Context) -> Self {
+        Self {
+            zkp: ChaumPedersenProtocol::new(ctx),
+        }
+    }
+
+    fn append_to_transcript(
+        &self,
+        transcript: &mut Transcript,
+        (ys, new_ys): (&[i64], &[i64]),
+        (rs, new_rs): (&[i64], &[i64]),
+    ) {
+        self.zkp.ctx().append_to_transcript(transcript);
+        transcript.append_i64s(b"y", ys);
+        transcript.append_i64s(b"new-y", new_ys);
+        transcript.append_i64s(b"r", rs);
+        transcript.append_i64s(b"new-r", new_rs);
+    }
+
+    /// Calculates the new public keys `y_i' = g_i^x'` to register.
+    pub fn calculate_new_registration_data(&self, new_x: i64) -> Vec<i64> {
+        self.zkp.public_keys(new_x)
+    }
+
+    pub fn create_proof(
+        &self,
+        transcript: &mut Transcript,
+        (x, k): (i64, i64),
+        (new_x, new_k): (i64, i64),
+    ) -> RotationProof {
+        let ys = self.zkp.public_keys(x);
+        let new_ys = self.zkp.public_keys(new_x);
+        let rs = self.zkp.public_keys(k);
+        let new_rs = self.zkp.public_keys(new_k);
+        self.append_to_transcript(transcript, (&ys, &new_ys), (&rs, &new_rs));
+
+        let c = transcript.challenge_scalar(b"c", self.zkp.ctx().q());
+        RotationProof {
+            c,
+            s: self.zkp.ctx().response(k, c, x),
+            new_s: self.zkp.ctx().response(new_k, c, new_x),
+        }
+    }
+
+    pub fn verify_proof(
+        &self,
+        transcript: &mut Transcript,
+        ys: &[i64],
+        new_ys: &[i64],
+        proof: &RotationProof,
+    ) -> Result<(), ZKPError> {
+        self.zkp.ctx().check_elements(ys)?;
+        self.zkp.ctx().check_elements(new_ys)?;
+        self.zkp.ctx().check_scalar(proof.c)?;
+        self.zkp.ctx().check_scalar(proof.s)?;
+        self.zkp.ctx().check_scalar(proof.new_s)?;
+
+        let rs = self
+            .zkp
+            .calculate_multi_verification_data(ys, proof.c, proof.s);
+        let new_rs = self
+            .zkp
+            .calculate_multi_verification_data(new_ys, proof.c, proof.new_s);
+        self.append_to_transcript(transcript, (ys, new_ys), (&rs, &new_rs));
+
+        if transcript.challenge_scalar(b"c", self.zkp.ctx().q()) != proof.c {
+            return Err(ZKPError::VerificationFailed);
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ZKP_G: i64 = 4;
+    const ZKP_H: i64 = 9;
+    const ZKP_P: i64 = 20123;
+    const ZKP_Q: i64 = 10061;
+    const SECRET: (i64, i64) = (9, 27);
+    const NEW_SECRET: (i64, i64) = (11, 31);
+
+    fn init() -> RotationProtocol {
+        RotationProtocol::new(Context::new(ZKP_G, ZKP_H, ZKP_P, ZKP_Q).unwrap())
+    }
+
+    fn transcript() -> Transcript {
+        rotation_transcript("bidzyyys", 0)
+    }
+
+    #[test]
+    fn should_accept_valid_rotation_proof() {
+        let zkp = init();
+        let ys = zkp.calculate_new_registration_data(SECRET.0);
+        let new_ys = zkp.calculate_new_registration_data(NEW_SECRET.0);
+        let proof = zkp.create_proof(&mut transcript(), SECRET, NEW_SECRET);
+        assert!(zkp
+            .verify_proof(&mut transcript(), &ys, &new_ys, &proof)
+            .is_ok());
+    }
+
+    #[test]
+    fn should_reject_rotation_without_current_secret() {
+        let zkp = init();
+        let ys = zkp.calculate_new_registration_data(SECRET.0);
+        let new_ys = zkp.calculate_new_registration_data(NEW_SECRET.0);
+        let proof = zkp.create_proof(&mut transcript(), (SECRET.0 + 1, SECRET.1), NEW_SECRET);
+        assert_eq!(
+            zkp.verify_proof(&mut transcript(), &ys, &new_ys, &proof),
+            Err(ZKPError::VerificationFailed)
+        );
+    }
+
+    #[test]
+    fn should_reject_rotation_to_other_public_keys() {
+        let zkp = init();
+        let ys = zkp.calculate_new_registration_data(SECRET.0);
+        let other_ys = zkp.calculate_new_registration_data(NEW_SECRET.0 + 1);
+        let proof = zkp.create_proof(&mut transcript(), SECRET, NEW_SECRET);
+        assert_eq!(
+            zkp.verify_proof(&mut transcript(), &ys, &other_ys, &proof),
+            Err(ZKPError::VerificationFailed)
+        );
+    }
+
+    #[test]
+    fn should_reject_mismatched_number_of_public_keys() {
+        let zkp = init();
+        let ys = zkp.calculate_new_registration_data(SECRET.0);
+        let proof = zkp.create_proof(&mut transcript(), SECRET, NEW_SECRET);
+        assert_eq!(
+            zkp.verify_proof(&mut transcript(), &ys, &ys[..1], &proof),
+            Err(ZKPError::LengthMismatch {
+                expected: 2,
+                actual: 1
+            })
+        );
+    }
+
+    #[test]
+    fn should_bind_rotation_proof_to_username() {
+        let zkp = init();
+        let ys = zkp.calculate_new_registration_data(SECRET.0);
+        let new_ys = zkp.calculate_new_registration_data(NEW_SECRET.0);
+        let proof = zkp.create_proof(&mut transcript(), SECRET, NEW_SECRET);
+        assert_eq!(
+            zkp.verify_proof(&mut rotation_transcript("mallory", 0), &ys, &new_ys, &proof),
+            Err(ZKPError::VerificationFailed)
+        );
+    }
+
+    #[test]
+    fn should_bind_rotation_proof_to_key_version() {
+        let zkp = init();
+        let ys = zkp.calculate_new_registration_data(SECRET.0);
+        let new_ys = zkp.calculate_new_registration_data(NEW_SECRET.0);
+        let proof = zkp.create_proof(&mut transcript(), SECRET, NEW_SECRET);
+        assert_eq!(
+            zkp.verify_proof(
+                &mut rotation_transcript("bidzyyys", 2),
+                &ys,
+                &new_ys,
+                &proof
+            ),
+            Err(ZKPError::VerificationFailed)
+        );
+    }
+}
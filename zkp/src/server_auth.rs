@@ -0,0 +1,172 @@
+use alloc::vec::Vec;
+
+use rand_core::{CryptoRng, RngCore};
+
+use crate::{
+    chaum_pedersen::{ChaumPedersenProtocol, Context, Proof},
+    transcript::Transcript,
+    ZKPError,
+};
+
+/// Long-term key pair of the server: the secret `x` and the public key `y_i = g_i^x`.
+pub struct ServerKey {
+    x: i64,
+    public_key: Vec<i64>,
+}
+
+/// Lets the server prove its identity to clients.
+///
+/// The server answers every authentication challenge with a non-interactive proof of knowledge of
+/// its secret, bound to the user, the commitments, the key shares and the challenge. The proof
+/// is worthless for any other login, so an impostor can neither forge nor replay it.
+pub struct ServerAuthProtocol {
+    ctx: Context,
+    zkp: ChaumPedersenProtocol,
+}
+
+/// Starts the transcript of a challenge the server proves its identity over.
+pub fn challenge_transcript(
+    username: &str,
+    commitments: &[i64],
+    key_shares: Option<(i64, i64)>,
+    auth_id: &str,
+    c: i64,
+) -> Transcript {
+    let mut transcript = Transcript::new(b"zkp-auth/server-challenge");
+    transcript.append_message(b"username", username.as_bytes());
+    transcript.append_i64s(b"commitments", commitments);
+    if let Some((client_share, server_share)) = key_shares {
+        transcript.append_i64(b"client-share", client_share);
+        transcript.append_i64(b"server-share", server_share);
+    }
+    transcript.append_message(b"auth-id", auth_id.as_bytes());
+    transcript.append_i64(b"c", c);
+    transcript
+}
+
+impl ServerKey {
+    pub fn public_key(&self) -> &[i64] {
+        &self.public_key
+    }
+}
+
+impl ServerAuthProtocol {
+    pub fn new(ctx: Context) -> Self {
+        Self {
+            zkp: ChaumPedersenProtocol::new(ctx.clone()),
+            ctx,
+        }
+    }
+
+    pub fn key_from_secret(&self, x: i64) -> Result<ServerKey, ZKPError> {
+        self.ctx.check_scalar(x)?;
+        if x == 0 {
+            return Err(ZKPError::InvalidParameters {
+                reason: "server secret must not be zero",
+            });
+        }
+
+        Ok(ServerKey {
+            x,
+            public_key: self.zkp.calculate_multi_registration_data(x)?,
+        })
+    }
+
+    pub fn generate_key<R: RngCore + CryptoRng>(&self, rng: &mut R) -> ServerKey {
+        loop {
+            if let Ok(key) = self.key_from_secret(self.ctx.random_scalar(rng)) {
+                return key;
+            }
+        }
+    }
+
+    /// Proves knowledge of the server secret over the transcript.
+    pub fn prove<R: RngCore + CryptoRng>(
+        &self,
+        rng: &mut R,
+        key: &ServerKey,
+        transcript: &mut Transcript,
+    ) -> Result<Proof, ZKPError> {
+        let k = self.ctx.random_scalar(rng);
+        self.zkp.create_proof(transcript, key.x, k)
+    }
+
+    /// Checks the proof against the pinned public key of the server.
+    pub fn verify(
+        &self,
+        public_key: &[i64],
+        transcript: &mut Transcript,
+        proof: &Proof,
+    ) -> Result<(), ZKPError> {
+        self.zkp.verify_proof(transcript, public_key, proof)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand_chacha::ChaCha20Rng;
+    use rand_core::SeedableRng;
+
+    const ZKP_G: i64 = 4;
+    const ZKP_H: i64 = 9;
+    const ZKP_P: i64 = 20123;
+    const ZKP_Q: i64 = 10061;
+    const USERNAME: &str = "alice";
+    const COMMITMENTS: [i64; 2] = [16, 81];
+    const AUTH_ID: &str = "auth";
+    const CHALLENGE_C: i64 = 4321;
+
+    fn init() -> (ServerAuthProtocol, ChaCha20Rng) {
+        let ctx = Context::new(ZKP_G, ZKP_H, ZKP_P, ZKP_Q).unwrap();
+        (ServerAuthProtocol::new(ctx), ChaCha20Rng::seed_from_u64(42))
+    }
+
+    fn transcript(c: i64) -> Transcript {
+        challenge_transcript(USERNAME, &COMMITMENTS, Some((25, 36)), AUTH_ID, c)
+    }
+
+    #[test]
+    fn should_accept_proof_of_genuine_server() {
+        let (server_auth, mut rng) = init();
+        let key = server_auth.generate_key(&mut rng);
+        let proof = server_auth
+            .prove(&mut rng, &key, &mut transcript(CHALLENGE_C))
+            .unwrap();
+        assert_eq!(
+            server_auth.verify(key.public_key(), &mut transcript(CHALLENGE_C), &proof),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn should_reject_impostor_and_replayed_proofs() {
+        let (server_auth, mut rng) = init();
+        let key = server_auth.key_from_secret(9).unwrap();
+        let impostor = server_auth.key_from_secret(10).unwrap();
+
+        let forged = server_auth
+            .prove(&mut rng, &impostor, &mut transcript(CHALLENGE_C))
+            .unwrap();
+        assert_eq!(
+            server_auth.verify(key.public_key(), &mut transcript(CHALLENGE_C), &forged),
+            Err(ZKPError::VerificationFailed)
+        );
+
+        let proof = server_auth
+            .prove(&mut rng, &key, &mut transcript(CHALLENGE_C))
+            .unwrap();
+        assert_eq!(
+            server_auth.verify(key.public_key(), &mut transcript(CHALLENGE_C + 1), &proof),
+            Err(ZKPError::VerificationFailed)
+        );
+    }
+
+    #[test]
+    fn should_reject_invalid_server_secret() {
+        let (server_auth, _rng) = init();
+        for x in [0, ZKP_Q] {
+            assert!(server_auth.key_from_secret(x).is_err());
+        }
+    }
+}
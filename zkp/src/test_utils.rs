@@ -0,0 +1,45 @@
+use rand_core::{CryptoRng, RngCore};
+
+use crate::chaum_pedersen::Context;
+
+/// Small group of order `q = 10061` modulo `p = 20123`, fast enough for unit tests.
+pub const TEST_G: i64 = 4;
+pub const TEST_H: i64 = 9;
+pub const TEST_P: i64 = 20123;
+pub const TEST_Q: i64 = 10061;
+
+pub fn test_context() -> Context {
+    Context::new(TEST_G, TEST_H, TEST_P, TEST_Q).expect("test group parameters are valid")
+}
+
+/// Generator whose every draw fails, e.g. to test the handling of `ZKPError::RngFailure`.
+pub struct FailingRng;
+
+impl RngCore for FailingRng {
+    fn next_u32(&mut self) -> u32 {
+        let mut bytes = [0; 4];
+        self.fill_bytes(&mut bytes);
+        u32::from_le_bytes(bytes)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        let mut bytes = [0; 8];
+        self.fill_bytes(&mut bytes);
+        u64::from_le_bytes(bytes)
+    }
+
+    /// Panics, as the infallible draws cannot report the failure. Code under test has to use
+    /// `try_fill_bytes` for `FailingRng` to exercise its error handling.
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        if let Err(e) = self.try_fill_bytes(dest) {
+            panic!("FailingRng cannot draw infallibly: {e}");
+        }
+    }
+
+    fn try_fill_bytes(&mut self, _dest: &mut [u8]) -> Result<(), rand_core::Error> {
+        let code = core::num::NonZeroU32::new(rand_core::Error::CUSTOM_START).unwrap();
+        Err(rand_core::Error::from(code))
+    }
+}
+
+impl CryptoRng for FailingRng {}
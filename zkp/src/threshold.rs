@@ -0,0 +1,320 @@
+use alloc::vec::Vec;
+
+use rand_core::{CryptoRng, RngCore};
+
+use crate::{
+    chaum_pedersen::Context,
+    math::{mod_add, mod_inv, mod_mul, mod_sub},
+    ZKPError,
+};
+
+/// Shamir share `f(index)` of the secret `x = f(0)` over `Z_q`.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Share {
+    pub index: i64,
+    pub value: i64,
+}
+
+/// Nonce of a single login attempt; it is consumed by [`Participant::respond`] so it can never
+/// be used for two challenges.
+pub struct SigningNonce(i64);
+
+/// Threshold variant of the Chaum-Pedersen prover.
+///
+/// The secret `x` is split with a degree `t - 1` polynomial, so any `t` share holders can log in
+/// together while fewer learn nothing about `x`. Every signer commits to its own nonce `k_i`, the
+/// commitments multiply to `r_j = g_j^k` with `k = sum(k_i)` and the partial responses
+/// `s_i = k_i - c * l_i * x_i` add up to the ordinary response `s = k - c * x`, where `l_i` is the
+/// Lagrange coefficient of the signer.
+pub struct ThresholdProtocol {
+    ctx: Context,
+}
+
+/// Holder of one share, e.g. a phone, a laptop or a hardware key.
+pub struct Participant {
+    share: Share,
+}
+
+impl ThresholdProtocol {
+    pub fn new(ctx: Context) -> Self {
+        Self { ctx }
+    }
+
+    /// Splits `x` into `shares` shares with indices `1..=shares`, any `threshold` of which
+    /// recover it.
+    pub fn split_secret<R: RngCore + CryptoRng>(
+        &self,
+        rng: &mut R,
+        x: i64,
+        threshold: usize,
+        shares: usize,
+    ) -> Result<Vec<Share>, ZKPError> {
+        if threshold == 0 || threshold > shares {
+            return Err(ZKPError::InvalidShares {
+                reason: "threshold must be between 1 and the number of shares",
+            });
+        }
+        if shares as i128 >= self.ctx.q() as i128 {
+            return Err(ZKPError::InvalidShares {
+                reason: "number of shares must be lower than q",
+            });
+        }
+
+        let mut coefficients = Vec::with_capacity(threshold);
+        coefficients.push(x.rem_euclid(self.ctx.q()));
+        coefficients.extend((1..threshold).map(|_| self.ctx.random_scalar(rng)));
+
+        Ok((1..=shares as i64)
+            .map(|index| Share {
+                index,
+                value: self.evaluate(&coefficients, index),
+            })
+            .collect())
+    }
+
+    /// Recovers `x` from at least `threshold` of the shares it was split into.
+    pub fn recover_secret(&self, shares: &[Share], threshold: usize) -> Result<i64, ZKPError> {
+        if shares.is_empty() || shares.len() < threshold {
+            return Err(ZKPError::InvalidShares {
+                reason: "fewer shares than the threshold",
+            });
+        }
+        let indices: Vec<i64> = shares.iter().map(|share| share.index).collect();
+        shares.iter().try_fold(0, |acc, share| {
+            let coefficient = self.lagrange_coefficient(share.index, &indices)?;
+            Ok(mod_add(
+                acc,
+                mod_mul(coefficient, share.value, self.ctx.q()),
+                self.ctx.q(),
+            ))
+        })
+    }
+
+    /// Calculates the Lagrange coefficient `l_i = prod(j / (j - i))` at zero for the share
+    /// `index` within the signer set `indices`.
+    pub fn lagrange_coefficient(&self, index: i64, indices: &[i64]) -> Result<i64, ZKPError> {
+        self.check_indices(indices)?;
+        if !indices.contains(&index) {
+            return Err(ZKPError::InvalidShares {
+                reason: "share is not part of the signer set",
+            });
+        }
+
+        let q = self.ctx.q();
+        let (numerator, denominator) = indices.iter().filter(|other| **other != index).fold(
+            (1, 1),
+            |(numerator, denominator), other| {
+                (
+                    mod_mul(numerator, *other, q),
+                    mod_mul(denominator, mod_sub(*other, index, q), q),
+                )
+            },
+        );
+
+        Ok(mod_mul(numerator, mod_inv(denominator, q), q))
+    }
+
+    /// Multiplies the commitments of all signers into the commitments `r_j = g_j^k`.
+    pub fn aggregate_commitments(&self, commitments: &[Vec<i64>]) -> Result<Vec<i64>, ZKPError> {
+        let mut aggregated = alloc::vec![1; self.ctx.bases().len()];
+        for commitment in commitments {
+            self.ctx.check_elements(commitment)?;
+            for (acc, r) in aggregated.iter_mut().zip(commitment) {
+                *acc = self.ctx.mul(*acc, *r);
+            }
+        }
+
+        Ok(aggregated)
+    }
+
+    /// Adds the partial responses of all signers into the response `s = k - c * x`.
+    pub fn aggregate_responses(&self, responses: &[i64]) -> i64 {
+        responses
+            .iter()
+            .fold(0, |acc, s| mod_add(acc, *s, self.ctx.q()))
+    }
+
+    fn evaluate(&self, coefficients: &[i64], index: i64) -> i64 {
+        coefficients.iter().rev().fold(0, |acc, coefficient| {
+            mod_add(
+                mod_mul(acc, index, self.ctx.q()),
+                *coefficient,
+                self.ctx.q(),
+            )
+        })
+    }
+
+    fn check_indices(&self, indices: &[i64]) -> Result<(), ZKPError> {
+        let valid = indices.iter().enumerate().all(|(position, index)| {
+            (1..self.ctx.q()).contains(index) && !indices[..position].contains(index)
+        });
+        if !valid {
+            return Err(ZKPError::InvalidShares {
+                reason: "share indices must be distinct and in [1, q)",
+            });
+        }
+
+        Ok(())
+    }
+}
+
+impl Participant {
+    pub fn new(share: Share) -> Self {
+        Self { share }
+    }
+
+    pub fn index(&self) -> i64 {
+        self.share.index
+    }
+
+    /// Draws a fresh nonce `k_i` and returns it together with the commitments `g_j^k_i`.
+    pub fn commit<R: RngCore + CryptoRng>(
+        &self,
+        protocol: &ThresholdProtocol,
+        rng: &mut R,
+    ) -> (SigningNonce, Vec<i64>) {
+        let k = protocol.ctx.random_scalar(rng);
+        let commitments = protocol
+            .ctx
+            .bases()
+            .iter()
+            .map(|base| protocol.ctx.pow(*base, k))
+            .collect();
+
+        (SigningNonce(k), commitments)
+    }
+
+    /// Calculates the partial response `s_i = k_i - c * l_i * x_i` to the challenge `c`.
+    pub fn respond(
+        &self,
+        protocol: &ThresholdProtocol,
+        nonce: SigningNonce,
+        signers: &[i64],
+        c: i64,
+    ) -> Result<i64, ZKPError> {
+        protocol.ctx.check_scalar(c)?;
+        let coefficient = protocol.lagrange_coefficient(self.share.index, signers)?;
+        let x = mod_mul(coefficient, self.share.value, protocol.ctx.q());
+
+        Ok(protocol.ctx.response(nonce.0, c, x))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chaum_pedersen::ChaumPedersenProtocol;
+    use rand_chacha::ChaCha20Rng;
+    use rand_core::SeedableRng;
+
+    const ZKP_G: i64 = 4;
+    const ZKP_H: i64 = 9;
+    const ZKP_P: i64 = 20123;
+    const ZKP_Q: i64 = 10061;
+    const SECRET: i64 = 9;
+    const CHALLENGE_C: i64 = 1234;
+
+    fn ctx() -> Context {
+        Context::new(ZKP_G, ZKP_H, ZKP_P, ZKP_Q).unwrap()
+    }
+
+    fn rng() -> ChaCha20Rng {
+        ChaCha20Rng::seed_from_u64(42)
+    }
+
+    /// Runs a login of the given share holders against an ordinary Chaum-Pedersen verifier.
+    fn simulate_login(signers: &[&Participant]) -> Result<(), ZKPError> {
+        let threshold = ThresholdProtocol::new(ctx());
+        let verifier = ChaumPedersenProtocol::new(ctx());
+        let ys = verifier.calculate_multi_registration_data(SECRET).unwrap();
+        let mut rng = rng();
+
+        let (nonces, commitments): (Vec<_>, Vec<_>) = signers
+            .iter()
+            .map(|signer| signer.commit(&threshold, &mut rng))
+            .unzip();
+        let rs = threshold.aggregate_commitments(&commitments)?;
+
+        let indices: Vec<i64> = signers.iter().map(|signer| signer.index()).collect();
+        let responses = signers
+            .iter()
+            .zip(nonces)
+            .map(|(signer, nonce)| signer.respond(&threshold, nonce, &indices, CHALLENGE_C))
+            .collect::<Result<Vec<i64>, ZKPError>>()?;
+        let s = threshold.aggregate_responses(&responses);
+
+        verifier.verify_multi_auth_challenge(&ys, &rs, CHALLENGE_C, s)
+    }
+
+    fn devices() -> Vec<Participant> {
+        ThresholdProtocol::new(ctx())
+            .split_secret(&mut rng(), SECRET, 2, 3)
+            .unwrap()
+            .into_iter()
+            .map(Participant::new)
+            .collect()
+    }
+
+    #[test]
+    fn should_recover_secret_from_any_threshold_of_shares() {
+        let threshold = ThresholdProtocol::new(ctx());
+        let shares = threshold.split_secret(&mut rng(), SECRET, 2, 3).unwrap();
+        for pair in [[0, 1], [0, 2], [1, 2]] {
+            let subset = [shares[pair[0]].clone(), shares[pair[1]].clone()];
+            assert_eq!(threshold.recover_secret(&subset, 2), Ok(SECRET));
+        }
+        assert_eq!(threshold.recover_secret(&shares, 2), Ok(SECRET));
+    }
+
+    #[test]
+    fn should_reject_fewer_shares_than_threshold() {
+        let threshold = ThresholdProtocol::new(ctx());
+        let shares = threshold.split_secret(&mut rng(), SECRET, 2, 3).unwrap();
+        for (shares, threshold_count) in [(&shares[..1], 2), (&[][..], 0), (&[][..], 2)] {
+            assert!(matches!(
+                threshold.recover_secret(shares, threshold_count),
+                Err(ZKPError::InvalidShares { .. })
+            ));
+        }
+    }
+
+    #[test]
+    fn should_accept_login_of_any_two_devices() {
+        let devices = devices();
+        let (phone, laptop, hardware_key) = (&devices[0], &devices[1], &devices[2]);
+        for signers in [
+            [phone, laptop],
+            [phone, hardware_key],
+            [laptop, hardware_key],
+        ] {
+            assert_eq!(simulate_login(&signers), Ok(()));
+        }
+        assert_eq!(simulate_login(&[phone, laptop, hardware_key]), Ok(()));
+    }
+
+    #[test]
+    fn should_reject_login_of_single_device() {
+        let devices = devices();
+        for device in &devices {
+            assert_eq!(simulate_login(&[device]), Err(ZKPError::VerificationFailed));
+        }
+    }
+
+    #[test]
+    fn should_reject_invalid_sharing_parameters() {
+        let threshold = ThresholdProtocol::new(ctx());
+        for (t, n) in [(0, 3), (4, 3)] {
+            assert!(matches!(
+                threshold.split_secret(&mut rng(), SECRET, t, n),
+                Err(ZKPError::InvalidShares { .. })
+            ));
+        }
+        for indices in [&[1, 1][..], &[0, 2], &[1, ZKP_Q]] {
+            assert!(matches!(
+                threshold.lagrange_coefficient(indices[1], indices),
+                Err(ZKPError::InvalidShares { .. })
+            ));
+        }
+    }
+}
@@ -0,0 +1,120 @@
+use rand_core::{CryptoRng, RngCore};
+use sha2::{Digest, Sha256};
+
+use crate::ZKPError;
+
+/// Fiat-Shamir transcript in the spirit of Merlin.
+///
+/// Every message is absorbed together with its label and length, so different sequences of
+/// messages can never produce the same state. Challenges are squeezed from the running hash and
+/// fed back into it, so consecutive challenges are bound to each other.
+#[derive(Clone)]
+pub struct Transcript {
+    hasher: Sha256,
+}
+
+impl Transcript {
+    pub fn new(label: &'static [u8]) -> Self {
+        let mut transcript = Self {
+            hasher: Sha256::new(),
+        };
+        transcript.append_message(b"dom-sep", label);
+        transcript
+    }
+
+    pub fn append_message(&mut self, label: &'static [u8], message: &[u8]) {
+        self.hasher.update((label.len() as u64).to_be_bytes());
+        self.hasher.update(label);
+        self.hasher.update((message.len() as u64).to_be_bytes());
+        self.hasher.update(message);
+    }
+
+    pub fn append_i64(&mut self, label: &'static [u8], value: i64) {
+        self.append_message(label, &value.to_be_bytes());
+    }
+
+    pub fn append_i64s(&mut self, label: &'static [u8], values: &[i64]) {
+        self.append_i64(label, values.len() as i64);
+        for value in values {
+            self.append_i64(label, *value);
+        }
+    }
+
+    /// Appends fresh randomness, so the following challenges cannot be predicted from the
+    /// public messages alone.
+    pub fn append_verifier_nonce<R: RngCore + CryptoRng>(
+        &mut self,
+        rng: &mut R,
+    ) -> Result<(), ZKPError> {
+        let mut nonce = [0u8; 32];
+        rng.try_fill_bytes(&mut nonce)
+            .map_err(|_| ZKPError::RngFailure)?;
+        self.append_message(b"verifier-nonce", &nonce);
+        Ok(())
+    }
+
+    /// Squeezes 32 challenge bytes, e.g. key material.
+    pub fn challenge_bytes(&mut self, label: &'static [u8]) -> [u8; 32] {
+        self.append_message(b"challenge", label);
+        let digest = self.hasher.clone().finalize();
+        self.hasher.update(digest);
+        digest.into()
+    }
+
+    /// Squeezes a challenge scalar from `[0, q)`.
+    pub fn challenge_scalar(&mut self, label: &'static [u8], q: i64) -> i64 {
+        let digest = self.challenge_bytes(label);
+        let wide = u128::from_be_bytes(digest[..16].try_into().expect("digest has 32 bytes"));
+        (wide % q as u128) as i64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::FailingRng;
+
+    const ZKP_Q: i64 = 10061;
+
+    #[test]
+    fn should_derive_same_challenge_for_same_messages() {
+        let mut first = Transcript::new(b"test");
+        let mut second = Transcript::new(b"test");
+        first.append_i64(b"y", 545);
+        second.append_i64(b"y", 545);
+        assert_eq!(
+            first.challenge_scalar(b"c", ZKP_Q),
+            second.challenge_scalar(b"c", ZKP_Q)
+        );
+    }
+
+    #[test]
+    fn should_bind_challenge_to_labels_and_messages() {
+        let challenge = |label: &'static [u8], message: &[u8]| {
+            let mut transcript = Transcript::new(b"test");
+            transcript.append_message(label, message);
+            transcript.challenge_scalar(b"c", i64::MAX)
+        };
+        assert_ne!(challenge(b"y", b"545"), challenge(b"r", b"545"));
+        assert_ne!(challenge(b"y", b"545"), challenge(b"y", b"546"));
+        assert_ne!(challenge(b"y", b"545"), challenge(b"y5", b"45"));
+    }
+
+    #[test]
+    fn should_derive_different_consecutive_challenges() {
+        let mut transcript = Transcript::new(b"test");
+        let first = transcript.challenge_scalar(b"c", i64::MAX);
+        let second = transcript.challenge_scalar(b"c", i64::MAX);
+        assert_ne!(first, second);
+        assert!((0..ZKP_Q).contains(&transcript.challenge_scalar(b"c", ZKP_Q)));
+    }
+
+    #[test]
+    fn should_report_rng_failure() {
+        let mut transcript = Transcript::new(b"test");
+        assert_eq!(
+            transcript.append_verifier_nonce(&mut FailingRng),
+            Err(ZKPError::RngFailure)
+        );
+    }
+}
@@ -0,0 +1,15 @@
+use std::env;
+use std::path::PathBuf;
+
+fn main() {
+    let crate_dir = env::var("CARGO_MANIFEST_DIR").expect("Cargo sets CARGO_MANIFEST_DIR");
+    let out_dir = PathBuf::from(env::var("OUT_DIR").expect("Cargo sets OUT_DIR"));
+
+    // The committed `include/zkp.h` is compared against this one by `tests/header.rs`.
+    cbindgen::generate(&crate_dir)
+        .expect("Unable to generate C bindings")
+        .write_to_file(out_dir.join("zkp.h"));
+
+    println!("cargo:rerun-if-changed=src/lib.rs");
+    println!("cargo:rerun-if-changed=cbindgen.toml");
+}
@@ -0,0 +1,171 @@
+#ifndef ZKP_H
+#define ZKP_H
+
+/* Generated with cbindgen from zkp_ffi/src/lib.rs. Do not modify manually. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/**
+ * Status codes are part of the C ABI: new ones are appended and retired ones are never reused.
+ */
+typedef enum ZkpStatus {
+  ZKP_STATUS_OK = 0,
+  ZKP_STATUS_NULL_POINTER = 1,
+  ZKP_STATUS_MALFORMED_ENCODING = 2,
+  ZKP_STATUS_EMPTY_BASES = 3,
+  ZKP_STATUS_LENGTH_MISMATCH = 4,
+  ZKP_STATUS_INVALID_PARAMETERS = 5,
+  ZKP_STATUS_PANIC = 8,
+  ZKP_STATUS_ELEMENT_NOT_IN_SUBGROUP = 9,
+  ZKP_STATUS_SCALAR_OUT_OF_RANGE = 10,
+  ZKP_STATUS_VERIFICATION_FAILED = 11,
+  ZKP_STATUS_RNG_FAILURE = 12,
+  ZKP_STATUS_INVALID_SHARES = 13,
+  ZKP_STATUS_INVALID_BACKUP_SHARE = 14,
+  ZKP_STATUS_INVALID_MNEMONIC = 15,
+  ZKP_STATUS_INVALID_KEYSTORE = 16,
+  ZKP_STATUS_WRONG_PASSWORD = 17,
+  ZKP_STATUS_INVALID_KEY_SHARE = 18,
+} ZkpStatus;
+
+/**
+ * Opaque handle to the group parameters of the protocol.
+ */
+typedef struct ZkpContext ZkpContext;
+
+/**
+ * Byte buffer allocated by this library, release it with [`zkp_buffer_free`].
+ */
+typedef struct ZkpBuffer {
+  uint8_t *data;
+  size_t len;
+} ZkpBuffer;
+
+/**
+ * Creates a context over `bases_len` generators of the order `q` subgroup modulo `p`.
+ *
+ * # Safety
+ *
+ * `bases` must be valid for `bases_len` reads and `out` valid for writes.
+ */
+enum ZkpStatus zkp_context_new(const int64_t *bases,
+                               size_t bases_len,
+                               int64_t p,
+                               int64_t q,
+                               struct ZkpContext **out);
+
+/**
+ * Releases a context created by [`zkp_context_new`], null is ignored.
+ *
+ * # Safety
+ *
+ * `ctx` must be null or a context not released yet.
+ */
+void zkp_context_free(struct ZkpContext *ctx);
+
+/**
+ * Releases a buffer returned by this library, an empty buffer is ignored.
+ *
+ * # Safety
+ *
+ * `buffer` must have been returned by this library and not released yet.
+ */
+void zkp_buffer_free(struct ZkpBuffer buffer);
+
+/**
+ * Draws a uniformly random scalar from `[0, q)` from the operating system generator, e.g. a
+ * secret `x` or the nonce `k` of [`zkp_commitments`]. Use a fresh nonce for every login.
+ *
+ * # Safety
+ *
+ * `ctx` must be a live context and `out` valid for writes.
+ */
+enum ZkpStatus zkp_random_scalar(const struct ZkpContext *ctx, int64_t *out);
+
+/**
+ * Calculates the encoded public keys `y_i = g_i^x`.
+ *
+ * # Safety
+ *
+ * `ctx` must be a live context and `out` valid for writes.
+ */
+enum ZkpStatus zkp_registration_data(const struct ZkpContext *ctx,
+                                     int64_t x,
+                                     struct ZkpBuffer *out);
+
+/**
+ * Calculates the encoded commitments `r_i = g_i^k` to a nonce from [`zkp_random_scalar`].
+ *
+ * # Safety
+ *
+ * `ctx` must be a live context and `out` valid for writes.
+ */
+enum ZkpStatus zkp_commitments(const struct ZkpContext *ctx, int64_t k, struct ZkpBuffer *out);
+
+/**
+ * Calculates the response `s = k - c * x` to the verifier challenge.
+ *
+ * # Safety
+ *
+ * `ctx` must be a live context and `out` valid for writes.
+ */
+enum ZkpStatus zkp_response(const struct ZkpContext *ctx,
+                            int64_t k,
+                            int64_t c,
+                            int64_t x,
+                            int64_t *out);
+
+/**
+ * Checks the response `s` to the challenge `c` against the encoded public keys and commitments.
+ *
+ * # Safety
+ *
+ * `ctx` must be a live context, `ys` and `rs` valid for `ys_len` and `rs_len` reads and `out`
+ * valid for writes.
+ */
+enum ZkpStatus zkp_verify(const struct ZkpContext *ctx,
+                          const uint8_t *ys,
+                          size_t ys_len,
+                          const uint8_t *rs,
+                          size_t rs_len,
+                          int64_t c,
+                          int64_t s,
+                          bool *out);
+
+/**
+ * Creates an encoded non-interactive proof of `x` bound to the caller supplied `context` bytes,
+ * with a fresh nonce from the operating system generator.
+ *
+ * # Safety
+ *
+ * `ctx` must be a live context, `context` valid for `context_len` reads and `out` valid for
+ * writes.
+ */
+enum ZkpStatus zkp_prove(const struct ZkpContext *ctx,
+                         const uint8_t *context,
+                         size_t context_len,
+                         int64_t x,
+                         struct ZkpBuffer *out);
+
+/**
+ * Verifies an encoded proof created by [`zkp_prove`] with the same `context` bytes.
+ *
+ * # Safety
+ *
+ * `ctx` must be a live context, `context`, `ys` and `proof` valid for reads of their lengths and
+ * `out` valid for writes.
+ */
+enum ZkpStatus zkp_verify_proof(const struct ZkpContext *ctx,
+                                const uint8_t *context,
+                                size_t context_len,
+                                const uint8_t *ys,
+                                size_t ys_len,
+                                const uint8_t *proof,
+                                size_t proof_len,
+                                bool *out);
+
+#endif  /* ZKP_H */
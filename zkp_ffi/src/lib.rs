@@ -0,0 +1,478 @@
+//! C bindings for the `zkp` crate.
+//!
+//! Contexts are exposed as opaque handles. Group elements are passed as arrays of 8-byte
+//! big-endian signed integers and proofs as the 16-byte encoding `c || s`. Every function returns
+//! a [`ZkpStatus`] and writes its result through an out pointer. Nonces are drawn from the
+//! operating system generator, never supplied by the caller of [`zkp_prove`].
+
+use std::{
+    panic::{self, UnwindSafe},
+    ptr, slice,
+};
+
+use rand_core::OsRng;
+use zkp::{
+    chaum_pedersen::{ChaumPedersenProtocol, Context, Proof},
+    transcript::Transcript,
+    ZKPError,
+};
+
+const SCALAR_LEN: usize = 8;
+const PROOF_TRANSCRIPT_LABEL: &[u8] = b"zkp-ffi/proof";
+
+/// Status codes are part of the C ABI: new ones are appended and retired ones are never reused.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ZkpStatus {
+    Ok = 0,
+    NullPointer = 1,
+    MalformedEncoding = 2,
+    EmptyBases = 3,
+    LengthMismatch = 4,
+    InvalidParameters = 5,
+    // 6 and 7 were `MathOverflow` and `CastError`.
+    Panic = 8,
+    ElementNotInSubgroup = 9,
+    ScalarOutOfRange = 10,
+    VerificationFailed = 11,
+    RngFailure = 12,
+    InvalidShares = 13,
+    InvalidBackupShare = 14,
+    InvalidMnemonic = 15,
+    InvalidKeystore = 16,
+    WrongPassword = 17,
+    InvalidKeyShare = 18,
+}
+
+impl From<ZKPError> for ZkpStatus {
+    fn from(error: ZKPError) -> Self {
+        match error {
+            ZKPError::InvalidParameters { .. } => ZkpStatus::InvalidParameters,
+            ZKPError::EmptyBases => ZkpStatus::EmptyBases,
+            ZKPError::LengthMismatch { .. } => ZkpStatus::LengthMismatch,
+            ZKPError::ElementNotInSubgroup { .. } => ZkpStatus::ElementNotInSubgroup,
+            ZKPError::ScalarOutOfRange { .. } => ZkpStatus::ScalarOutOfRange,
+            ZKPError::MalformedEncoding { .. } => ZkpStatus::MalformedEncoding,
+            ZKPError::InvalidShares { .. } => ZkpStatus::InvalidShares,
+            ZKPError::InvalidBackupShare { .. } => ZkpStatus::InvalidBackupShare,
+            ZKPError::InvalidMnemonic { .. } => ZkpStatus::InvalidMnemonic,
+            ZKPError::InvalidKeystore { .. } => ZkpStatus::InvalidKeystore,
+            ZKPError::WrongPassword => ZkpStatus::WrongPassword,
+            ZKPError::InvalidKeyShare => ZkpStatus::InvalidKeyShare,
+            ZKPError::VerificationFailed => ZkpStatus::VerificationFailed,
+            ZKPError::RngFailure => ZkpStatus::RngFailure,
+        }
+    }
+}
+
+/// Opaque handle to the group parameters of the protocol.
+pub struct ZkpContext {
+    protocol: ChaumPedersenProtocol,
+}
+
+/// Byte buffer allocated by this library, release it with [`zkp_buffer_free`].
+#[repr(C)]
+pub struct ZkpBuffer {
+    pub data: *mut u8,
+    pub len: usize,
+}
+
+impl ZkpBuffer {
+    fn from_vec(bytes: Vec<u8>) -> Self {
+        let len = bytes.len();
+        let data = Box::into_raw(bytes.into_boxed_slice()) as *mut u8;
+        ZkpBuffer { data, len }
+    }
+}
+
+fn guard<F>(f: F) -> ZkpStatus
+where
+    F: FnOnce() -> Result<(), ZkpStatus> + UnwindSafe,
+{
+    match panic::catch_unwind(f) {
+        Ok(Ok(())) => ZkpStatus::Ok,
+        Ok(Err(status)) => status,
+        Err(_) => ZkpStatus::Panic,
+    }
+}
+
+/// # Safety
+///
+/// `ptr` must be null or valid for writes.
+unsafe fn write<T>(ptr: *mut T, value: T) -> Result<(), ZkpStatus> {
+    if ptr.is_null() {
+        return Err(ZkpStatus::NullPointer);
+    }
+    ptr.write(value);
+    Ok(())
+}
+
+/// # Safety
+///
+/// `ptr` must be null or point to a live context.
+unsafe fn context<'a>(ptr: *const ZkpContext) -> Result<&'a ZkpContext, ZkpStatus> {
+    ptr.as_ref().ok_or(ZkpStatus::NullPointer)
+}
+
+/// # Safety
+///
+/// `data` must be null or valid for `len` reads.
+unsafe fn bytes<'a, T>(data: *const T, len: usize) -> Result<&'a [T], ZkpStatus> {
+    match (data.is_null(), len) {
+        (true, 0) => Ok(&[]),
+        (true, _) => Err(ZkpStatus::NullPointer),
+        (false, _) => Ok(slice::from_raw_parts(data, len)),
+    }
+}
+
+fn encode_scalars(values: &[i64]) -> Vec<u8> {
+    values
+        .iter()
+        .flat_map(|value| value.to_be_bytes())
+        .collect()
+}
+
+fn decode_scalars(bytes: &[u8]) -> Result<Vec<i64>, ZkpStatus> {
+    if !bytes.len().is_multiple_of(SCALAR_LEN) {
+        return Err(ZkpStatus::MalformedEncoding);
+    }
+
+    Ok(bytes
+        .chunks_exact(SCALAR_LEN)
+        .map(|chunk| i64::from_be_bytes(chunk.try_into().expect("chunk of SCALAR_LEN bytes")))
+        .collect())
+}
+
+/// Reports a failed verification through the out parameter rather than the status.
+fn verified(result: Result<(), ZKPError>) -> Result<bool, ZkpStatus> {
+    match result {
+        Ok(()) => Ok(true),
+        Err(ZKPError::VerificationFailed) => Ok(false),
+        Err(error) => Err(error.into()),
+    }
+}
+
+fn proof_transcript(context: &[u8]) -> Transcript {
+    let mut transcript = Transcript::new(PROOF_TRANSCRIPT_LABEL);
+    transcript.append_message(b"context", context);
+    transcript
+}
+
+/// Creates a context over `bases_len` generators of the order `q` subgroup modulo `p`.
+///
+/// # Safety
+///
+/// `bases` must be valid for `bases_len` reads and `out` valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn zkp_context_new(
+    bases: *const i64,
+    bases_len: usize,
+    p: i64,
+    q: i64,
+    out: *mut *mut ZkpContext,
+) -> ZkpStatus {
+    guard(|| {
+        let bases = bytes(bases, bases_len)?.to_vec();
+        let ctx = Context::with_bases(bases, p, q)?;
+        let handle = Box::new(ZkpContext {
+            protocol: ChaumPedersenProtocol::new(ctx),
+        });
+        write(out, Box::into_raw(handle))
+    })
+}
+
+/// Releases a context created by [`zkp_context_new`], null is ignored.
+///
+/// # Safety
+///
+/// `ctx` must be null or a context not released yet.
+#[no_mangle]
+pub unsafe extern "C" fn zkp_context_free(ctx: *mut ZkpContext) {
+    if !ctx.is_null() {
+        drop(Box::from_raw(ctx));
+    }
+}
+
+/// Releases a buffer returned by this library, an empty buffer is ignored.
+///
+/// # Safety
+///
+/// `buffer` must have been returned by this library and not released yet.
+#[no_mangle]
+pub unsafe extern "C" fn zkp_buffer_free(buffer: ZkpBuffer) {
+    if !buffer.data.is_null() {
+        drop(Box::from_raw(ptr::slice_from_raw_parts_mut(
+            buffer.data,
+            buffer.len,
+        )));
+    }
+}
+
+/// Draws a uniformly random scalar from `[0, q)` from the operating system generator, e.g. a
+/// secret `x` or the nonce `k` of [`zkp_commitments`]. Use a fresh nonce for every login.
+///
+/// # Safety
+///
+/// `ctx` must be a live context and `out` valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn zkp_random_scalar(ctx: *const ZkpContext, out: *mut i64) -> ZkpStatus {
+    guard(|| {
+        let scalar = context(ctx)?.protocol.ctx().try_random_scalar(&mut OsRng)?;
+        write(out, scalar)
+    })
+}
+
+/// Calculates the encoded public keys `y_i = g_i^x`.
+///
+/// # Safety
+///
+/// `ctx` must be a live context and `out` valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn zkp_registration_data(
+    ctx: *const ZkpContext,
+    x: i64,
+    out: *mut ZkpBuffer,
+) -> ZkpStatus {
+    guard(|| {
+        let ys = context(ctx)?
+            .protocol
+            .calculate_multi_registration_data(x)?;
+        write(out, ZkpBuffer::from_vec(encode_scalars(&ys)))
+    })
+}
+
+/// Calculates the encoded commitments `r_i = g_i^k` to a nonce from [`zkp_random_scalar`].
+///
+/// # Safety
+///
+/// `ctx` must be a live context and `out` valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn zkp_commitments(
+    ctx: *const ZkpContext,
+    k: i64,
+    out: *mut ZkpBuffer,
+) -> ZkpStatus {
+    guard(|| {
+        let rs = context(ctx)?
+            .protocol
+            .calculate_multi_login_challenge_data(k)?;
+        write(out, ZkpBuffer::from_vec(encode_scalars(&rs)))
+    })
+}
+
+/// Calculates the response `s = k - c * x` to the verifier challenge.
+///
+/// # Safety
+///
+/// `ctx` must be a live context and `out` valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn zkp_response(
+    ctx: *const ZkpContext,
+    k: i64,
+    c: i64,
+    x: i64,
+    out: *mut i64,
+) -> ZkpStatus {
+    guard(|| {
+        let s = context(ctx)?.protocol.calculate_challenge(k, c, x)?;
+        write(out, s)
+    })
+}
+
+/// Checks the response `s` to the challenge `c` against the encoded public keys and commitments.
+///
+/// # Safety
+///
+/// `ctx` must be a live context, `ys` and `rs` valid for `ys_len` and `rs_len` reads and `out`
+/// valid for writes.
+#[no_mangle]
+#[allow(clippy::too_many_arguments)]
+pub unsafe extern "C" fn zkp_verify(
+    ctx: *const ZkpContext,
+    ys: *const u8,
+    ys_len: usize,
+    rs: *const u8,
+    rs_len: usize,
+    c: i64,
+    s: i64,
+    out: *mut bool,
+) -> ZkpStatus {
+    guard(|| {
+        let ys = decode_scalars(bytes(ys, ys_len)?)?;
+        let rs = decode_scalars(bytes(rs, rs_len)?)?;
+        let valid = verified(
+            context(ctx)?
+                .protocol
+                .verify_multi_auth_challenge(&ys, &rs, c, s),
+        )?;
+        write(out, valid)
+    })
+}
+
+/// Creates an encoded non-interactive proof of `x` bound to the caller supplied `context` bytes,
+/// with a fresh nonce from the operating system generator.
+///
+/// # Safety
+///
+/// `ctx` must be a live context, `context` valid for `context_len` reads and `out` valid for
+/// writes.
+#[no_mangle]
+pub unsafe extern "C" fn zkp_prove(
+    ctx: *const ZkpContext,
+    context: *const u8,
+    context_len: usize,
+    x: i64,
+    out: *mut ZkpBuffer,
+) -> ZkpStatus {
+    guard(|| {
+        let mut transcript = proof_transcript(bytes(context, context_len)?);
+        let protocol = &self::context(ctx)?.protocol;
+        let k = protocol.ctx().try_random_scalar(&mut OsRng)?;
+        let proof = protocol.create_proof(&mut transcript, x, k)?;
+        write(out, ZkpBuffer::from_vec(proof.to_bytes().to_vec()))
+    })
+}
+
+/// Verifies an encoded proof created by [`zkp_prove`] with the same `context` bytes.
+///
+/// # Safety
+///
+/// `ctx` must be a live context, `context`, `ys` and `proof` valid for reads of their lengths and
+/// `out` valid for writes.
+#[no_mangle]
+#[allow(clippy::too_many_arguments)]
+pub unsafe extern "C" fn zkp_verify_proof(
+    ctx: *const ZkpContext,
+    context: *const u8,
+    context_len: usize,
+    ys: *const u8,
+    ys_len: usize,
+    proof: *const u8,
+    proof_len: usize,
+    out: *mut bool,
+) -> ZkpStatus {
+    guard(|| {
+        let mut transcript = proof_transcript(bytes(context, context_len)?);
+        let ys = decode_scalars(bytes(ys, ys_len)?)?;
+        let proof = Proof::from_bytes(bytes(proof, proof_len)?)?;
+        let valid = verified(self::context(ctx)?.protocol.verify_proof(
+            &mut transcript,
+            &ys,
+            &proof,
+        ))?;
+        write(out, valid)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BASES: [i64; 2] = [4, 9];
+    const ZKP_P: i64 = 20123;
+    const ZKP_Q: i64 = 10061;
+
+    fn init() -> *mut ZkpContext {
+        let mut ctx = ptr::null_mut();
+        let status =
+            unsafe { zkp_context_new(BASES.as_ptr(), BASES.len(), ZKP_P, ZKP_Q, &mut ctx) };
+        assert_eq!(status, ZkpStatus::Ok);
+        ctx
+    }
+
+    fn to_vec(buffer: ZkpBuffer) -> Vec<u8> {
+        let bytes = unsafe { slice::from_raw_parts(buffer.data, buffer.len) }.to_vec();
+        unsafe { zkp_buffer_free(buffer) };
+        bytes
+    }
+
+    #[test]
+    fn should_round_trip_scalars() {
+        let values = [0, 1, -1, i64::MAX, i64::MIN];
+        assert_eq!(decode_scalars(&encode_scalars(&values)).unwrap(), values);
+    }
+
+    #[test]
+    fn should_reject_malformed_encodings() {
+        assert_eq!(decode_scalars(&[0; 7]), Err(ZkpStatus::MalformedEncoding));
+        assert_eq!(
+            Proof::from_bytes(&[0; 24]).map_err(ZkpStatus::from),
+            Err(ZkpStatus::MalformedEncoding)
+        );
+    }
+
+    #[test]
+    fn should_map_context_errors() {
+        let mut ctx = ptr::null_mut();
+        let status = unsafe { zkp_context_new(ptr::null(), 0, ZKP_P, ZKP_Q, &mut ctx) };
+        assert_eq!(status, ZkpStatus::EmptyBases);
+        let status = unsafe { zkp_context_new(BASES.as_ptr(), BASES.len(), 1, ZKP_Q, &mut ctx) };
+        assert_eq!(status, ZkpStatus::InvalidParameters);
+        assert!(ctx.is_null());
+    }
+
+    #[test]
+    fn should_reject_null_pointers() {
+        let ctx = init();
+        let status = unsafe { zkp_response(ctx, 1, 2, 3, ptr::null_mut()) };
+        assert_eq!(status, ZkpStatus::NullPointer);
+        let mut s = 0;
+        let status = unsafe { zkp_response(ptr::null(), 1, 2, 3, &mut s) };
+        assert_eq!(status, ZkpStatus::NullPointer);
+        unsafe { zkp_context_free(ctx) };
+    }
+
+    #[test]
+    fn should_draw_random_scalars_in_range() {
+        let ctx = init();
+        let scalars: Vec<i64> = (0..32)
+            .map(|_| {
+                let mut k = -1;
+                assert_eq!(unsafe { zkp_random_scalar(ctx, &mut k) }, ZkpStatus::Ok);
+                k
+            })
+            .collect();
+        assert!(scalars.iter().all(|k| (0..ZKP_Q).contains(k)));
+        assert!(scalars.iter().any(|k| *k != scalars[0]));
+        unsafe { zkp_context_free(ctx) };
+    }
+
+    #[test]
+    fn should_accept_valid_proof() {
+        let ctx = init();
+        let mut ys = ZkpBuffer {
+            data: ptr::null_mut(),
+            len: 0,
+        };
+        let mut proof = ZkpBuffer {
+            data: ptr::null_mut(),
+            len: 0,
+        };
+        let context = b"bidzyyys";
+        unsafe {
+            assert_eq!(zkp_registration_data(ctx, 9, &mut ys), ZkpStatus::Ok);
+            assert_eq!(
+                zkp_prove(ctx, context.as_ptr(), context.len(), 9, &mut proof),
+                ZkpStatus::Ok
+            );
+        }
+        let (ys, proof) = (to_vec(ys), to_vec(proof));
+
+        let mut valid = false;
+        let status = unsafe {
+            zkp_verify_proof(
+                ctx,
+                context.as_ptr(),
+                context.len(),
+                ys.as_ptr(),
+                ys.len(),
+                proof.as_ptr(),
+                proof.len(),
+                &mut valid,
+            )
+        };
+        assert_eq!(status, ZkpStatus::Ok);
+        assert!(valid);
+        unsafe { zkp_context_free(ctx) };
+    }
+}
@@ -0,0 +1,89 @@
+#include <assert.h>
+#include <stdbool.h>
+#include <stdint.h>
+#include <stdio.h>
+#include <string.h>
+
+#include "zkp.h"
+
+static const int64_t BASES[] = {4, 9};
+static const int64_t ZKP_P = 20123;
+static const int64_t ZKP_Q = 10061;
+static const int64_t SECRET = 9;
+static const int64_t CHALLENGE = 2;
+
+static void test_interactive_protocol(const ZkpContext *ctx) {
+    ZkpBuffer ys, rs;
+    int64_t k, s;
+    bool valid = false;
+
+    assert(zkp_registration_data(ctx, SECRET, &ys) == ZKP_STATUS_OK);
+    assert(ys.len == 2 * sizeof(int64_t));
+    assert(zkp_random_scalar(ctx, &k) == ZKP_STATUS_OK);
+    assert(k >= 0 && k < ZKP_Q);
+    assert(zkp_commitments(ctx, k, &rs) == ZKP_STATUS_OK);
+
+    assert(zkp_response(ctx, k, CHALLENGE, SECRET, &s) == ZKP_STATUS_OK);
+    assert(zkp_verify(ctx, ys.data, ys.len, rs.data, rs.len, CHALLENGE, s, &valid) ==
+           ZKP_STATUS_OK);
+    assert(valid);
+
+    assert(zkp_response(ctx, k, CHALLENGE, SECRET + 1, &s) == ZKP_STATUS_OK);
+    assert(zkp_verify(ctx, ys.data, ys.len, rs.data, rs.len, CHALLENGE, s, &valid) ==
+           ZKP_STATUS_OK);
+    assert(!valid);
+
+    assert(zkp_verify(ctx, ys.data, ys.len - 1, rs.data, rs.len, CHALLENGE, s, &valid) ==
+           ZKP_STATUS_MALFORMED_ENCODING);
+    assert(zkp_verify(ctx, ys.data, ys.len / 2, rs.data, rs.len, CHALLENGE, s, &valid) ==
+           ZKP_STATUS_LENGTH_MISMATCH);
+
+    zkp_buffer_free(ys);
+    zkp_buffer_free(rs);
+}
+
+static void test_non_interactive_proof(const ZkpContext *ctx) {
+    const char *context = "bidzyyys";
+    const char *other_context = "mallory";
+    ZkpBuffer ys, proof;
+    bool valid = false;
+
+    assert(zkp_registration_data(ctx, SECRET, &ys) == ZKP_STATUS_OK);
+    assert(zkp_prove(ctx, (const uint8_t *)context, strlen(context), SECRET, &proof) ==
+           ZKP_STATUS_OK);
+    assert(proof.len == 2 * sizeof(int64_t));
+
+    assert(zkp_verify_proof(ctx, (const uint8_t *)context, strlen(context), ys.data, ys.len,
+                            proof.data, proof.len, &valid) == ZKP_STATUS_OK);
+    assert(valid);
+
+    assert(zkp_verify_proof(ctx, (const uint8_t *)other_context, strlen(other_context), ys.data,
+                            ys.len, proof.data, proof.len, &valid) == ZKP_STATUS_OK);
+    assert(!valid);
+
+    zkp_buffer_free(ys);
+    zkp_buffer_free(proof);
+}
+
+static void test_errors(void) {
+    ZkpContext *ctx = NULL;
+
+    assert(zkp_context_new(NULL, 0, ZKP_P, ZKP_Q, &ctx) == ZKP_STATUS_EMPTY_BASES);
+    assert(zkp_context_new(BASES, 2, ZKP_P, 1, &ctx) == ZKP_STATUS_INVALID_PARAMETERS);
+    assert(zkp_context_new(BASES, 2, ZKP_P, ZKP_Q, NULL) == ZKP_STATUS_NULL_POINTER);
+    assert(zkp_random_scalar(NULL, NULL) == ZKP_STATUS_NULL_POINTER);
+    assert(ctx == NULL);
+}
+
+int main(void) {
+    ZkpContext *ctx = NULL;
+
+    assert(zkp_context_new(BASES, 2, ZKP_P, ZKP_Q, &ctx) == ZKP_STATUS_OK);
+    test_interactive_protocol(ctx);
+    test_non_interactive_proof(ctx);
+    test_errors();
+    zkp_context_free(ctx);
+
+    printf("zkp C API tests passed\n");
+    return 0;
+}
@@ -0,0 +1,39 @@
+#![cfg(target_os = "linux")]
+
+use std::{env, path::PathBuf, process::Command};
+
+/// Directory holding `libzkp_ffi.a`, the parent of the `deps` directory of this test binary.
+fn library_dir() -> PathBuf {
+    let exe = env::current_exe().expect("Unable to locate the test binary");
+    exe.parent()
+        .and_then(|deps| deps.parent())
+        .expect("Test binary outside of the target directory")
+        .to_path_buf()
+}
+
+#[test]
+fn should_pass_c_test_program() {
+    let manifest_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let binary = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("test_zkp");
+
+    let status = Command::new(env::var("CC").unwrap_or_else(|_| "cc".to_string()))
+        .arg(manifest_dir.join("tests/c/test_zkp.c"))
+        .arg("-I")
+        .arg(manifest_dir.join("include"))
+        .arg("-L")
+        .arg(library_dir())
+        .args(["-l:libzkp_ffi.a", "-lpthread", "-ldl", "-lm", "-o"])
+        .arg(&binary)
+        .status()
+        .expect("Unable to run the C compiler");
+    assert!(status.success(), "Compiling the C test program failed");
+
+    let output = Command::new(&binary)
+        .output()
+        .expect("Unable to run the C test program");
+    assert!(
+        output.status.success(),
+        "C test program failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
@@ -0,0 +1,20 @@
+use std::{env, fs, path::PathBuf};
+
+const GENERATED: &str = include_str!(concat!(env!("OUT_DIR"), "/zkp.h"));
+
+/// Fails when the committed header is out of date. `ZKP_FFI_UPDATE_HEADER=1` overwrites it with
+/// the generated one.
+#[test]
+fn should_match_generated_header() {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("include/zkp.h");
+    if env::var_os("ZKP_FFI_UPDATE_HEADER").is_some() {
+        fs::write(&path, GENERATED).expect("Unable to write the header");
+    }
+
+    let committed = fs::read_to_string(&path).expect("Unable to read the header");
+    assert!(
+        committed == GENERATED,
+        "{} is out of date, regenerate it with `ZKP_FFI_UPDATE_HEADER=1 cargo test --package zkp_ffi --test header`",
+        path.display()
+    );
+}